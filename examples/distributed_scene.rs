@@ -0,0 +1,45 @@
+#![allow(non_snake_case)]
+
+use std::net::SocketAddr;
+use Handmade3DRenderer::*;
+
+// start a few `render_worker` examples first, then
+// cargo run --release --example distributed_scene -- 127.0.0.1:7878 127.0.0.1:7879
+fn main() {
+    let workers: Vec<SocketAddr> = std::env::args()
+        .skip(1)
+        .map(|a| a.parse().expect("Invalid worker address"))
+        .collect();
+
+    let mut world = World::new();
+    world.lights.push(Light::new(
+        Point3::new(-10, 10, -10),
+        Color::new(1.0, 1.0, 1.0),
+    ));
+
+    let mut sphere = Sphere::new();
+    sphere.set_transform(Matrix::translation(-0.5, 1.0, 0.5));
+    sphere.material.color = Color::new(1.0, 0.0, 0.0);
+    sphere.material.diffuse = 0.7;
+    sphere.material.specular = 0.3;
+
+    let mut plane = Plane::new();
+    plane.material.reflective = 0.7;
+    plane.material.pattern = Some(Pattern::checkers(vec![
+        Color::new(0.41, 0.41, 0.41),
+        Color::new(0.82, 0.82, 0.82),
+    ]));
+
+    world.objects.insert(plane.get_id(), Box::new(plane));
+    world.objects.insert(sphere.get_id(), Box::new(sphere));
+
+    let mut camera = Camera::new(70 * 10, 50 * 10, consts::PI / 3.0);
+    camera.transform = Matrix::view_transformation(
+        Point3::new(0.0, 1.5, -5.0),
+        Point3::new(0, 1, 0),
+        Vector3::new(0, 1, 0),
+    );
+    let canvas = render_distributed(&camera, &world, &TileSettings::default(), &workers)
+        .expect("Distributed render failed");
+    canvas.save_as_ppm("distributed_scene.ppm");
+}
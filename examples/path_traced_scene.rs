@@ -0,0 +1,44 @@
+#![allow(non_snake_case)]
+
+use Handmade3DRenderer::*;
+
+fn main() {
+    let mut world = World::new();
+    world.lights.push(Light::new(
+        Point3::new(-10, 10, -10),
+        Color::new(0.8, 0.8, 0.8),
+    ));
+
+    let mut floor = Plane::new();
+    floor.material.color = Color::new(0.9, 0.9, 0.9);
+
+    let mut wall = Plane::new();
+    wall.set_transform(Matrix::translation(0.0, 0.0, 3.0) * &Matrix::rotation_x(consts::PI / 2.0));
+    wall.material.color = Color::new(0.9, 0.2, 0.2);
+
+    let mut sphere = Sphere::new();
+    sphere.set_transform(Matrix::translation(-0.5, 1.0, 0.5));
+    sphere.material.color = Color::new(0.2, 0.9, 0.2);
+
+    let mut lamp = Sphere::new();
+    lamp.set_transform(Matrix::translation(1.5, 0.5, -0.5) * &Matrix::scaling(0.5, 0.5, 0.5));
+    lamp.material.emissive = Color::new(4.0, 3.5, 2.0);
+
+    world.objects.insert(floor.get_id(), Box::new(floor));
+    world.objects.insert(wall.get_id(), Box::new(wall));
+    world.objects.insert(sphere.get_id(), Box::new(sphere));
+    world.objects.insert(lamp.get_id(), Box::new(lamp));
+
+    let mut camera = Camera::new(70 * 5, 50 * 5, consts::PI / 3.0);
+    camera.transform = Matrix::view_transformation(
+        Point3::new(0.0, 1.5, -5.0),
+        Point3::new(0, 1, 0),
+        Vector3::new(0, 1, 0),
+    );
+    camera.integrator = Integrator::PathTracer {
+        samples: 64,
+        max_depth: 8,
+    };
+    let canvas = camera.render(&world);
+    canvas.save_as_ppm("path_traced_scene.ppm");
+}
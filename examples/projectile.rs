@@ -3,13 +3,13 @@
 use Handmade3DRenderer::*;
 
 struct Projectile {
-    position: Vec3,
-    velocity: Vec3,
+    position: Point3,
+    velocity: Vector3,
 }
 
 struct Environment {
-    gravity: Vec3,
-    wind: Vec3,
+    gravity: Vector3,
+    wind: Vector3,
 }
 
 fn tick(e: &Environment, p: Projectile) -> Projectile {
@@ -22,12 +22,12 @@ fn tick(e: &Environment, p: Projectile) -> Projectile {
 fn main() {
     let velocity_scaling_factor = 11.25;
     let mut p = Projectile {
-        position: Vec3::point(0, 1, 0),
-        velocity: Vec3::vector(1.0, 1.8, 0.0).normalize() * velocity_scaling_factor,
+        position: Point3::new(0, 1, 0),
+        velocity: Vector3::new(1.0, 1.8, 0.0).normalize() * velocity_scaling_factor,
     };
     let e = Environment {
-        gravity: Vec3::vector(0.0, -0.1, 0.0),
-        wind: Vec3::vector(-0.01, 0.0, 0.0),
+        gravity: Vector3::new(0.0, -0.1, 0.0),
+        wind: Vector3::new(-0.01, 0.0, 0.0),
     };
 
     let mut canvas = Canvas::new(900, 550);
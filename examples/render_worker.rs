@@ -0,0 +1,14 @@
+#![allow(non_snake_case)]
+
+use std::net::TcpListener;
+use Handmade3DRenderer::*;
+
+// cargo run --release --example render_worker -- 127.0.0.1:7878
+fn main() {
+    let address = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "127.0.0.1:7878".to_string());
+    let listener = TcpListener::bind(&address).expect("Unable to bind worker address");
+    println!("worker listening on {}", address);
+    run_worker(&listener).expect("Worker stopped");
+}
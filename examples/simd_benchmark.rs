@@ -0,0 +1,55 @@
+#![allow(non_snake_case)]
+
+// cargo run --release --example simd_benchmark
+
+use std::hint::black_box;
+use std::time::Instant;
+use Handmade3DRenderer::*;
+
+const ROUNDS: u32 = 20;
+
+fn main() {
+    let world = World::default();
+    let mut camera = Camera::new(200, 200, consts::PI / 3.0);
+    camera.transform = Matrix::view_transformation(
+        Point3::new(0, 0, -5),
+        Point3::new(0, 0, 0),
+        Vector3::new(0, 1, 0),
+    );
+    let rays: Vec<Ray> = (0..camera.vsize)
+        .flat_map(|py| (0..camera.hsize).map(move |px| (px, py)))
+        .map(|(px, py)| camera.ray_for_pixel(px, py))
+        .collect();
+    let n_rays = rays.len() as f64 * ROUNDS as f64;
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for ray in &rays {
+            black_box(world.color_at(ray));
+        }
+    }
+    let single = start.elapsed().as_secs_f64();
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for rays in rays.chunks_exact(LANES) {
+            let rays = [rays[0], rays[1], rays[2], rays[3]];
+            black_box(world.color_at_packet(&RayPacket::new(&rays)));
+        }
+    }
+    let packets = start.elapsed().as_secs_f64();
+
+    println!("single rays: {:>12.0} rays/s", n_rays / single);
+    println!("ray packets: {:>12.0} rays/s", n_rays / packets);
+    println!("speedup:     {:>12.2}x", single / packets);
+
+    let a = Matrix::rotation_y(0.5) * &Matrix::scaling(1.0, 2.0, 3.0);
+    let b = Matrix::translation(1.0, 2.0, 3.0) * &Matrix::rotation_x(0.25);
+    let n_products = 1_000_000;
+    let start = Instant::now();
+    for _ in 0..n_products {
+        black_box(black_box(&a) * black_box(&b));
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+    println!("matrix products: {:>8.0} /s", n_products as f64 / elapsed);
+}
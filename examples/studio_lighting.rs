@@ -0,0 +1,51 @@
+#![allow(non_snake_case)]
+
+use std::path::Path;
+use Handmade3DRenderer::*;
+
+// cargo run --release --example studio_lighting -- studio.hdr
+fn main() {
+    let mut world = World::new();
+    world.background = match std::env::args().nth(1) {
+        Some(path) => {
+            let image = Canvas::load_hdr(Path::new(&path)).expect("Unable to load environment");
+            Background::Environment(EnvironmentMap::new(image))
+        }
+        None => Background::Gradient {
+            bottom: Color::new(0.3, 0.3, 0.3),
+            top: Color::new(0.6, 0.7, 1.0),
+        },
+    };
+
+    let mut floor = Plane::new();
+    floor.material.color = Color::new(0.8, 0.8, 0.8);
+    floor.material.ambient = 0.0;
+    floor.material.specular = 0.0;
+
+    let mut gold = Sphere::new();
+    gold.set_transform(Matrix::translation(-1.1, 1.0, 0.0));
+    gold.material = Material::metallic_roughness(Color::new(1.0, 0.78, 0.34), 1.0, 0.3);
+    gold.material.ambient = 0.0;
+
+    let mut plastic = Sphere::new();
+    plastic.set_transform(Matrix::translation(1.1, 1.0, 0.0));
+    plastic.material = Material::metallic_roughness(Color::new(0.1, 0.3, 0.8), 0.0, 0.2);
+    plastic.material.ambient = 0.0;
+
+    world.objects.insert(floor.get_id(), Box::new(floor));
+    world.objects.insert(gold.get_id(), Box::new(gold));
+    world.objects.insert(plastic.get_id(), Box::new(plastic));
+
+    let mut camera = Camera::new(64 * 5, 36 * 5, consts::PI / 3.0);
+    camera.transform = Matrix::view_transformation(
+        Point3::new(0.0, 1.5, -5.0),
+        Point3::new(0, 1, 0),
+        Vector3::new(0, 1, 0),
+    );
+    camera.integrator = Integrator::PathTracer {
+        samples: 32,
+        max_depth: 6,
+    };
+    let canvas = camera.render(&world);
+    canvas.save_as_ppm("studio_lighting.ppm");
+}
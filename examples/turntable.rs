@@ -0,0 +1,77 @@
+#![allow(non_snake_case)]
+
+use consts::PI;
+use std::path::Path;
+use Handmade3DRenderer::*;
+
+fn main() {
+    let mut world = World::new();
+    world.lights.push(Light::new(
+        Point3::new(-10, 10, -10),
+        Color::new(1.0, 1.0, 1.0),
+    ));
+
+    let mut floor = Plane::new();
+    floor.material.pattern = Some(Pattern::checkers(vec![
+        Color::new(0.41, 0.41, 0.41),
+        Color::new(0.82, 0.82, 0.82),
+    ]));
+
+    let mut block = Cube::new();
+    block.material.color = Color::new(0.1, 0.6, 1.0);
+    block.material.diffuse = 0.7;
+    block.material.specular = 0.3;
+
+    let mut ball = Sphere::new();
+    ball.material.color = Color::new(1.0, 0.2, 0.1);
+
+    let mut animation = Animation::new();
+    // the block spins once while the ball bounces over it
+    let mut spin = TransformTrack::new();
+    spin.translation = Track::constant(Vector3::new(0, 1, 0));
+    // a quarter turn between keyframes, a full turn would look like none
+    spin.rotation = Track::new(
+        (0..=4)
+            .map(|i| {
+                let angle = i as Float * PI / 2.0;
+                (
+                    i as Float * 12.0,
+                    Quaternion::from_axis_angle(Vector3::new(0, 1, 0), angle),
+                )
+            })
+            .collect(),
+        Interpolation::Linear,
+    );
+    animation.objects.push((block.get_id(), spin));
+    let mut bounce = TransformTrack::new();
+    bounce.translation = Track::new(
+        vec![
+            (0.0, Vector3::new(-2.5, 1.0, 0.0)),
+            (24.0, Vector3::new(0.0, 4.0, 0.0)),
+            (48.0, Vector3::new(2.5, 1.0, 0.0)),
+        ],
+        Interpolation::CatmullRom,
+    );
+    animation.objects.push((ball.get_id(), bounce));
+    animation.materials.push((
+        ball.get_id(),
+        MaterialTrack::Color(Track::new(
+            vec![
+                (0.0, Color::new(1.0, 0.2, 0.1)),
+                (48.0, Color::new(1.0, 0.9, 0.1)),
+            ],
+            Interpolation::Ease,
+        )),
+    ));
+    // and the camera circles the scene once
+    animation.rig = Some(CameraRig::orbit(Point3::new(0, 1, 0), 9.0, 3.0, 48.0));
+
+    world.objects.insert(floor.get_id(), Box::new(floor));
+    world.objects.insert(block.get_id(), Box::new(block));
+    world.objects.insert(ball.get_id(), Box::new(ball));
+
+    let mut camera = Camera::new(320, 200, PI / 3.0);
+    animation
+        .render_frames(&mut world, &mut camera, 0..=48, Path::new("turntable"))
+        .expect("Unable to write frames");
+}
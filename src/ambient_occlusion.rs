@@ -0,0 +1,181 @@
+use super::color::*;
+use super::error::*;
+use super::float_cmp::{Float, HIT_TOLERANCE};
+use super::object::RayKind;
+use super::ray::Ray;
+use super::sampling::*;
+use super::vec3::{Point3, Vector3};
+use super::world::*;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AmbientOcclusion {
+    // hemisphere rays traced per shaded point
+    pub samples: u32,
+    // objects further away than this don't occlude
+    pub max_distance: Float,
+}
+
+impl Default for AmbientOcclusion {
+    fn default() -> Self {
+        Self {
+            samples: 16,
+            max_distance: 1.0,
+        }
+    }
+}
+
+impl AmbientOcclusion {
+    pub fn new(samples: u32, max_distance: Float) -> Self {
+        Self::try_new(samples, max_distance).unwrap_or_else(|error| panic!("{}", error))
+    }
+    // fails without samples or with a distance that isn't positive
+    pub fn try_new(samples: u32, max_distance: Float) -> Result<Self> {
+        if samples == 0 {
+            return Err(Error::ValueOutOfRange {
+                name: "ambient occlusion samples",
+                value: 0.0,
+            });
+        }
+        Ok(Self {
+            samples,
+            max_distance: check_positive("ambient occlusion distance", max_distance)?,
+        })
+    }
+}
+
+impl World {
+    // Fraction of the cosine weighted hemisphere above `point` that is open,
+    // 1 on a lone surface and 0 deep inside a crease. `point` should already be
+    // offset from the surface like `State::over_point`, `time` is that of the
+    // ray reaching it.
+    pub fn occlusion(
+        &self,
+        point: Point3,
+        normal: Vector3,
+        time: Float,
+        settings: &AmbientOcclusion,
+    ) -> Float {
+        let n = settings.samples.max(1);
+        let mut rng = point_rng(point, 3);
+        let blocked = (0..n)
+            .filter(|_| {
+                let ray = Ray::new(point, cosine_hemisphere(normal, &mut rng)).with_time(time);
+                self.is_blocked(&ray, settings.max_distance)
+            })
+            .count();
+        1.0 - blocked as Float / n as Float
+    }
+    // Grayscale ambient occlusion of the surface seen along `ray`, white where it
+    // leaves the scene. Uses the world settings or the defaults if AO is disabled.
+    pub fn occlusion_color(&self, ray: &Ray) -> Color {
+        match self.closest_hit(ray, RayKind::Camera, -HIT_TOLERANCE, Float::INFINITY) {
+            Some(hit) => {
+                let state = hit.compute_state(ray, self);
+                let settings = self.ambient_occlusion.unwrap_or_default();
+                WHITE * self.occlusion(state.over_point, state.normalv, state.time, &settings)
+            }
+            None => WHITE,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::camera::Camera;
+    use super::super::float_cmp::consts::FRAC_PI_2;
+    use super::super::light::Light;
+    use super::super::matrix::Matrix;
+    use super::super::object::Object;
+    use super::super::path_tracer::Integrator;
+    use super::super::plane::Plane;
+    use super::*;
+
+    // floor with a wall standing on it at z = 1
+    fn corner() -> World {
+        let mut world = World::new();
+        world
+            .lights
+            .push(Light::new(Point3::new(0, 10, -10), WHITE));
+        let floor = Plane::new();
+        world.objects.insert(floor.get_id(), Box::new(floor));
+        let mut wall = Plane::new();
+        wall.set_transform(Matrix::translation(0.0, 0.0, 1.0) * &Matrix::rotation_x(FRAC_PI_2));
+        world.objects.insert(wall.get_id(), Box::new(wall));
+        world
+    }
+    #[test]
+    fn open_surface_is_not_occluded() {
+        let world = corner();
+        let settings = AmbientOcclusion::new(64, 1.0);
+        let up = Vector3::new(0, 1, 0);
+        assert_eq!(
+            world.occlusion(Point3::new(0.0, 0.001, -5.0), up, 0.0, &settings),
+            1.0
+        );
+    }
+    #[test]
+    fn corner_is_half_occluded() {
+        let world = corner();
+        let up = Vector3::new(0, 1, 0);
+        let point = Point3::new(0.0, 0.001, 0.999);
+        let near = world.occlusion(point, up, 0.0, &AmbientOcclusion::new(1024, 100.0));
+        assert!((near - 0.5).abs() < 0.05);
+        // only the wall right next to the point is within reach
+        let short = world.occlusion(point, up, 0.0, &AmbientOcclusion::new(1024, 0.01));
+        assert!(short > near && short < 1.0);
+    }
+    #[test]
+    fn occlusion_darkens_ambient_only() {
+        let mut world = corner();
+        let ray = Ray::new(
+            Point3::new(0.0, 1.0, 0.0),
+            Vector3::new(0.0, -1.0, 0.999).normalize(),
+        );
+        let xs = world.intersect_with(&ray);
+        let state = xs.hit().unwrap().compute_state(&ray, &world);
+        let plain = world.shade_hit(&state);
+        world.ambient_occlusion = Some(AmbientOcclusion::new(256, 10.0));
+        let occluded = world.shade_hit(&state);
+        // the default material has ambient 0.1, about half of it is blocked
+        assert!((plain.red - occluded.red - 0.05).abs() < 0.01);
+    }
+    #[test]
+    #[should_panic]
+    fn no_samples() {
+        AmbientOcclusion::new(0, 1.0);
+    }
+    #[test]
+    fn invalid_settings() {
+        assert!(AmbientOcclusion::try_new(0, 1.0).is_err());
+        assert_eq!(
+            AmbientOcclusion::try_new(8, -1.0),
+            Err(Error::ValueOutOfRange {
+                name: "ambient occlusion distance",
+                value: -1.0
+            })
+        );
+        assert!(AmbientOcclusion::try_new(8, 1.0).is_ok());
+    }
+    #[test]
+    fn render_occlusion_only() {
+        let mut world = corner();
+        world.ambient_occlusion = Some(AmbientOcclusion::new(64, 1.0));
+        let mut camera = Camera::new(9, 9, FRAC_PI_2);
+        camera.transform = Matrix::view_transformation(
+            Point3::new(0, 1, -2),
+            Point3::new(0, 1, 0),
+            Vector3::new(0, 1, 0),
+        );
+        camera.integrator = Integrator::AmbientOcclusion;
+        let canvas = camera.render(&world);
+        for pixel in &canvas.grid {
+            assert_eq!(pixel.red, pixel.green);
+            assert_eq!(pixel.red, pixel.blue);
+        }
+        // the wall just above the floor is darker than further up
+        let wall = canvas.pixel_at(0, 4);
+        let crease = canvas.pixel_at(5, 4);
+        assert_eq!(wall, WHITE);
+        assert!(crease.red < 0.9);
+    }
+}
@@ -0,0 +1,613 @@
+use super::camera::Camera;
+use super::camera_rig::CameraRig;
+use super::color::Color;
+use super::error::*;
+use super::float_cmp::Float;
+use super::material::Material;
+use super::matrix::Matrix;
+use super::motion::Motion;
+use super::object::transform_inverse;
+use super::quaternion::Quaternion;
+use super::vec3::{Point3, Vector3};
+use super::world::World;
+use std::fs;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+// how a track moves from one keyframe to the next
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Interpolation {
+    Linear,
+    // starts and stops slowly at every keyframe
+    Ease,
+    // smooth curve through all keyframes, overshooting a little at sharp turns
+    CatmullRom,
+}
+
+// values a track can blend between keyframes
+pub trait Animatable: Copy {
+    // sum of the values scaled by their weights, the weights add up to 1
+    fn weighted_sum(terms: &[(Self, Float)]) -> Self;
+    // blend from `a` at 0 to `b` at 1
+    fn blend(a: Self, b: Self, s: Float) -> Self {
+        Self::weighted_sum(&[(a, 1.0 - s), (b, s)])
+    }
+}
+
+impl Animatable for Float {
+    fn weighted_sum(terms: &[(Self, Float)]) -> Self {
+        terms.iter().map(|(value, weight)| value * weight).sum()
+    }
+}
+
+impl Animatable for Color {
+    fn weighted_sum(terms: &[(Self, Float)]) -> Self {
+        let mut sum = Self::new(0.0, 0.0, 0.0);
+        for (value, weight) in terms {
+            sum = sum + *value * *weight;
+        }
+        sum
+    }
+}
+
+// the weights of a track add up to one, so the sum of points is a point again
+impl Animatable for Point3 {
+    fn weighted_sum(terms: &[(Self, Float)]) -> Self {
+        let mut sum = Self::origin();
+        for (value, weight) in terms {
+            sum.x += value.x * weight;
+            sum.y += value.y * weight;
+            sum.z += value.z * weight;
+        }
+        sum
+    }
+}
+
+impl Animatable for Vector3 {
+    fn weighted_sum(terms: &[(Self, Float)]) -> Self {
+        let mut sum = Self::new(0, 0, 0);
+        for (value, weight) in terms {
+            sum = sum + *value * *weight;
+        }
+        sum
+    }
+}
+
+// Rotations blend with `Quaternion::slerp`, the weighted sum of more than two
+// rotations is normalized and only approximately on the shortest arc.
+impl Animatable for Quaternion {
+    fn weighted_sum(terms: &[(Self, Float)]) -> Self {
+        let mut previous = terms[0].0;
+        let mut sum = Self::new(0.0, 0.0, 0.0, 0.0);
+        for (value, weight) in terms {
+            // q and -q are the same rotation, pick the one closer to the last
+            let value = if value.dot(&previous) < 0.0 {
+                -*value
+            } else {
+                *value
+            };
+            previous = value;
+            sum.w += value.w * weight;
+            sum.x += value.x * weight;
+            sum.y += value.y * weight;
+            sum.z += value.z * weight;
+        }
+        sum.normalize()
+    }
+    fn blend(a: Self, b: Self, s: Float) -> Self {
+        a.slerp(&b, s)
+    }
+}
+
+// Value changing over time, given as keyframes of (frame, value). Before the
+// first and after the last keyframe the value is held.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track<T> {
+    keys: Vec<(Float, T)>,
+    pub interpolation: Interpolation,
+}
+
+impl<T: Animatable> Track<T> {
+    pub fn new(keys: Vec<(Float, T)>, interpolation: Interpolation) -> Self {
+        Self::try_new(keys, interpolation).unwrap_or_else(|error| panic!("{}", error))
+    }
+    // fails if there are no keyframes or their times aren't increasing
+    pub fn try_new(keys: Vec<(Float, T)>, interpolation: Interpolation) -> Result<Self> {
+        check_keyframes("track needs at least one keyframe", &keys)?;
+        Ok(Self {
+            keys,
+            interpolation,
+        })
+    }
+    pub fn constant(value: T) -> Self {
+        Self::new(vec![(0.0, value)], Interpolation::Linear)
+    }
+    pub fn keys(&self) -> &[(Float, T)] {
+        &self.keys
+    }
+    pub fn value_at(&self, frame: Float) -> T {
+        let next = self.keys.partition_point(|(t, _)| *t <= frame);
+        if next == 0 {
+            return self.keys[0].1;
+        }
+        if next == self.keys.len() {
+            return self.keys[next - 1].1;
+        }
+        let (t0, v0) = self.keys[next - 1];
+        let (t1, v1) = self.keys[next];
+        let s = (frame - t0) / (t1 - t0);
+        match self.interpolation {
+            Interpolation::Linear => T::blend(v0, v1, s),
+            Interpolation::Ease => T::blend(v0, v1, s * s * (3.0 - 2.0 * s)),
+            Interpolation::CatmullRom => {
+                // the end keyframes stand in for their missing neighbours
+                let before = self.keys[next.saturating_sub(2)].1;
+                let after = self.keys[(next + 1).min(self.keys.len() - 1)].1;
+                let s2 = s * s;
+                let s3 = s2 * s;
+                T::weighted_sum(&[
+                    (before, (-s3 + 2.0 * s2 - s) / 2.0),
+                    (v0, (3.0 * s3 - 5.0 * s2 + 2.0) / 2.0),
+                    (v1, (-3.0 * s3 + 4.0 * s2 + s) / 2.0),
+                    (after, (s3 - s2) / 2.0),
+                ])
+            }
+        }
+    }
+}
+
+// keyframed parameters of `Matrix::view_transformation`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraTrack {
+    pub from: Track<Point3>,
+    pub to: Track<Point3>,
+    pub up: Track<Vector3>,
+}
+
+impl CameraTrack {
+    pub fn new(from: Track<Point3>, to: Track<Point3>, up: Track<Vector3>) -> Self {
+        Self { from, to, up }
+    }
+    pub fn transform_at(&self, frame: Float) -> Matrix {
+        Matrix::view_transformation(
+            self.from.value_at(frame),
+            self.to.value_at(frame),
+            self.up.value_at(frame),
+        )
+    }
+}
+
+// Keyframed object transform, scaled first, then rotated and moved by
+// `translation` last. A rotation keyframe can't tell a full turn from none,
+// spins need keyframes less than half a turn apart.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransformTrack {
+    pub translation: Track<Vector3>,
+    pub rotation: Track<Quaternion>,
+    pub scale: Track<Vector3>,
+}
+
+impl TransformTrack {
+    pub fn new() -> Self {
+        Self {
+            translation: Track::constant(Vector3::new(0, 0, 0)),
+            rotation: Track::constant(Quaternion::identity()),
+            scale: Track::constant(Vector3::new(1, 1, 1)),
+        }
+    }
+    pub fn transform_at(&self, frame: Float) -> Matrix {
+        let translation = self.translation.value_at(frame);
+        let scale = self.scale.value_at(frame);
+        Matrix::translation(translation.x, translation.y, translation.z)
+            * &self.rotation.value_at(frame).to_matrix()
+            * &Matrix::scaling(scale.x, scale.y, scale.z)
+    }
+}
+
+impl Default for TransformTrack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// keyframed material value
+#[derive(Debug, Clone, PartialEq)]
+pub enum MaterialTrack {
+    Color(Track<Color>),
+    Emissive(Track<Color>),
+    Ambient(Track<Float>),
+    Diffuse(Track<Float>),
+    Specular(Track<Float>),
+    Shininess(Track<Float>),
+    Reflective(Track<Float>),
+    Transparency(Track<Float>),
+}
+
+impl MaterialTrack {
+    pub fn apply(&self, frame: Float, material: &mut Material) {
+        match self {
+            Self::Color(track) => material.color = track.value_at(frame),
+            Self::Emissive(track) => material.emissive = track.value_at(frame),
+            Self::Ambient(track) => material.ambient = track.value_at(frame),
+            Self::Diffuse(track) => material.diffuse = track.value_at(frame),
+            Self::Specular(track) => material.specular = track.value_at(frame),
+            Self::Shininess(track) => material.shininess = track.value_at(frame),
+            Self::Reflective(track) => material.reflective = track.value_at(frame),
+            Self::Transparency(track) => material.transparency = track.value_at(frame),
+        }
+    }
+}
+
+// Tracks moving a scene over a range of frames. Objects are referred to by
+// id and lights by their index in `World::lights`. Everything without a
+// track keeps the state it was set up with.
+#[derive(Debug, Clone, Default)]
+pub struct Animation {
+    pub camera: Option<CameraTrack>,
+    // moves the camera instead of `camera`, after the objects are posed
+    pub rig: Option<CameraRig>,
+    pub objects: Vec<(usize, TransformTrack)>,
+    pub materials: Vec<(usize, MaterialTrack)>,
+    pub lights: Vec<(usize, Track<Point3>)>,
+}
+
+impl Animation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    // Poses the scene at `frame`. When the camera shutter is open, animated
+    // objects get a `Motion` from `frame + shutter_open` to
+    // `frame + shutter_close` so fast objects blur. Fails without changing
+    // anything if a track refers to an object or light missing from `world`
+    // or poses an object with a transform that can't be inverted.
+    pub fn apply(&self, frame: Float, world: &mut World, camera: &mut Camera) -> Result<()> {
+        let obj_ids = self.objects.iter().map(|(obj_id, _)| obj_id);
+        let mut obj_ids = obj_ids.chain(self.materials.iter().map(|(obj_id, _)| obj_id));
+        if let Some(obj_id) = obj_ids.find(|obj_id| !world.objects.contains_key(obj_id)) {
+            return Err(Error::UnknownObject(*obj_id));
+        }
+        if let Some(CameraRig::LookAt { obj_id, .. }) = &self.rig {
+            if !world.objects.contains_key(obj_id) {
+                return Err(Error::UnknownObject(*obj_id));
+            }
+        }
+        let mut light_indices = self.lights.iter().map(|(light_index, _)| *light_index);
+        if let Some(light_index) = light_indices.find(|index| *index >= world.lights.len()) {
+            return Err(Error::UnknownLight(light_index));
+        }
+
+        // every pose is built before the first change to the scene
+        let blur = camera.shutter_close > camera.shutter_open;
+        let mut poses = Vec::with_capacity(self.objects.len());
+        for (obj_id, track) in &self.objects {
+            let transform = track.transform_at(frame);
+            transform_inverse(&transform)?;
+            let motion = if blur {
+                Some(Motion::try_new(vec![
+                    (
+                        camera.shutter_open,
+                        track.transform_at(frame + camera.shutter_open),
+                    ),
+                    (
+                        camera.shutter_close,
+                        track.transform_at(frame + camera.shutter_close),
+                    ),
+                ])?)
+            } else {
+                None
+            };
+            poses.push((obj_id, transform, motion));
+        }
+
+        if let Some(track) = &self.camera {
+            camera.transform = track.transform_at(frame);
+        }
+        // the ids and transforms were checked above
+        for (obj_id, transform, motion) in poses {
+            let object = world.objects.get_mut(obj_id).unwrap();
+            object.set_transform(transform);
+            *object.mut_motion() = motion;
+        }
+        for (obj_id, track) in &self.materials {
+            track.apply(frame, world.objects.get_mut(obj_id).unwrap().mut_material());
+        }
+        for (light_index, track) in &self.lights {
+            world.lights[*light_index].position = track.value_at(frame);
+        }
+        if let Some(rig) = &self.rig {
+            camera.transform = rig.transform_at(world, frame)?;
+        }
+        Ok(())
+    }
+    // Renders every frame of `frames` into a numbered PPM file next to `base`,
+    // see `frame_path`, and returns the paths written.
+    pub fn render_frames(
+        &self,
+        world: &mut World,
+        camera: &mut Camera,
+        frames: RangeInclusive<u32>,
+        base: &Path,
+    ) -> io::Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        for frame in frames {
+            self.apply(frame as Float, world, camera)?;
+            let path = frame_path(base, frame);
+            fs::write(&path, camera.render(world).to_ppm())?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+}
+
+// "out/turntable" becomes "out/turntable_0007.ppm" for frame 7
+pub fn frame_path(base: &Path, frame: u32) -> PathBuf {
+    let mut name = base.file_name().unwrap_or_default().to_os_string();
+    name.push(format!("_{:04}.ppm", frame));
+    base.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::float_cmp::consts::PI;
+    use super::super::light::Light;
+    use super::super::sphere::Sphere;
+    use super::*;
+
+    // directory of its own for every test and test process
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("h3d_animation_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+    fn keys() -> Vec<(Float, Float)> {
+        vec![(0.0, 0.0), (10.0, 10.0), (20.0, 0.0)]
+    }
+    #[test]
+    fn linear_track() {
+        let track = Track::new(keys(), Interpolation::Linear);
+        assert_eq!(track.value_at(5.0), 5.0);
+        assert_eq!(track.value_at(15.0), 5.0);
+        // held before and after the keyframes
+        assert_eq!(track.value_at(-3.0), 0.0);
+        assert_eq!(track.value_at(30.0), 0.0);
+    }
+    #[test]
+    fn eased_track() {
+        let track = Track::new(keys(), Interpolation::Ease);
+        assert_eq!(track.value_at(5.0), 5.0);
+        assert!(track.value_at(1.0) < 1.0);
+        assert!(track.value_at(9.0) > 9.0);
+        assert_eq!(track.value_at(10.0), 10.0);
+    }
+    #[test]
+    fn catmull_rom_track() {
+        let track = Track::new(
+            vec![(0.0, 0.0), (1.0, 1.0), (2.0, 2.0), (3.0, 3.0)],
+            Interpolation::CatmullRom,
+        );
+        // evenly spaced keyframes on a line stay on the line
+        assert!((track.value_at(1.5) - 1.5).abs() < 1.0e-6);
+        // passes through the keyframes
+        let track = Track::new(keys(), Interpolation::CatmullRom);
+        assert_eq!(track.value_at(10.0), 10.0);
+        assert!(track.value_at(9.0) > 9.0);
+    }
+    #[test]
+    #[should_panic]
+    fn track_keyframes_out_of_order() {
+        Track::new(vec![(1.0, 0.0), (0.0, 1.0)], Interpolation::Linear);
+    }
+    #[test]
+    fn invalid_track_keyframes() {
+        assert!(Track::<Float>::try_new(vec![], Interpolation::Linear).is_err());
+        assert_eq!(
+            Track::try_new(vec![(1.0, 0.0), (1.0, 1.0)], Interpolation::Linear),
+            Err(Error::ValueOutOfRange {
+                name: "keyframe time",
+                value: 1.0
+            })
+        );
+    }
+    #[test]
+    fn interpolate_points_and_colors() {
+        let track = Track::new(
+            vec![(0.0, Point3::new(0, 0, 0)), (2.0, Point3::new(2, 4, 6))],
+            Interpolation::Linear,
+        );
+        assert_eq!(track.value_at(1.0), Point3::new(1, 2, 3));
+        let track = Track::new(
+            vec![
+                (0.0, Color::new(0.0, 0.0, 1.0)),
+                (1.0, Color::new(1.0, 0.0, 0.0)),
+            ],
+            Interpolation::Linear,
+        );
+        assert_eq!(track.value_at(0.5), Color::new(0.5, 0.0, 0.5));
+    }
+    #[test]
+    fn rotation_track() {
+        let axis = Vector3::new(0, 0, 1);
+        let keys: Vec<_> = (0..4)
+            .map(|i| {
+                let angle = i as Float * PI / 3.0;
+                (i as Float, Quaternion::from_axis_angle(axis, angle))
+            })
+            .collect();
+        let track = Track::new(keys.clone(), Interpolation::Linear);
+        assert_eq!(
+            track.value_at(0.5),
+            Quaternion::from_axis_angle(axis, PI / 6.0)
+        );
+        // a steady spin stays steady along the spline
+        let track = Track::new(keys, Interpolation::CatmullRom);
+        assert_eq!(
+            track.value_at(1.5),
+            Quaternion::from_axis_angle(axis, PI / 2.0)
+        );
+    }
+    #[test]
+    fn camera_track() {
+        let track = CameraTrack::new(
+            Track::new(
+                vec![(0.0, Point3::new(0, 0, -8)), (10.0, Point3::new(0, 0, 8))],
+                Interpolation::Linear,
+            ),
+            Track::constant(Point3::new(0, 0, 0)),
+            Track::constant(Vector3::new(0, 1, 0)),
+        );
+        assert_eq!(
+            track.transform_at(10.0),
+            Matrix::view_transformation(
+                Point3::new(0, 0, 8),
+                Point3::new(0, 0, 0),
+                Vector3::new(0, 1, 0)
+            )
+        );
+    }
+    #[test]
+    fn transform_track() {
+        let mut track = TransformTrack::new();
+        assert_eq!(track.transform_at(3.0), Matrix::identity_matrix(4));
+        track.translation = Track::constant(Vector3::new(1, 0, 0));
+        track.rotation = Track::new(
+            vec![
+                (0.0, Quaternion::identity()),
+                (
+                    1.0,
+                    Quaternion::from_axis_angle(Vector3::new(0, 1, 0), PI / 4.0),
+                ),
+                (
+                    3.0,
+                    Quaternion::from_axis_angle(Vector3::new(0, 1, 0), 3.0 * PI / 4.0),
+                ),
+            ],
+            Interpolation::Linear,
+        );
+        track.scale = Track::constant(Vector3::new(2, 2, 2));
+        let p = &track.transform_at(2.0) * Point3::new(1, 0, 0);
+        assert_eq!(p, Point3::new(1, 0, -2));
+    }
+    #[test]
+    fn animate_a_scene() {
+        let mut world = World::new();
+        world
+            .lights
+            .push(Light::new(Point3::new(0, 0, 0), Color::new(1.0, 1.0, 1.0)));
+        let sphere = Sphere::new();
+        let id = sphere.get_id();
+        world.objects.insert(id, Box::new(sphere));
+        let mut camera = Camera::new(10, 10, PI / 2.0);
+        let mut animation = Animation::new();
+        let mut track = TransformTrack::new();
+        track.translation = Track::new(
+            vec![(0.0, Vector3::new(0, 0, 0)), (10.0, Vector3::new(10, 0, 0))],
+            Interpolation::Linear,
+        );
+        animation.objects.push((id, track));
+        animation.materials.push((
+            id,
+            MaterialTrack::Ambient(Track::new(
+                vec![(0.0, 0.0), (10.0, 1.0)],
+                Interpolation::Linear,
+            )),
+        ));
+        animation.lights.push((
+            0,
+            Track::new(
+                vec![(0.0, Point3::new(0, 0, 0)), (10.0, Point3::new(0, 10, 0))],
+                Interpolation::Linear,
+            ),
+        ));
+        animation.apply(5.0, &mut world, &mut camera).unwrap();
+        let object = &world.objects[&id];
+        assert_eq!(*object.transform(), Matrix::translation(5.0, 0.0, 0.0));
+        assert!(object.motion().is_none());
+        assert_eq!(object.material().ambient, 0.5);
+        assert_eq!(world.lights[0].position, Point3::new(0, 5, 0));
+        // an open shutter blurs the movement within the frame
+        camera.shutter_close = 0.5;
+        animation.apply(5.0, &mut world, &mut camera).unwrap();
+        let motion = world.objects[&id].motion().unwrap();
+        assert_eq!(motion.transform_at(0.5), Matrix::translation(5.5, 0.0, 0.0));
+    }
+    #[test]
+    fn reject_tracks_of_missing_objects_and_lights() {
+        let mut world = World::new();
+        let mut camera = Camera::new(10, 10, PI / 2.0);
+        let mut animation = Animation::new();
+        animation
+            .materials
+            .push((usize::MAX, MaterialTrack::Ambient(Track::constant(0.5))));
+        assert_eq!(
+            animation.apply(0.0, &mut world, &mut camera),
+            Err(Error::UnknownObject(usize::MAX))
+        );
+        let mut animation = Animation::new();
+        animation
+            .lights
+            .push((0, Track::constant(Point3::new(0, 0, 0))));
+        assert_eq!(
+            animation.apply(0.0, &mut world, &mut camera),
+            Err(Error::UnknownLight(0))
+        );
+        let dir = temp_dir("missing_light");
+        let base = dir.join("frame");
+        let error = animation
+            .render_frames(&mut world, &mut camera, 0..=0, &base)
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(!frame_path(&base, 0).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    #[test]
+    fn failed_apply_changes_nothing() {
+        let mut world = World::new();
+        let moved = Sphere::new();
+        let flattened = Sphere::new();
+        let (moved_id, flattened_id) = (moved.get_id(), flattened.get_id());
+        world.objects.insert(moved_id, Box::new(moved));
+        world.objects.insert(flattened_id, Box::new(flattened));
+        let mut camera = Camera::new(10, 10, PI / 2.0);
+        let mut animation = Animation::new();
+        animation.camera = Some(CameraTrack::new(
+            Track::constant(Point3::new(0, 0, -8)),
+            Track::constant(Point3::new(0, 0, 0)),
+            Track::constant(Vector3::new(0, 1, 0)),
+        ));
+        let mut track = TransformTrack::new();
+        track.translation = Track::constant(Vector3::new(1, 0, 0));
+        animation.objects.push((moved_id, track));
+        let mut track = TransformTrack::new();
+        track.scale = Track::constant(Vector3::new(1, 0, 1));
+        animation.objects.push((flattened_id, track));
+        assert_eq!(
+            animation.apply(0.0, &mut world, &mut camera),
+            Err(Error::NotInvertible)
+        );
+        assert_eq!(camera.transform, Matrix::identity_matrix(4));
+        assert_eq!(
+            *world.objects[&moved_id].transform(),
+            Matrix::identity_matrix(4)
+        );
+    }
+    #[test]
+    fn numbered_frame_files() {
+        assert_eq!(
+            frame_path(Path::new("out/turntable"), 7),
+            Path::new("out/turntable_0007.ppm")
+        );
+        let dir = temp_dir("frames");
+        let mut world = World::new();
+        let mut camera = Camera::new(4, 3, PI / 2.0);
+        let paths = Animation::new()
+            .render_frames(&mut world, &mut camera, 2..=4, &dir.join("frame"))
+            .unwrap();
+        assert_eq!(paths.len(), 3);
+        assert_eq!(paths[0], dir.join("frame_0002.ppm"));
+        let ppm = fs::read_to_string(&paths[2]).unwrap();
+        assert!(ppm.starts_with("P3\n4 3\n255\n"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
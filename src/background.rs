@@ -0,0 +1,283 @@
+use super::canvas::Canvas;
+use super::color::*;
+use super::error::*;
+use super::float_cmp::consts::PI;
+use super::float_cmp::Float;
+use super::sampling::Rng;
+use super::vec3::Vector3;
+
+// Light arriving along rays that miss every object.
+#[derive(Debug, Clone)]
+pub enum Background {
+    Solid(Color),
+    // blends from `bottom` straight down to `top` straight up
+    Gradient { bottom: Color, top: Color },
+    // latitude / longitude image of the surroundings, laid out like the
+    // images rendered with `Camera::equirectangular`
+    Environment(EnvironmentMap),
+}
+
+// Environment image prepared for importance sampling, bright parts of the
+// image are chosen in proportion to their share of the light in the scene.
+#[derive(Debug, Clone)]
+pub struct EnvironmentMap {
+    image: Canvas,
+    // cumulative weights of the rows, then of the pixels within every row
+    row_cdf: Vec<Float>,
+    pixel_cdf: Vec<Float>,
+}
+
+// direction towards the environment and the light arriving from it
+#[derive(Debug, Copy, Clone)]
+pub struct EnvironmentSample {
+    pub direction: Vector3,
+    pub radiance: Color,
+    // probability density per unit of solid angle
+    pub pdf: Float,
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Self::Solid(BLACK)
+    }
+}
+
+impl Background {
+    // light arriving from `direction`
+    pub fn color_in(&self, direction: Vector3) -> Color {
+        match self {
+            Self::Solid(color) => *color,
+            Self::Gradient { bottom, top } => {
+                let t = (direction.normalize().y + 1.0) / 2.0;
+                *bottom * (1.0 - t) + *top * t
+            }
+            Self::Environment(map) => {
+                let (u, v) = equirectangular_uv(direction);
+                sample_bilinear(&map.image, u, v)
+            }
+        }
+    }
+}
+
+impl EnvironmentMap {
+    pub fn new(image: Canvas) -> Self {
+        Self::try_new(image).unwrap_or_else(|error| panic!("{}", error))
+    }
+    pub fn try_new(image: Canvas) -> Result<Self> {
+        if image.width == 0 || image.height == 0 {
+            return Err(Error::InvalidDimensions("environment image is empty"));
+        }
+        let (width, height) = (image.width as usize, image.height as usize);
+        let mut row_cdf = Vec::with_capacity(height);
+        let mut pixel_cdf = Vec::with_capacity(width * height);
+        let mut total = 0.0;
+        for row in 0..height {
+            // rows near the poles cover less solid angle
+            let sin_theta = (PI * (row as Float + 0.5) / height as Float).sin();
+            let mut row_total = 0.0;
+            for pixel in &image.grid[row * width..(row + 1) * width] {
+                row_total += luminance(*pixel).max(0.0) * sin_theta;
+                pixel_cdf.push(row_total);
+            }
+            total += row_total;
+            row_cdf.push(total);
+        }
+        Ok(Self {
+            image,
+            row_cdf,
+            pixel_cdf,
+        })
+    }
+    pub fn image(&self) -> &Canvas {
+        &self.image
+    }
+    // direction chosen in proportion to the light arriving from it,
+    // None if the environment is completely dark
+    pub fn sample(&self, rng: &mut Rng) -> Option<EnvironmentSample> {
+        let width = self.image.width as usize;
+        let total = *self.row_cdf.last()?;
+        if total <= 0.0 {
+            return None;
+        }
+        let row = pick(&self.row_cdf, rng.next_float() * total);
+        let pixels = &self.pixel_cdf[row * width..(row + 1) * width];
+        let col = pick(pixels, rng.next_float() * pixels[width - 1]);
+
+        let u = (col as Float + rng.next_float()) / width as Float;
+        let v = (row as Float + rng.next_float()) / self.image.height as Float;
+        let pdf = self.pixel_pdf(row, col, v);
+        if pdf <= 0.0 || !pdf.is_finite() {
+            return None;
+        }
+        Some(EnvironmentSample {
+            direction: equirectangular_direction(u, v),
+            radiance: self.image.pixel_at(row as u32, col as u32),
+            pdf,
+        })
+    }
+    // probability density of `sample` choosing `direction`
+    pub fn pdf(&self, direction: Vector3) -> Float {
+        let (width, height) = (self.image.width as usize, self.image.height as usize);
+        let (u, v) = equirectangular_uv(direction);
+        let col = ((u * width as Float) as usize).min(width - 1);
+        let row = ((v * height as Float) as usize).min(height - 1);
+        self.pixel_pdf(row, col, v)
+    }
+    // density over the image of the pixel, converted from image area to
+    // solid angle at the height `v` within the image
+    fn pixel_pdf(&self, row: usize, col: usize, v: Float) -> Float {
+        let (width, height) = (self.image.width as usize, self.image.height as usize);
+        let total = self.row_cdf[height - 1];
+        let sin_theta = (PI * v).sin();
+        if total <= 0.0 || sin_theta <= 0.0 {
+            return 0.0;
+        }
+        let index = row * width + col;
+        let previous = if col == 0 {
+            0.0
+        } else {
+            self.pixel_cdf[index - 1]
+        };
+        let weight = self.pixel_cdf[index] - previous;
+        weight / total * (width * height) as Float / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+fn luminance(color: Color) -> Float {
+    0.2126 * color.red + 0.7152 * color.green + 0.0722 * color.blue
+}
+
+// first index whose cumulative weight exceeds `value`, skipping zero weights
+fn pick(cdf: &[Float], value: Float) -> usize {
+    cdf.partition_point(|&c| c <= value).min(cdf.len() - 1)
+}
+
+// inverse of `equirectangular_uv`
+pub fn equirectangular_direction(u: Float, v: Float) -> Vector3 {
+    let longitude = (u - 0.5) * 2.0 * PI;
+    let latitude = (0.5 - v) * PI;
+    Vector3::new(
+        -latitude.cos() * longitude.sin(),
+        latitude.sin(),
+        -latitude.cos() * longitude.cos(),
+    )
+}
+
+// image coordinates in [0, 1] of `direction`, the inverse of the
+// equirectangular camera projection
+pub fn equirectangular_uv(direction: Vector3) -> (Float, Float) {
+    let direction = direction.normalize();
+    let latitude = direction.y.clamp(-1.0, 1.0).asin();
+    let longitude = (-direction.x).atan2(-direction.z);
+    (longitude / (2.0 * PI) + 0.5, 0.5 - latitude / PI)
+}
+
+// wraps around horizontally and clamps at the poles
+fn sample_bilinear(image: &Canvas, u: Float, v: Float) -> Color {
+    let x = u * image.width as Float - 0.5;
+    let y = (v * image.height as Float - 0.5).clamp(0.0, (image.height - 1) as Float);
+    let x0 = x.floor();
+    let y0 = y.floor();
+    let fx = x - x0;
+    let fy = y - y0;
+    let col = |x: Float| (x as i64).rem_euclid(i64::from(image.width)) as u32;
+    let row = |y: Float| (y as u32).min(image.height - 1);
+    let (c0, c1) = (col(x0), col(x0 + 1.0));
+    let (r0, r1) = (row(y0), row(y0 + 1.0));
+    let top = image.pixel_at(r0, c0) * (1.0 - fx) + image.pixel_at(r0, c1) * fx;
+    let bottom = image.pixel_at(r1, c0) * (1.0 - fx) + image.pixel_at(r1, c1) * fx;
+    top * (1.0 - fy) + bottom * fy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn solid_background() {
+        let background = Background::Solid(RED);
+        assert_eq!(background.color_in(Vector3::new(1, 2, 3)), RED);
+        assert_eq!(Background::default().color_in(Vector3::new(0, 1, 0)), BLACK);
+    }
+    #[test]
+    fn gradient_sky() {
+        let background = Background::Gradient {
+            bottom: WHITE,
+            top: BLUE,
+        };
+        assert_eq!(background.color_in(Vector3::new(0, 1, 0)), BLUE);
+        assert_eq!(background.color_in(Vector3::new(0, -2, 0)), WHITE);
+        assert_eq!(
+            background.color_in(Vector3::new(1, 0, 0)),
+            Color::new(0.5, 0.5, 1.0)
+        );
+    }
+    #[test]
+    fn environment_directions() {
+        // forward is the center of the image, up the top row
+        assert_eq!(equirectangular_uv(Vector3::new(0, 0, -1)), (0.5, 0.5));
+        assert_eq!(equirectangular_uv(Vector3::new(0, 1, 0)).1, 0.0);
+        assert_eq!(equirectangular_uv(Vector3::new(0, -1, 0)).1, 1.0);
+        let (u, _) = equirectangular_uv(Vector3::new(1, 0, 0));
+        assert!((u - 0.25).abs() < 1.0e-6);
+    }
+    #[test]
+    fn environment_wraps_horizontally() {
+        let mut image = Canvas::new(4, 2);
+        image.set_pixel(0, 0, RED);
+        image.set_pixel(0, 3, BLUE);
+        // halfway between the last and the first column
+        let color = sample_bilinear(&image, 0.0, 0.25);
+        assert!(color.equals(Color::new(0.5, 0.0, 0.5)));
+        assert!(sample_bilinear(&image, 0.125, 0.0).equals(RED));
+    }
+    #[test]
+    fn environment_pdf_is_normalized() {
+        let mut image = Canvas::new(8, 4);
+        for (i, pixel) in image.grid.iter_mut().enumerate() {
+            *pixel = Color::new(i as Float, 1.0, 0.5);
+        }
+        let map = EnvironmentMap::new(image);
+        // uniform directions over the sphere have density 1 / (4 PI)
+        let mut rng = Rng::new(4);
+        let n = 50000;
+        let mut sum = 0.0;
+        for _ in 0..n {
+            let z = 1.0 - 2.0 * rng.next_float();
+            let r = (1.0 - z * z).sqrt();
+            let phi = 2.0 * PI * rng.next_float();
+            sum += map.pdf(Vector3::new(r * phi.cos(), r * phi.sin(), z)) * 4.0 * PI;
+        }
+        assert!((sum / n as Float - 1.0).abs() < 0.02);
+        let sample = map.sample(&mut rng).unwrap();
+        assert!((sample.pdf - map.pdf(sample.direction)).abs() < 1.0e-3 * sample.pdf);
+    }
+    #[test]
+    fn environment_samples_bright_pixels() {
+        let mut image = Canvas::new(8, 4);
+        image.set_pixel(1, 6, Color::new(100.0, 100.0, 100.0));
+        let map = EnvironmentMap::new(image);
+        let mut rng = Rng::new(8);
+        for _ in 0..100 {
+            let sample = map.sample(&mut rng).unwrap();
+            let (u, v) = equirectangular_uv(sample.direction);
+            assert_eq!(((u * 8.0) as u32, (v * 4.0) as u32), (6, 1));
+            assert_eq!(sample.radiance, Color::new(100.0, 100.0, 100.0));
+        }
+        let dark = EnvironmentMap::new(Canvas::new(4, 2));
+        assert!(dark.sample(&mut rng).is_none());
+    }
+    #[test]
+    fn equirectangular_direction_roundtrip() {
+        for &(u, v) in &[(0.1, 0.2), (0.5, 0.5), (0.9, 0.7)] {
+            let (u2, v2) = equirectangular_uv(equirectangular_direction(u, v));
+            assert!((u - u2).abs() < 1.0e-5 && (v - v2).abs() < 1.0e-5);
+        }
+    }
+    #[test]
+    fn empty_environment_image() {
+        assert_eq!(
+            EnvironmentMap::try_new(Canvas::new(0, 4)).unwrap_err(),
+            Error::InvalidDimensions("environment image is empty")
+        );
+    }
+}
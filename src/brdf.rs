@@ -0,0 +1,397 @@
+use super::color::*;
+use super::error::*;
+use super::float_cmp::consts::PI;
+use super::float_cmp::Float;
+use super::material::Material;
+use super::sampling::*;
+use super::vec3::Vector3;
+
+// GGX becomes a delta function at zero roughness, which point lights can't hit
+const MIN_ALPHA: Float = 0.002;
+// reflectance of common dielectrics at normal incidence
+const DIELECTRIC_F0: Float = 0.04;
+
+// Reflection model of a `Material`.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Brdf {
+    // ambient, diffuse and specular terms of the Phong model, see `Material::lighting`
+    #[default]
+    Phong,
+    // energy conserving GGX / Cook-Torrance microfacet model over a diffuse base,
+    // parameterized like the metallic-roughness PBR workflow
+    Microfacet {
+        metallic: Float,
+        roughness: Float,
+    },
+}
+
+impl Brdf {
+    pub fn microfacet(metallic: Float, roughness: Float) -> Self {
+        Self::try_microfacet(metallic, roughness).unwrap_or_else(|error| panic!("{}", error))
+    }
+    pub fn try_microfacet(metallic: Float, roughness: Float) -> Result<Self> {
+        Ok(Self::Microfacet {
+            metallic: check_range("metallic", metallic, 0.0..=1.0)?,
+            roughness: check_range("roughness", roughness, 0.0..=1.0)?,
+        })
+    }
+    // Fraction of the light arriving from `light_v` that leaves towards `eye_v`,
+    // per unit of solid angle. Phong uses the `diffuse`, `specular` and
+    // `shininess` of `material` as a lambertian base and a normalized
+    // (n + 2) / 2PI cos^n lobe, `Material::lighting` keeps the classic terms.
+    pub fn eval(
+        &self,
+        material: &Material,
+        color: Color,
+        light_v: Vector3,
+        eye_v: Vector3,
+        normal_v: Vector3,
+    ) -> Color {
+        let n_dot_l = normal_v.dot(light_v);
+        let n_dot_v = normal_v.dot(eye_v);
+        if n_dot_l <= 0.0 || n_dot_v <= 0.0 {
+            return BLACK;
+        }
+        match *self {
+            Self::Phong => {
+                // normalized Phong lobe, reflects at most `specular` of the light
+                let reflect_dot_eye = (-light_v).reflect(normal_v).dot(eye_v);
+                let specular = if reflect_dot_eye > 0.0 {
+                    material.specular * (material.shininess + 2.0) / (2.0 * PI)
+                        * reflect_dot_eye.powf(material.shininess)
+                } else {
+                    0.0
+                };
+                color * (material.diffuse / PI) + WHITE * specular
+            }
+            Self::Microfacet {
+                metallic,
+                roughness,
+            } => {
+                let alpha = alpha(roughness);
+                let half_v = (light_v + eye_v).normalize();
+                let fresnel = fresnel(f0(color, metallic), eye_v.dot(half_v));
+                let specular = fresnel
+                    * (ggx(alpha, normal_v.dot(half_v))
+                        * smith_g1(alpha, n_dot_l)
+                        * smith_g1(alpha, n_dot_v)
+                        / (4.0 * n_dot_l * n_dot_v));
+                // Ashikhmin-Shirley diffuse term, light reflected by the specular layer
+                // at either direction never enters the surface to be scattered diffusely
+                let f0 = f0(color, metallic);
+                let diffuse = (WHITE - f0)
+                    * color
+                    * ((1.0 - metallic) * 28.0 / (23.0 * PI)
+                        * (1.0 - (1.0 - n_dot_l / 2.0).powi(5))
+                        * (1.0 - (1.0 - n_dot_v / 2.0).powi(5)));
+                diffuse + specular
+            }
+        }
+    }
+    // Picks a direction to continue a path leaving towards `eye_v`. Returns the
+    // direction and the weight eval * cos / pdf, None if the sample is absorbed.
+    pub fn sample(
+        &self,
+        material: &Material,
+        color: Color,
+        eye_v: Vector3,
+        normal_v: Vector3,
+        rng: &mut Rng,
+    ) -> Option<(Vector3, Color)> {
+        let light_v = match *self {
+            Self::Phong => {
+                if rng.next_float() < phong_specular_probability(material) {
+                    sample_phong_lobe(material.shininess, (-eye_v).reflect(normal_v), rng)
+                } else {
+                    cosine_hemisphere(normal_v, rng)
+                }
+            }
+            Self::Microfacet {
+                metallic,
+                roughness,
+            } => {
+                if rng.next_float() < specular_probability(metallic) {
+                    let half_v = sample_ggx(alpha(roughness), normal_v, rng);
+                    (-eye_v).reflect(half_v)
+                } else {
+                    cosine_hemisphere(normal_v, rng)
+                }
+            }
+        };
+        let pdf = self.pdf(material, light_v, eye_v, normal_v);
+        if pdf <= 0.0 {
+            return None;
+        }
+        let cos = normal_v.dot(light_v);
+        Some((
+            light_v,
+            self.eval(material, color, light_v, eye_v, normal_v) * (cos / pdf),
+        ))
+    }
+    // probability density of `sample` choosing `light_v`
+    pub fn pdf(
+        &self,
+        material: &Material,
+        light_v: Vector3,
+        eye_v: Vector3,
+        normal_v: Vector3,
+    ) -> Float {
+        let n_dot_l = normal_v.dot(light_v);
+        if n_dot_l <= 0.0 {
+            return 0.0;
+        }
+        match *self {
+            Self::Phong => {
+                let shininess = material.shininess;
+                let reflect_dot_light = (-eye_v).reflect(normal_v).dot(light_v).max(0.0);
+                let specular = (shininess + 1.0) / (2.0 * PI) * reflect_dot_light.powf(shininess);
+                let p = phong_specular_probability(material);
+                p * specular + (1.0 - p) * n_dot_l / PI
+            }
+            Self::Microfacet {
+                metallic,
+                roughness,
+            } => {
+                let half_v = (light_v + eye_v).normalize();
+                let n_dot_h = normal_v.dot(half_v);
+                let v_dot_h = eye_v.dot(half_v);
+                let specular = if v_dot_h > 0.0 {
+                    ggx(alpha(roughness), n_dot_h) * n_dot_h / (4.0 * v_dot_h)
+                } else {
+                    0.0
+                };
+                let p = specular_probability(metallic);
+                p * specular + (1.0 - p) * n_dot_l / PI
+            }
+        }
+    }
+}
+
+fn alpha(roughness: Float) -> Float {
+    (roughness * roughness).max(MIN_ALPHA)
+}
+
+// metals tint their reflection, dielectrics reflect a few percent of white
+fn f0(color: Color, metallic: Float) -> Color {
+    WHITE * (DIELECTRIC_F0 * (1.0 - metallic)) + color * metallic
+}
+
+// Schlick's approximation
+fn fresnel(f0: Color, cos: Float) -> Color {
+    f0 + (WHITE - f0) * (1.0 - cos.max(0.0)).powi(5)
+}
+
+// distribution of microfacet normals
+fn ggx(alpha: Float, n_dot_h: Float) -> Float {
+    if n_dot_h <= 0.0 {
+        return 0.0;
+    }
+    let a2 = alpha * alpha;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    a2 / (PI * d * d)
+}
+
+// Smith masking of a single direction
+fn smith_g1(alpha: Float, cos: Float) -> Float {
+    let a2 = alpha * alpha;
+    2.0 * cos / (cos + (a2 + (1.0 - a2) * cos * cos).sqrt())
+}
+
+// metals have no diffuse lobe worth sampling
+fn specular_probability(metallic: Float) -> Float {
+    0.5 + 0.5 * metallic
+}
+
+// the Phong lobe is sampled in proportion to its share of the reflected light
+fn phong_specular_probability(material: &Material) -> Float {
+    let total = material.diffuse + material.specular;
+    if total > 0.0 {
+        material.specular / total
+    } else {
+        0.0
+    }
+}
+
+// direction with pdf (n + 1) / 2PI cos^n around `reflect_v`
+fn sample_phong_lobe(shininess: Float, reflect_v: Vector3, rng: &mut Rng) -> Vector3 {
+    let cos_theta = rng.next_float().powf(1.0 / (shininess + 1.0));
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * rng.next_float();
+    let (tangent, bitangent) = orthonormal_basis(reflect_v);
+    (tangent * (sin_theta * phi.cos())
+        + bitangent * (sin_theta * phi.sin())
+        + reflect_v * cos_theta)
+        .normalize()
+}
+
+// microfacet normal with pdf ggx(h) * cos(theta_h)
+fn sample_ggx(alpha: Float, normal: Vector3, rng: &mut Rng) -> Vector3 {
+    let u1 = rng.next_float();
+    let u2 = rng.next_float();
+    let cos_theta = ((1.0 - u1) / (1.0 + (alpha * alpha - 1.0) * u1)).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * u2;
+    let (tangent, bitangent) = orthonormal_basis(normal);
+    (tangent * (sin_theta * phi.cos()) + bitangent * (sin_theta * phi.sin()) + normal * cos_theta)
+        .normalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // directional albedo, the fraction of light from `eye_v` that is reflected at all
+    fn albedo(brdf: Brdf, color: Color, eye_v: Vector3) -> Color {
+        let normal = Vector3::new(0, 1, 0);
+        let mut rng = Rng::new(11);
+        let n = 20000;
+        let mut sum = BLACK;
+        for _ in 0..n {
+            let material = Material::default();
+            if let Some((_, weight)) = brdf.sample(&material, color, eye_v, normal, &mut rng) {
+                sum = sum + weight;
+            }
+        }
+        sum * (1.0 / n as Float)
+    }
+    #[test]
+    fn microfacet_conserves_energy() {
+        for &metallic in &[0.0, 1.0] {
+            for &roughness in &[0.05, 0.3, 0.6, 1.0] {
+                for &eye_v in &[
+                    Vector3::new(0, 1, 0),
+                    Vector3::new(1.0, 0.2, 0.0).normalize(),
+                ] {
+                    let brdf = Brdf::microfacet(metallic, roughness);
+                    let a = albedo(brdf, WHITE, eye_v);
+                    assert!(a.red <= 1.02, "{:?} reflects {}", brdf, a.red);
+                    // single scattering GGX loses some energy on very rough surfaces
+                    assert!(a.red > 0.25, "{:?} reflects {}", brdf, a.red);
+                }
+            }
+        }
+    }
+    #[test]
+    fn sampling_matches_uniform_integration() {
+        // importance sampling and plain uniform hemisphere sampling estimate the same integral
+        let brdf = Brdf::microfacet(0.3, 0.5);
+        let color = Color::new(0.9, 0.5, 0.2);
+        let normal = Vector3::new(0, 1, 0);
+        let eye_v = Vector3::new(0.0, 1.0, -1.0).normalize();
+        let mut rng = Rng::new(3);
+        let n = 200_000;
+        let mut uniform = BLACK;
+        for _ in 0..n {
+            let z = rng.next_float();
+            let r = (1.0 - z * z).sqrt();
+            let phi = 2.0 * PI * rng.next_float();
+            let light_v = Vector3::new(r * phi.cos(), z, r * phi.sin());
+            uniform = uniform
+                + brdf.eval(&Material::default(), color, light_v, eye_v, normal) * (z * 2.0 * PI);
+        }
+        uniform = uniform * (1.0 / n as Float);
+        let sampled = albedo(brdf, color, eye_v);
+        assert!((uniform.red - sampled.red).abs() < 0.02);
+        assert!((uniform.blue - sampled.blue).abs() < 0.02);
+    }
+    #[test]
+    fn smooth_metal_reflects_its_color() {
+        let brdf = Brdf::microfacet(1.0, 0.0);
+        let normal = Vector3::new(0, 1, 0);
+        let eye_v = Vector3::new(0, 1, 0);
+        let mut rng = Rng::new(1);
+        let material = Material::default();
+        let (direction, weight) = brdf
+            .sample(&material, RED, eye_v, normal, &mut rng)
+            .unwrap();
+        assert!(direction.dot(normal) > 0.99);
+        assert!(weight.red > 0.9 && weight.green < 0.01);
+    }
+    #[test]
+    fn nothing_is_reflected_below_the_surface() {
+        let normal = Vector3::new(0, 1, 0);
+        let below = Vector3::new(0, -1, 0);
+        for brdf in &[Brdf::Phong, Brdf::microfacet(0.5, 0.5)] {
+            let material = Material::default();
+            assert_eq!(brdf.eval(&material, WHITE, below, normal, normal), BLACK);
+            assert_eq!(brdf.eval(&material, WHITE, normal, below, normal), BLACK);
+            assert_eq!(brdf.pdf(&material, below, normal, normal), 0.0);
+        }
+    }
+    #[test]
+    fn phong_has_a_highlight() {
+        let material = Material::default();
+        let normal = Vector3::new(0, 1, 0);
+        let eye_v = Vector3::new(0.0, 1.0, -1.0).normalize();
+        let mirror = Vector3::new(0.0, 1.0, 1.0).normalize();
+        let away = Vector3::new(0.0, 1.0, -1.0).normalize();
+        let highlight = Brdf::Phong.eval(&material, WHITE, mirror, eye_v, normal);
+        let diffuse = Brdf::Phong.eval(&material, WHITE, away, eye_v, normal);
+        assert_eq!(diffuse, WHITE * (material.diffuse / PI));
+        assert!(highlight.red > diffuse.red * 2.0);
+    }
+    #[test]
+    fn phong_conserves_energy() {
+        // the old lobe divided by cos and blew up at grazing light
+        let eye_v = Vector3::new(1.0, 0.05, 0.0).normalize();
+        let a = albedo(Brdf::Phong, WHITE, eye_v);
+        let material = Material::default();
+        assert!(a.red <= material.diffuse + material.specular + 0.02);
+        let mut rng = Rng::new(5);
+        let normal = Vector3::new(0, 1, 0);
+        for _ in 0..1000 {
+            if let Some((_, weight)) = Brdf::Phong.sample(&material, WHITE, eye_v, normal, &mut rng)
+            {
+                assert!(weight.red < 2.0, "weight {}", weight.red);
+            }
+        }
+    }
+    #[test]
+    fn phong_sampling_matches_uniform_integration() {
+        let color = Color::new(0.9, 0.5, 0.2);
+        let normal = Vector3::new(0, 1, 0);
+        let eye_v = Vector3::new(0.0, 1.0, -1.0).normalize();
+        let material = Material {
+            shininess: 20.0,
+            ..Material::default()
+        };
+        let mut rng = Rng::new(3);
+        let n = 200_000;
+        let mut uniform = BLACK;
+        let mut sampled = BLACK;
+        for _ in 0..n {
+            let z = rng.next_float();
+            let r = (1.0 - z * z).sqrt();
+            let phi = 2.0 * PI * rng.next_float();
+            let light_v = Vector3::new(r * phi.cos(), z, r * phi.sin());
+            uniform = uniform
+                + Brdf::Phong.eval(&material, color, light_v, eye_v, normal) * (z * 2.0 * PI);
+            if let Some((_, weight)) = Brdf::Phong.sample(&material, color, eye_v, normal, &mut rng)
+            {
+                sampled = sampled + weight;
+            }
+        }
+        let (uniform, sampled) = (uniform * (1.0 / n as Float), sampled * (1.0 / n as Float));
+        assert!((uniform.red - sampled.red).abs() < 0.02);
+        assert!((uniform.blue - sampled.blue).abs() < 0.02);
+    }
+    #[test]
+    #[should_panic]
+    fn roughness_out_of_bounds() {
+        Brdf::microfacet(0.0, 1.5);
+    }
+    #[test]
+    fn try_microfacet_values() {
+        assert_eq!(
+            Brdf::try_microfacet(-0.5, 0.5),
+            Err(Error::ValueOutOfRange {
+                name: "metallic",
+                value: -0.5
+            })
+        );
+        assert_eq!(
+            Brdf::try_microfacet(0.5, 0.5),
+            Ok(Brdf::microfacet(0.5, 0.5))
+        );
+    }
+}
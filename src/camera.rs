@@ -1,64 +1,230 @@
+use super::background::equirectangular_direction;
 use super::canvas::Canvas;
+use super::checkpoint::*;
+use super::color::*;
+use super::float_cmp::Float;
 use super::matrix::Matrix;
-use super::ray::Ray;
-use super::vec3::Vec3;
+use super::path_tracer::Integrator;
+use super::ray::{Ray, RayPacket};
+use super::sampling::Rng;
+use super::simd::LANES;
+use super::tile::*;
+use super::vec3::{Point3, Vector3};
 use super::world::World;
 
+use super::float_cmp::consts::PI;
 use indicatif::{ParallelProgressIterator, ProgressBar, ProgressStyle};
 use rayon::iter::ParallelIterator;
 use rayon::prelude::*;
+use std::convert::TryFrom;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    // pinhole camera, rays fan out from the eye through the image plane
+    Perspective { field_of_view: Float },
+    // parallel rays, `view_size` is the extent of the larger image dimension
+    // in world units
+    Orthographic { view_size: Float },
+    // equidistant fisheye, angle from the view axis grows linearly with the
+    // distance from the image center, `field_of_view` spans the larger image
+    // dimension and may exceed PI
+    Fisheye { field_of_view: Float },
+    // latitude / longitude panorama covering the full sphere around the camera
+    Equirectangular,
+}
 
 #[derive(Clone, Debug)]
 pub struct Camera {
     pub hsize: u32,
     pub vsize: u32,
-    pub field_of_view: f32,
     pub transform: Matrix,
-    pub pixel_size: f32,
-    pub half_width: f32,
-    pub half_height: f32,
+    pub pixel_size: Float,
+    pub half_width: Float,
+    pub half_height: Float,
+    pub projection: Projection,
+    pub integrator: Integrator,
+    // times the shutter opens and closes, path traced samples are spread over
+    // the interval so moving objects blur, the other integrators render the
+    // moment in the middle
+    pub shutter_open: Float,
+    pub shutter_close: Float,
 }
 
 impl Camera {
-    pub fn new(horizontal_size: u32, vertical_size: u32, field_of_view: f32) -> Self {
-        let half_view = (field_of_view / 2.0).tan();
-        let aspect = horizontal_size as f32 / vertical_size as f32;
+    fn with_half_view(
+        horizontal_size: u32,
+        vertical_size: u32,
+        half_view: Float,
+        projection: Projection,
+    ) -> Self {
+        let aspect = horizontal_size as Float / vertical_size as Float;
 
         let (half_width, half_height) = if aspect >= 1.0 {
-            (half_view, half_view / aspect as f32)
+            (half_view, half_view / aspect)
         } else {
-            (half_view * aspect as f32, half_view)
+            (half_view * aspect, half_view)
         };
 
-        let pixel_size = half_width * 2.0 / horizontal_size as f32;
+        let pixel_size = half_width * 2.0 / horizontal_size as Float;
         Self {
             hsize: horizontal_size,
             vsize: vertical_size,
-            field_of_view,
             half_width,
             half_height,
             pixel_size,
             transform: Matrix::identity_matrix(4),
+            projection,
+            integrator: Integrator::Whitted,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
         }
     }
-    pub fn ray_for_pixel(&self, px: u32, py: u32) -> Ray {
-        let xoffset = (px as f32 + 0.5) * self.pixel_size;
-        let yoffset = (py as f32 + 0.5) * self.pixel_size;
+    pub fn new(horizontal_size: u32, vertical_size: u32, field_of_view: Float) -> Self {
+        let half_view = (field_of_view / 2.0).tan();
+        Self::with_half_view(
+            horizontal_size,
+            vertical_size,
+            half_view,
+            Projection::Perspective { field_of_view },
+        )
+    }
+    pub fn orthographic(horizontal_size: u32, vertical_size: u32, view_size: Float) -> Self {
+        Self::with_half_view(
+            horizontal_size,
+            vertical_size,
+            view_size / 2.0,
+            Projection::Orthographic { view_size },
+        )
+    }
+    pub fn fisheye(horizontal_size: u32, vertical_size: u32, field_of_view: Float) -> Self {
+        Self::with_half_view(
+            horizontal_size,
+            vertical_size,
+            1.0,
+            Projection::Fisheye { field_of_view },
+        )
+    }
+    pub fn equirectangular(horizontal_size: u32, vertical_size: u32) -> Self {
+        // longitude spans the width and latitude the height,
+        // a 2:1 aspect ratio gives square pixels
+        Self {
+            hsize: horizontal_size,
+            vsize: vertical_size,
+            half_width: PI,
+            half_height: PI / 2.0,
+            pixel_size: 2.0 * PI / horizontal_size as Float,
+            transform: Matrix::identity_matrix(4),
+            projection: Projection::Equirectangular,
+            integrator: Integrator::Whitted,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }
+    }
+    // origin and direction in camera space of the ray through the image
+    // position `x`, `y` measured in pixels from the top left corner
+    fn camera_space_ray(&self, x: Float, y: Float) -> (Point3, Vector3) {
+        let xoffset = x * self.pixel_size;
+        let yoffset = y * self.pixel_size;
 
         let world_x = self.half_width - xoffset;
         let world_y = self.half_height - yoffset;
 
-        let pixel = self.transform.inverse_matrix() * Vec3::point(world_x, world_y, -1.0);
-        let origin = self.transform.inverse_matrix() * Vec3::point(0, 0, 0);
-        let direction = (pixel - origin).normalize();
+        match self.projection {
+            Projection::Perspective { .. } => {
+                (Point3::new(0, 0, 0), Vector3::new(world_x, world_y, -1.0))
+            }
+            Projection::Orthographic { .. } => {
+                (Point3::new(world_x, world_y, 0.0), Vector3::new(0, 0, -1))
+            }
+            Projection::Fisheye { field_of_view } => {
+                let radius = (world_x * world_x + world_y * world_y).sqrt();
+                let theta = radius * field_of_view / 2.0;
+                let direction = if radius > 0.0 {
+                    let sin_theta = theta.sin();
+                    Vector3::new(
+                        sin_theta * world_x / radius,
+                        sin_theta * world_y / radius,
+                        -theta.cos(),
+                    )
+                } else {
+                    Vector3::new(0, 0, -1)
+                };
+                (Point3::new(0, 0, 0), direction)
+            }
+            Projection::Equirectangular => {
+                let u = x / self.hsize as Float;
+                let v = y / self.vsize as Float;
+                (Point3::new(0, 0, 0), equirectangular_direction(u, v))
+            }
+        }
+    }
+    pub fn ray_through(&self, x: Float, y: Float) -> Ray {
+        let (origin, direction) = self.camera_space_ray(x, y);
+        let inverse = self.transform.inverse_matrix();
+        let origin = &inverse * origin;
+        let direction = (&inverse * direction).normalize();
         Ray::new(origin, direction)
     }
+    pub fn ray_for_pixel(&self, px: u32, py: u32) -> Ray {
+        self.ray_through(px as Float + 0.5, py as Float + 0.5)
+            .with_time(self.shutter_time(0.5))
+    }
+    // time `fraction` of the way through the shutter interval
+    pub fn shutter_time(&self, fraction: Float) -> Float {
+        self.shutter_open + (self.shutter_close - self.shutter_open) * fraction
+    }
+    pub fn color_for_pixel(&self, world: &World, px: u32, py: u32) -> Color {
+        match self.integrator {
+            Integrator::Whitted => world.color_at(&self.ray_for_pixel(px, py)),
+            Integrator::AmbientOcclusion => world.occlusion_color(&self.ray_for_pixel(px, py)),
+            Integrator::PathTracer { samples, max_depth } => {
+                let samples = samples.max(1);
+                let mut color = BLACK;
+                for sample in 0..samples {
+                    // jittered position inside the pixel for anti-aliasing
+                    let mut rng = Rng::for_pixel(px, py, sample);
+                    let mut ray = self.ray_through(
+                        px as Float + rng.next_float(),
+                        py as Float + rng.next_float(),
+                    );
+                    ray.time = if self.shutter_close > self.shutter_open {
+                        self.shutter_time(rng.next_float())
+                    } else {
+                        self.shutter_open
+                    };
+                    color = color + world.path_trace(&ray, max_depth, &mut rng);
+                }
+                color * (1.0 / samples as Float)
+            }
+        }
+    }
+    // Colors of the (column, row) `pixels`. Whitted camera rays of a full
+    // packet of pixels are intersected together, neighbouring rays mostly hit
+    // the same objects.
+    fn colors_for_pixels(&self, world: &World, pixels: &[(u32, u32)], colors: &mut [Color]) {
+        match <&[(u32, u32); LANES]>::try_from(pixels) {
+            Ok(pixels) if self.integrator == Integrator::Whitted => {
+                let rays = pixels.map(|(col, row)| self.ray_for_pixel(col, row));
+                colors.copy_from_slice(&world.color_at_packet(&RayPacket::new(&rays)));
+            }
+            _ => {
+                for (color, (col, row)) in colors.iter_mut().zip(pixels) {
+                    *color = self.color_for_pixel(world, *col, *row);
+                }
+            }
+        }
+    }
     pub fn render(&self, world: &World) -> Canvas {
         let mut canvas = Canvas::new(self.hsize, self.vsize);
         let n_pixels: u64 = u64::from(self.hsize) * u64::from(self.vsize);
 
         // Provide a custom bar style
-        let pb = ProgressBar::new(n_pixels);
+        let pb = ProgressBar::new(n_pixels.div_ceil(LANES as u64));
         pb.set_style(
             ProgressStyle::default_bar()
                 .template("[{elapsed}] {bar:30} {pos:>7}/{len:7} {msg}ETA:{eta}"),
@@ -66,67 +232,529 @@ impl Camera {
 
         canvas
             .grid
-            .par_iter_mut()
+            .par_chunks_mut(LANES)
             .enumerate()
             .progress_with(pb)
-            .for_each(|(index, color)| {
-                let x = index as u32 / self.hsize;
-                let y = index as u32 % self.hsize;
-                let ray = self.ray_for_pixel(y, x);
-                *color = world.color_at(&ray);
+            .for_each(|(chunk, colors)| {
+                let mut pixels = [(0, 0); LANES];
+                for (i, pixel) in pixels.iter_mut().enumerate() {
+                    let index = (chunk * LANES + i) as u32;
+                    *pixel = (index % self.hsize, index / self.hsize);
+                }
+                self.colors_for_pixels(world, &pixels[..colors.len()], colors);
             });
 
         canvas
     }
+    pub fn render_tile(&self, world: &World, tile: Tile) -> RenderedTile {
+        let positions: Vec<(u32, u32)> = tile.pixels().collect();
+        let mut pixels = vec![BLACK; positions.len()];
+        for (positions, colors) in positions.chunks(LANES).zip(pixels.chunks_mut(LANES)) {
+            self.colors_for_pixels(world, positions, colors);
+        }
+        RenderedTile {
+            tile,
+            pixels,
+            samples: self.integrator.samples(),
+        }
+    }
+    fn trace_tiles<F>(
+        &self,
+        world: &World,
+        tiles: &[Tile],
+        cancel: &CancelToken,
+        on_tile: F,
+    ) -> Vec<RenderedTile>
+    where
+        F: Fn(&RenderedTile) + Sync,
+    {
+        // workers pull tiles from a shared counter so that tiles are started
+        // in the requested order regardless of how rayon splits the range
+        let next = AtomicUsize::new(0);
+        (0..tiles.len())
+            .into_par_iter()
+            .filter_map(|_| {
+                if cancel.is_cancelled() {
+                    return None;
+                }
+                let tile = tiles[next.fetch_add(1, Ordering::SeqCst)];
+                let rendered = self.render_tile(world, tile);
+                on_tile(&rendered);
+                Some(rendered)
+            })
+            .collect()
+    }
+    // Renders the image tile by tile, handing every finished tile to `on_tile`.
+    // Once `cancel` is triggered no new tiles are started and the canvas is
+    // returned with the unfinished tiles left black.
+    pub fn render_tiles<F>(
+        &self,
+        world: &World,
+        settings: &TileSettings,
+        cancel: &CancelToken,
+        on_tile: F,
+    ) -> Canvas
+    where
+        F: Fn(&RenderedTile) + Sync,
+    {
+        let mut canvas = Canvas::new(self.hsize, self.vsize);
+        self.render_tiles_into(world, settings, cancel, &mut canvas, on_tile);
+        canvas
+    }
+    // Same as `render_tiles` but writes into an existing canvas, pixels
+    // outside of `settings.crop` keep their previous color.
+    pub fn render_tiles_into<F>(
+        &self,
+        world: &World,
+        settings: &TileSettings,
+        cancel: &CancelToken,
+        canvas: &mut Canvas,
+        on_tile: F,
+    ) where
+        F: Fn(&RenderedTile) + Sync,
+    {
+        assert!(
+            canvas.width == self.hsize && canvas.height == self.vsize,
+            "canvas dimensions don't match the camera"
+        );
+        let tiles = Tile::split(self.hsize, self.vsize, settings);
+        let rendered = self.trace_tiles(world, &tiles, cancel, on_tile);
+        for tile in &rendered {
+            canvas.set_tile(tile);
+        }
+    }
+    // Re-renders only the pixels inside `crop`, leaving the rest of `canvas` untouched.
+    pub fn render_crop(&self, world: &World, crop: Tile, canvas: &mut Canvas) {
+        let settings = TileSettings {
+            crop: Some(crop),
+            ..TileSettings::default()
+        };
+        self.render_tiles_into(world, &settings, &CancelToken::new(), canvas, |_| {});
+    }
+    // Renders like `render_tiles`, saving the finished tiles to `checkpoint_path`
+    // whenever `interval` has passed since the last save and once more at the
+    // end, also when cancelled.
+    // Tiles found in an existing checkpoint file are reused instead of traced
+    // again, so an interrupted render can be resumed by calling this again.
+    pub fn render_resumable<F>(
+        &self,
+        world: &World,
+        settings: &TileSettings,
+        cancel: &CancelToken,
+        checkpoint_path: &Path,
+        interval: Duration,
+        on_tile: F,
+    ) -> io::Result<Canvas>
+    where
+        F: Fn(&RenderedTile) + Sync,
+    {
+        let scene = scene_hash(self, world);
+        let checkpoint = if checkpoint_path.exists() {
+            let checkpoint = Checkpoint::load(checkpoint_path)?;
+            if checkpoint.width != self.hsize || checkpoint.height != self.vsize {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "checkpoint dimensions don't match the camera",
+                ));
+            }
+            if checkpoint.scene != scene {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "checkpoint belongs to a different scene or render settings",
+                ));
+            }
+            checkpoint
+        } else {
+            Checkpoint::new(self.hsize, self.vsize, scene)
+        };
+        let tiles: Vec<Tile> = Tile::split(self.hsize, self.vsize, settings)
+            .into_iter()
+            .filter(|tile| !checkpoint.is_done(tile))
+            .collect();
+
+        // (checkpoint, time of the last save)
+        let state = Mutex::new((checkpoint, Instant::now()));
+        // (tiles in the saved file, first failed save)
+        let saved = Mutex::new((0, None));
+        self.trace_tiles(world, &tiles, cancel, |rendered| {
+            on_tile(rendered);
+            let snapshot = {
+                let mut state = state.lock().unwrap();
+                let (checkpoint, last_save) = &mut *state;
+                checkpoint.push(rendered.clone());
+                if last_save.elapsed() < interval {
+                    return;
+                }
+                *last_save = Instant::now();
+                checkpoint.clone()
+            };
+            // written without holding up the other workers, a slower save of
+            // an older snapshot doesn't replace a newer file
+            let mut saved = saved.lock().unwrap();
+            let (n_saved, error) = &mut *saved;
+            if snapshot.tiles().len() > *n_saved {
+                match snapshot.save(checkpoint_path) {
+                    Ok(()) => *n_saved = snapshot.tiles().len(),
+                    Err(e) => {
+                        error.get_or_insert(e);
+                    }
+                }
+            }
+        });
+        let (checkpoint, _) = state.into_inner().unwrap();
+        let (_, error) = saved.into_inner().unwrap();
+        checkpoint.save(checkpoint_path)?;
+        if let Some(e) = error {
+            return Err(e);
+        }
+
+        let mut canvas = Canvas::new(self.hsize, self.vsize);
+        checkpoint.apply(&mut canvas);
+        Ok(canvas)
+    }
 }
 
 #[cfg(test)]
-pub mod tests {
-    use super::super::color::*;
+mod tests {
+    use super::super::background::*;
+    use super::super::float_cmp::{self, consts};
     use super::*;
     #[test]
     fn create_camera() {
-        let camera = Camera::new(160, 120, std::f32::consts::PI / 2.0);
+        let camera = Camera::new(160, 120, consts::PI / 2.0);
         assert_eq!(camera.hsize, 160);
         assert_eq!(camera.vsize, 120);
-        assert_eq!(camera.field_of_view, std::f32::consts::PI / 2.0);
+        assert_eq!(
+            camera.projection,
+            Projection::Perspective {
+                field_of_view: consts::PI / 2.0
+            }
+        );
         assert_eq!(camera.transform, Matrix::identity_matrix(4));
     }
     #[test]
     fn pixel_size() {
-        let c1 = Camera::new(200, 125, std::f32::consts::PI / 2.0);
-        assert_eq!(c1.pixel_size, 0.01);
-        let c2 = Camera::new(125, 200, std::f32::consts::PI / 2.0);
-        assert_eq!(c2.pixel_size, 0.01);
+        let c1 = Camera::new(200, 125, consts::PI / 2.0);
+        assert!(float_cmp::equal(c1.pixel_size, 0.01));
+        let c2 = Camera::new(125, 200, consts::PI / 2.0);
+        assert!(float_cmp::equal(c2.pixel_size, 0.01));
     }
     #[test]
     fn compute_ray_for_pixel() {
-        let mut camera = Camera::new(201, 101, std::f32::consts::PI / 2.0);
+        let mut camera = Camera::new(201, 101, consts::PI / 2.0);
         let r1 = camera.ray_for_pixel(100, 50);
         let r2 = camera.ray_for_pixel(0, 0);
         camera.transform =
-            Matrix::rotation_y(std::f32::consts::PI / 4.0) * &Matrix::translation(0.0, -2.0, 5.0);
+            Matrix::rotation_y(consts::PI / 4.0) * &Matrix::translation(0.0, -2.0, 5.0);
         let r3 = camera.ray_for_pixel(100, 50);
-        assert_eq!(r1.origin, Vec3::point(0, 0, 0));
-        assert_eq!(r1.direction, Vec3::vector(0, 0, -1));
-        assert_eq!(r2.origin, Vec3::point(0, 0, 0));
-        assert_eq!(r2.direction, Vec3::vector(0.66519, 0.33259, -0.66851));
-        assert_eq!(r3.origin, Vec3::point(0, 2, -5));
-        assert_eq!(r3.direction, Vec3::vector(0.70710665, 0.0, -0.7071069));
+        assert_eq!(r1.origin, Point3::new(0, 0, 0));
+        assert_eq!(r1.direction, Vector3::new(0, 0, -1));
+        assert_eq!(r2.origin, Point3::new(0, 0, 0));
+        assert_eq!(r2.direction, Vector3::new(0.66519, 0.33259, -0.66851));
+        assert_eq!(r3.origin, Point3::new(0, 2, -5));
+        assert_eq!(r3.direction, Vector3::new(0.70710665, 0.0, -0.7071069));
+    }
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let mut camera = Camera::orthographic(201, 101, 2.0);
+        assert_eq!(
+            camera.projection,
+            Projection::Orthographic { view_size: 2.0 }
+        );
+        let r1 = camera.ray_for_pixel(0, 0);
+        let r2 = camera.ray_for_pixel(100, 50);
+        camera.transform = Matrix::translation(0.0, -2.0, 5.0);
+        let r3 = camera.ray_for_pixel(100, 50);
+        assert_eq!(r1.origin, Point3::new(0.99502, 0.49751, 0.0));
+        assert_eq!(r1.direction, Vector3::new(0, 0, -1));
+        assert_eq!(r2.origin, Point3::new(0, 0, 0));
+        assert_eq!(r2.direction, Vector3::new(0, 0, -1));
+        assert_eq!(r3.origin, Point3::new(0, 2, -5));
+        assert_eq!(r3.direction, Vector3::new(0, 0, -1));
+    }
+    #[test]
+    fn fisheye_ray_for_pixel() {
+        let camera = Camera::fisheye(201, 101, consts::PI);
+        let center = camera.ray_for_pixel(100, 50);
+        let edge = camera.ray_for_pixel(0, 50);
+        assert_eq!(center.origin, Point3::new(0, 0, 0));
+        assert_eq!(center.direction, Vector3::new(0, 0, -1));
+        assert_eq!(edge.direction, Vector3::new(0.99997, 0.0, -0.00781));
+    }
+    #[test]
+    fn equirectangular_ray_for_pixel() {
+        let camera = Camera::equirectangular(360, 180);
+        let behind = camera.ray_for_pixel(0, 89);
+        let up = camera.ray_for_pixel(180, 0);
+        let quadrant = camera.ray_for_pixel(90, 45);
+        assert_eq!(behind.direction, Vector3::new(0.00873, 0.00873, 0.99992));
+        assert_eq!(up.direction, Vector3::new(-0.00008, 0.99996, -0.00873));
+        assert_eq!(quadrant.direction, Vector3::new(0.71322, 0.70091, -0.00622));
     }
     #[test]
     fn render_image() {
         let world = World::default();
-        let mut camera = Camera::new(11, 11, std::f32::consts::PI / 2.0);
+        let mut camera = Camera::new(11, 11, consts::PI / 2.0);
         camera.transform = Matrix::view_transformation(
-            Vec3::point(0, 0, -5),
-            Vec3::point(0, 0, 0),
-            Vec3::vector(0, 1, 0),
+            Point3::new(0, 0, -5),
+            Point3::new(0, 0, 0),
+            Vector3::new(0, 1, 0),
         );
         let image = camera.render(&world);
-        assert_eq!(
-            image.pixel_at(5, 5),
-            Color::new(0.38066125, 0.4758265, 0.28549594)
+        assert!(image
+            .pixel_at(5, 5)
+            .equals(Color::new(0.38066125, 0.4758265, 0.28549594)));
+    }
+    #[test]
+    fn render_tiles_matches_render() {
+        let world = World::default();
+        let mut camera = Camera::new(11, 9, consts::PI / 2.0);
+        camera.transform = Matrix::view_transformation(
+            Point3::new(0, 0, -5),
+            Point3::new(0, 0, 0),
+            Vector3::new(0, 1, 0),
+        );
+        let settings = TileSettings {
+            tile_size: 4,
+            order: TileOrder::Spiral,
+            crop: None,
+        };
+        let delivered = AtomicUsize::new(0);
+        let image = camera.render_tiles(&world, &settings, &CancelToken::new(), |tile| {
+            delivered.fetch_add(tile.pixels.len(), Ordering::SeqCst);
+        });
+        assert_eq!(delivered.load(Ordering::SeqCst), 11 * 9);
+        assert_eq!(image.grid, camera.render(&world).grid);
+    }
+    #[test]
+    fn cancel_render_tiles() {
+        let world = World::default();
+        let camera = Camera::new(16, 16, consts::PI / 2.0);
+        let settings = TileSettings {
+            tile_size: 2,
+            order: TileOrder::RowMajor,
+            crop: None,
+        };
+        let cancel = CancelToken::new();
+        let delivered = AtomicUsize::new(0);
+        camera.render_tiles(&world, &settings, &cancel, |_| {
+            delivered.fetch_add(1, Ordering::SeqCst);
+            cancel.cancel();
+        });
+        // tiles already in flight on other threads may still finish
+        assert!(delivered.load(Ordering::SeqCst) < 64);
+
+        let image = camera.render_tiles(&world, &settings, &cancel, |_| {
+            panic!("no tile should be rendered after cancellation")
+        });
+        assert!(image.grid.iter().all(|c| *c == BLACK));
+    }
+    #[test]
+    fn render_crop_into_existing_canvas() {
+        let world = World::default();
+        let mut camera = Camera::new(11, 11, consts::PI / 2.0);
+        camera.transform = Matrix::view_transformation(
+            Point3::new(0, 0, -5),
+            Point3::new(0, 0, 0),
+            Vector3::new(0, 1, 0),
+        );
+        let full = camera.render(&world);
+        let mut canvas = Canvas::new(11, 11);
+        for color in canvas.grid.iter_mut() {
+            *color = RED;
+        }
+        let crop = Tile::new(4, 3, 3, 5);
+        camera.render_crop(&world, crop, &mut canvas);
+        for (col, row) in Tile::new(0, 0, 11, 11).pixels() {
+            if crop.contains(col, row) {
+                assert_eq!(canvas.pixel_at(row, col), full.pixel_at(row, col));
+            } else {
+                assert_eq!(canvas.pixel_at(row, col), RED);
+            }
+        }
+    }
+    #[test]
+    fn resume_render_from_checkpoint() {
+        let world = World::default();
+        let mut camera = Camera::new(12, 10, consts::PI / 2.0);
+        camera.transform = Matrix::view_transformation(
+            Point3::new(0, 0, -5),
+            Point3::new(0, 0, 0),
+            Vector3::new(0, 1, 0),
+        );
+        let settings = TileSettings {
+            tile_size: 3,
+            order: TileOrder::RowMajor,
+            crop: None,
+        };
+        let n_tiles = Tile::split(12, 10, &settings).len();
+        let path = std::env::temp_dir().join(format!(
+            "resume_render_from_checkpoint_{}.ckpt",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        // interrupt the first render after a few tiles
+        let cancel = CancelToken::new();
+        let done = AtomicUsize::new(0);
+        camera
+            .render_resumable(
+                &world,
+                &settings,
+                &cancel,
+                &path,
+                Duration::from_secs(0),
+                |_| {
+                    if done.fetch_add(1, Ordering::SeqCst) >= 2 {
+                        cancel.cancel();
+                    }
+                },
+            )
+            .unwrap();
+        let checkpoint = Checkpoint::load(&path).unwrap();
+        let n_saved = checkpoint.tiles().len();
+        assert!(n_saved >= 3 && n_saved < n_tiles);
+
+        // only the missing tiles are traced when resuming
+        let done = AtomicUsize::new(0);
+        let image = camera
+            .render_resumable(
+                &world,
+                &settings,
+                &CancelToken::new(),
+                &path,
+                Duration::from_secs(60),
+                |_| {
+                    done.fetch_add(1, Ordering::SeqCst);
+                },
+            )
+            .unwrap();
+        assert_eq!(done.load(Ordering::SeqCst), n_tiles - n_saved);
+        assert_eq!(image.grid, camera.render(&world).grid);
+        assert_eq!(Checkpoint::load(&path).unwrap().n_pixels(), 12 * 10);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+    #[test]
+    fn reject_checkpoint_of_other_scene() {
+        let world = World::default();
+        let mut camera = Camera::new(6, 4, consts::PI / 2.0);
+        let settings = TileSettings {
+            tile_size: 2,
+            order: TileOrder::RowMajor,
+            crop: None,
+        };
+        let path = std::env::temp_dir().join(format!(
+            "reject_checkpoint_of_other_scene_{}.ckpt",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let render = |camera: &Camera, world: &World| {
+            camera.render_resumable(
+                world,
+                &settings,
+                &CancelToken::new(),
+                &path,
+                Duration::from_secs(60),
+                |_| {},
+            )
+        };
+        render(&camera, &world).unwrap();
+
+        // an equal world built again resumes
+        render(&camera, &World::default()).unwrap();
+
+        let mut moved = World::default();
+        moved.lights[0].position = Point3::new(10, 10, -10);
+        let error = render(&camera, &moved).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        camera.integrator = Integrator::PathTracer {
+            samples: 4,
+            max_depth: 2,
+        };
+        let error = render(&camera, &world).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+    #[test]
+    fn equirectangular_camera_sees_environment_unchanged() {
+        let mut image = Canvas::new(8, 4);
+        for (i, pixel) in image.grid.iter_mut().enumerate() {
+            *pixel = Color::new(i as Float, 0.5, 1.0);
+        }
+        let mut world = World::new();
+        world.background = Background::Environment(EnvironmentMap::new(image.clone()));
+        let rendered = Camera::equirectangular(8, 4).render(&world);
+        for (a, b) in image.grid.iter().zip(&rendered.grid) {
+            assert!((a.red - b.red).abs() < 1.0e-3, "{:?} {:?}", a, b);
+        }
+    }
+    #[test]
+    fn rays_are_traced_within_the_shutter_interval() {
+        let mut camera = Camera::new(11, 11, consts::PI / 2.0);
+        assert_eq!(camera.ray_for_pixel(5, 5).time, 0.0);
+        camera.shutter_open = 0.2;
+        camera.shutter_close = 0.6;
+        assert!((camera.ray_for_pixel(5, 5).time - 0.4).abs() < 1.0e-6);
+        assert_eq!(camera.shutter_time(1.0), 0.6);
+    }
+    #[test]
+    fn fast_objects_are_blurred() {
+        use super::super::motion::Motion;
+        use super::super::sphere::Sphere;
+        // a bright ball crossing the view from left to right while the shutter is open
+        let mut world = World::new();
+        let mut ball = Sphere::new();
+        ball.material.emissive = WHITE;
+        ball.material.diffuse = 0.0;
+        ball.material.ambient = 0.0;
+        ball.material.specular = 0.0;
+        ball.motion = Some(Motion::linear(
+            Matrix::translation(-2.0, 0.0, 0.0) * &Matrix::scaling(0.5, 0.5, 0.5),
+            Matrix::translation(2.0, 0.0, 0.0) * &Matrix::scaling(0.5, 0.5, 0.5),
+        ));
+        world.objects.insert(ball.get_id(), Box::new(ball));
+        let mut camera = Camera::new(21, 1, consts::PI / 2.0);
+        camera.transform = Matrix::view_transformation(
+            Point3::new(0, 0, -5),
+            Point3::new(0, 0, 0),
+            Vector3::new(0, 1, 0),
         );
+        camera.integrator = Integrator::PathTracer {
+            samples: 64,
+            max_depth: 1,
+        };
+        let still = camera.render(&world);
+        // without an open shutter the ball sits at its first keyframe
+        assert_eq!(still.pixel_at(0, 10), BLACK);
+        camera.shutter_close = 1.0;
+        let blurred = camera.render(&world);
+        // the ball leaves a partly covered streak across the middle
+        let center = blurred.pixel_at(0, 10);
+        assert!(center.red > 0.05 && center.red < 0.5);
+        assert!(blurred.pixel_at(0, 8).red > 0.05);
+        assert!(blurred.pixel_at(0, 12).red > 0.05);
+    }
+    #[test]
+    fn render_matches_single_pixels() {
+        let world = World::default();
+        // 11 columns, packets of pixels wrap around rows and the last one is short
+        let mut camera = Camera::new(11, 7, consts::PI / 2.0);
+        camera.transform = Matrix::view_transformation(
+            Point3::new(0, 0, -5),
+            Point3::new(0, 0, 0),
+            Vector3::new(0, 1, 0),
+        );
+        let image = camera.render(&world);
+        for row in 0..7 {
+            for col in 0..11 {
+                assert_eq!(
+                    image.pixel_at(row, col),
+                    camera.color_for_pixel(&world, col, row)
+                );
+            }
+        }
     }
 }
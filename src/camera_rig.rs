@@ -0,0 +1,189 @@
+use super::animation::*;
+use super::camera::Camera;
+use super::error::*;
+use super::float_cmp::consts;
+use super::float_cmp::Float;
+use super::matrix::Matrix;
+use super::vec3::{Point3, Vector3};
+use super::world::World;
+
+// Moves the camera over a sequence of frames, see `Animation::rig`. The up
+// direction is always +y.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CameraRig {
+    // circles `target` at `radius`, `height` above it, one full turn every
+    // `period` frames, starting on the -z side at frame 0
+    Orbit {
+        target: Point3,
+        radius: Float,
+        height: Float,
+        period: Float,
+    },
+    // moves along the `path` while looking at `target`
+    Dolly {
+        path: Track<Point3>,
+        target: Track<Point3>,
+    },
+    // follows the origin of the object with the id wherever it is moved
+    LookAt {
+        position: Track<Point3>,
+        obj_id: usize,
+    },
+}
+
+impl CameraRig {
+    pub fn orbit(target: Point3, radius: Float, height: Float, period: Float) -> Self {
+        Self::try_orbit(target, radius, height, period).unwrap_or_else(|error| panic!("{}", error))
+    }
+    pub fn try_orbit(target: Point3, radius: Float, height: Float, period: Float) -> Result<Self> {
+        Ok(Self::Orbit {
+            target,
+            radius,
+            height,
+            period: check_positive("orbit period", period)?,
+        })
+    }
+    // smooth spline through the keyframed positions, looking at a fixed target
+    pub fn dolly(path: Vec<(Float, Point3)>, target: Point3) -> Self {
+        Self::Dolly {
+            path: Track::new(path, Interpolation::CatmullRom),
+            target: Track::constant(target),
+        }
+    }
+    pub fn look_at(position: Point3, obj_id: usize) -> Self {
+        Self::LookAt {
+            position: Track::constant(position),
+            obj_id,
+        }
+    }
+    // camera position and the point it looks at, fails if the object looked
+    // at isn't part of `world`
+    pub fn eye_at(&self, world: &World, frame: Float) -> Result<(Point3, Point3)> {
+        Ok(match self {
+            Self::Orbit {
+                target,
+                radius,
+                height,
+                period,
+            } => {
+                let angle = 2.0 * consts::PI * frame / period;
+                let offset = Vector3::new(angle.sin() * radius, *height, -angle.cos() * radius);
+                (*target + offset, *target)
+            }
+            Self::Dolly { path, target } => (path.value_at(frame), target.value_at(frame)),
+            Self::LookAt { position, obj_id } => {
+                let object = world
+                    .objects
+                    .get(obj_id)
+                    .ok_or(Error::UnknownObject(*obj_id))?;
+                (
+                    position.value_at(frame),
+                    object.transform() * Point3::new(0, 0, 0),
+                )
+            }
+        })
+    }
+    pub fn transform_at(&self, world: &World, frame: Float) -> Result<Matrix> {
+        let (from, to) = self.eye_at(world, frame)?;
+        Ok(Matrix::view_transformation(from, to, Vector3::new(0, 1, 0)))
+    }
+    // `camera` looking from where the rig is at `frame`
+    pub fn camera_at(&self, camera: &Camera, world: &World, frame: Float) -> Result<Camera> {
+        let mut camera = camera.clone();
+        camera.transform = self.transform_at(world, frame)?;
+        Ok(camera)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::object::Object;
+    use super::super::sphere::Sphere;
+    use super::*;
+
+    #[test]
+    fn orbit_around_target() {
+        let world = World::new();
+        let rig = CameraRig::orbit(Point3::new(1, 0, 0), 5.0, 2.0, 40.0);
+        let (from, to) = rig.eye_at(&world, 0.0).unwrap();
+        assert_eq!(from, Point3::new(1, 2, -5));
+        assert_eq!(to, Point3::new(1, 0, 0));
+        assert_eq!(rig.eye_at(&world, 10.0).unwrap().0, Point3::new(6, 2, 0));
+        assert_eq!(rig.eye_at(&world, 20.0).unwrap().0, Point3::new(1, 2, 5));
+        // a full turn comes back to the start
+        assert_eq!(
+            rig.transform_at(&world, 40.0).unwrap(),
+            rig.transform_at(&world, 0.0).unwrap()
+        );
+    }
+    #[test]
+    fn dolly_along_spline() {
+        let world = World::new();
+        let rig = CameraRig::dolly(
+            vec![
+                (0.0, Point3::new(-4, 1, -8)),
+                (10.0, Point3::new(0, 1, -6)),
+                (20.0, Point3::new(4, 1, -8)),
+            ],
+            Point3::new(0, 0, 0),
+        );
+        assert_eq!(rig.eye_at(&world, 10.0).unwrap().0, Point3::new(0, 1, -6));
+        // bends smoothly through the middle keyframe instead of a sharp corner
+        let (from, _) = rig.eye_at(&world, 8.0).unwrap();
+        assert!(from.z > -6.5 && from.z < -6.0);
+    }
+    #[test]
+    fn look_at_moving_object() {
+        let mut world = World::new();
+        let mut sphere = Sphere::new();
+        sphere.set_transform(Matrix::translation(3.0, 1.0, 0.0));
+        let id = sphere.get_id();
+        world.objects.insert(id, Box::new(sphere));
+        let rig = CameraRig::look_at(Point3::new(0, 1, -5), id);
+        let camera = rig
+            .camera_at(&Camera::new(11, 11, consts::PI / 2.0), &world, 0.0)
+            .unwrap();
+        assert_eq!(
+            camera.transform,
+            Matrix::view_transformation(
+                Point3::new(0, 1, -5),
+                Point3::new(3, 1, 0),
+                Vector3::new(0, 1, 0)
+            )
+        );
+        // the center pixel sees the object
+        let ray = camera.ray_for_pixel(5, 5);
+        assert!(world.intersect_with(&ray).hit().is_some());
+    }
+    #[test]
+    fn animation_drives_the_rig() {
+        let mut world = World::new();
+        let mut camera = Camera::new(11, 11, consts::PI / 2.0);
+        let mut animation = Animation::new();
+        let rig = CameraRig::orbit(Point3::new(0, 0, 0), 5.0, 0.0, 4.0);
+        animation.rig = Some(rig.clone());
+        animation.apply(1.0, &mut world, &mut camera).unwrap();
+        assert_eq!(camera.transform, rig.transform_at(&world, 1.0).unwrap());
+    }
+    #[test]
+    fn look_at_missing_object() {
+        let mut world = World::new();
+        let mut camera = Camera::new(11, 11, consts::PI / 2.0);
+        let rig = CameraRig::look_at(Point3::new(0, 1, -5), usize::MAX);
+        assert_eq!(
+            rig.eye_at(&world, 0.0),
+            Err(Error::UnknownObject(usize::MAX))
+        );
+        let mut animation = Animation::new();
+        animation.rig = Some(rig);
+        let transform = camera.transform.clone();
+        assert!(animation.apply(0.0, &mut world, &mut camera).is_err());
+        assert_eq!(camera.transform, transform);
+    }
+    #[test]
+    fn orbit_period_has_to_be_positive() {
+        let target = Point3::new(0, 0, 0);
+        assert!(CameraRig::try_orbit(target, 5.0, 1.0, 0.0).is_err());
+        assert!(CameraRig::try_orbit(target, 5.0, 1.0, 40.0).is_ok());
+    }
+}
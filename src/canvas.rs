@@ -1,7 +1,11 @@
 use super::color::*;
+use super::error::*;
+use super::float_cmp::Float;
+use super::tile::RenderedTile;
 use std::fs::File;
 use std::io::prelude::*;
 
+#[derive(Debug, Clone)]
 pub struct Canvas {
     pub width: u32,
     pub height: u32,
@@ -24,32 +28,34 @@ impl Canvas {
         }
     }
     pub fn pixel_at(&self, row: u32, col: u32) -> Color {
-        assert!(
-            row < self.height,
-            "row value {} overflows height of canvas",
-            row
-        );
-        assert!(
-            col < self.width,
-            "col value {} overflows width of canvas",
-            col
-        );
-        self.grid[(row * self.width + col) as usize]
+        self.try_pixel_at(row, col)
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+    pub fn try_pixel_at(&self, row: u32, col: u32) -> Result<Color> {
+        Ok(self.grid[self.index(row, col)?])
     }
     pub fn set_pixel(&mut self, row: u32, col: u32, color: Color) {
-        assert!(
-            row < self.height,
-            "row value {} overflows height of canvas",
-            row
-        );
-        assert!(
-            col < self.width,
-            "col value {} overflows width of canvas",
-            col
-        );
-        self.grid[(row * self.width + col) as usize] = color
+        self.try_set_pixel(row, col, color)
+            .unwrap_or_else(|error| panic!("{}", error))
     }
-    fn clamp(i: f32) -> u8 {
+    pub fn try_set_pixel(&mut self, row: u32, col: u32, color: Color) -> Result<()> {
+        let index = self.index(row, col)?;
+        self.grid[index] = color;
+        Ok(())
+    }
+    fn index(&self, row: u32, col: u32) -> Result<usize> {
+        if row < self.height && col < self.width {
+            Ok((row * self.width + col) as usize)
+        } else {
+            Err(Error::PixelOutOfBounds { row, col })
+        }
+    }
+    pub fn set_tile(&mut self, rendered: &RenderedTile) {
+        for ((col, row), color) in rendered.tile.pixels().zip(&rendered.pixels) {
+            self.set_pixel(row, col, *color);
+        }
+    }
+    fn clamp(i: Float) -> u8 {
         let scaled = (i * 255.0) as i32;
         if scaled > 255 {
             255
@@ -60,6 +66,11 @@ impl Canvas {
         }
     }
     pub fn save_as_ppm(&self, filename: &str) {
+        let mut file = File::create(filename).expect("Unable to create file");
+        file.write_all(self.to_ppm().as_bytes())
+            .expect("Unable to write to file");
+    }
+    pub fn to_ppm(&self) -> String {
         let mut ppm = format!("P3\n{} {}\n255\n", self.width, self.height);
         for pixel in &self.grid {
             ppm += format!(
@@ -70,9 +81,7 @@ impl Canvas {
             )
             .as_str();
         }
-        let mut file = File::create(filename).expect("Unable to create file");
-        file.write_all(ppm.as_bytes())
-            .expect("Unable to write to file");
+        ppm
     }
 }
 
@@ -80,6 +89,17 @@ impl Canvas {
 mod tests {
     use super::*;
     #[test]
+    fn pixels_outside_of_canvas() {
+        let mut c = Canvas::new(5, 4);
+        assert_eq!(
+            c.try_pixel_at(4, 0),
+            Err(Error::PixelOutOfBounds { row: 4, col: 0 })
+        );
+        assert!(c.try_set_pixel(0, 5, WHITE).is_err());
+        assert!(c.try_set_pixel(3, 4, WHITE).is_ok());
+        assert_eq!(c.try_pixel_at(3, 4), Ok(WHITE));
+    }
+    #[test]
     fn create_canvas() {
         let c = Canvas::new(5, 6);
         assert_eq!(c.width, 5);
@@ -0,0 +1,170 @@
+use super::camera::Camera;
+use super::canvas::Canvas;
+use super::encode::*;
+use super::tile::*;
+use super::world::World;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+// ends in `FLOAT_BYTES`, f32 and f64 builds can't read each other's checkpoints
+#[cfg(not(feature = "f64"))]
+const MAGIC: &[u8; 8] = b"H3DCKP3\x04";
+#[cfg(feature = "f64")]
+const MAGIC: &[u8; 8] = b"H3DCKP3\x08";
+
+// Finished tiles of a partially rendered image.
+//
+// File layout: magic, width, height, scene hash and the list of rendered
+// tiles, see `encode`. Colors are stored bit exact so a resumed render matches
+// an uninterrupted one.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub width: u32,
+    pub height: u32,
+    // `scene_hash` of the render the tiles belong to
+    pub scene: u64,
+    tiles: Vec<RenderedTile>,
+    // the tiles of `tiles`, so checking every tile of a resumed render is linear
+    done: HashSet<Tile>,
+}
+
+// Fingerprint of the encoded camera, including its integrator and sample
+// count, and world. Stable between runs, unlike `std::hash`.
+pub fn scene_hash(camera: &Camera, world: &World) -> u64 {
+    let mut bytes = Vec::new();
+    camera
+        .encode(&mut bytes)
+        .and_then(|_| world.encode(&mut bytes))
+        .expect("encoding into memory doesn't fail");
+    // FNV-1a
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+impl Checkpoint {
+    pub fn new(width: u32, height: u32, scene: u64) -> Self {
+        Self {
+            width,
+            height,
+            scene,
+            tiles: Vec::new(),
+            done: HashSet::new(),
+        }
+    }
+    pub fn push(&mut self, tile: RenderedTile) {
+        self.done.insert(tile.tile);
+        self.tiles.push(tile);
+    }
+    pub fn tiles(&self) -> &[RenderedTile] {
+        &self.tiles
+    }
+    pub fn is_done(&self, tile: &Tile) -> bool {
+        self.done.contains(tile)
+    }
+    pub fn n_pixels(&self) -> usize {
+        self.tiles.iter().map(|t| t.tile.n_pixels()).sum()
+    }
+    pub fn apply(&self, canvas: &mut Canvas) {
+        for tile in &self.tiles {
+            canvas.set_tile(tile);
+        }
+    }
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        self.width.encode(writer)?;
+        self.height.encode(writer)?;
+        self.scene.encode(writer)?;
+        self.tiles.encode(writer)
+    }
+    pub fn read_from(reader: &mut impl Read) -> io::Result<Self> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if magic[..7] != MAGIC[..7] {
+            return Err(invalid_data("not a checkpoint file"));
+        }
+        if magic[7] != FLOAT_BYTES as u8 {
+            return Err(invalid_data(
+                "checkpoint was written with a different float precision",
+            ));
+        }
+        let width = u32::decode(reader)?;
+        let height = u32::decode(reader)?;
+        let scene = u64::decode(reader)?;
+        let image = Tile::new(0, 0, width, height);
+        let n_tiles = u32::decode(reader)?;
+        let mut checkpoint = Self::new(width, height, scene);
+        for _ in 0..n_tiles {
+            checkpoint.push(RenderedTile::decode_within(reader, &image)?);
+        }
+        Ok(checkpoint)
+    }
+    // writes to a temporary file first so a crash mid-save keeps the previous checkpoint
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let tmp_path = path.with_extension("tmp");
+        {
+            let mut writer = BufWriter::new(File::create(&tmp_path)?);
+            self.write_to(&mut writer)?;
+            writer.flush()?;
+        }
+        fs::rename(&tmp_path, path)
+    }
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        Self::read_from(&mut reader)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::color::*;
+    use super::*;
+    #[test]
+    fn checkpoint_roundtrip() {
+        let mut checkpoint = Checkpoint::new(4, 3, 7);
+        checkpoint.push(RenderedTile {
+            tile: Tile::new(0, 0, 2, 1),
+            pixels: vec![RED, Color::new(0.1, -0.25, 1.0e-7)],
+            samples: 1,
+        });
+        checkpoint.push(RenderedTile {
+            tile: Tile::new(2, 1, 2, 2),
+            pixels: vec![WHITE, BLACK, GREEN, BLUE],
+            samples: 16,
+        });
+        let mut bytes = Vec::new();
+        checkpoint.write_to(&mut bytes).unwrap();
+        let loaded = Checkpoint::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(loaded.width, 4);
+        assert_eq!(loaded.height, 3);
+        assert_eq!(loaded.scene, 7);
+        assert_eq!(loaded.tiles.len(), 2);
+        assert_eq!(loaded.n_pixels(), 6);
+        assert!(loaded.is_done(&Tile::new(2, 1, 2, 2)));
+        assert!(!loaded.is_done(&Tile::new(0, 1, 2, 2)));
+        assert_eq!(loaded.tiles[1].samples, 16);
+        for (a, b) in checkpoint.tiles.iter().zip(&loaded.tiles) {
+            assert_eq!(a.pixels, b.pixels);
+        }
+    }
+    #[test]
+    fn reject_corrupt_checkpoint() {
+        let mut checkpoint = Checkpoint::new(2, 2, 7);
+        checkpoint.push(RenderedTile {
+            tile: Tile::new(0, 0, 2, 2),
+            pixels: vec![WHITE; 4],
+            samples: 1,
+        });
+        let mut bytes = Vec::new();
+        checkpoint.write_to(&mut bytes).unwrap();
+        assert!(Checkpoint::read_from(&mut &bytes[..bytes.len() - 1]).is_err());
+        bytes[7] = 12 - bytes[7];
+        let error = Checkpoint::read_from(&mut bytes.as_slice()).unwrap_err();
+        assert!(error.to_string().contains("precision"));
+        bytes[0] = b'X';
+        assert!(Checkpoint::read_from(&mut bytes.as_slice()).is_err());
+    }
+}
@@ -1,10 +1,11 @@
+use super::float_cmp::Float;
 use std::ops::{Add, Mul, Sub};
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub struct Color {
-    pub red: f32,
-    pub green: f32,
-    pub blue: f32,
+    pub red: Float,
+    pub green: Float,
+    pub blue: Float,
 }
 
 // predefined constants
@@ -60,7 +61,7 @@ pub const ORANGE: Color = Color {
 };
 
 impl Color {
-    pub fn new(r: f32, g: f32, b: f32) -> Self {
+    pub fn new(r: Float, g: Float, b: Float) -> Self {
         Self {
             red: r,
             green: g,
@@ -103,17 +104,17 @@ impl Mul<i32> for Color {
 
     fn mul(self, rhs: i32) -> Self {
         Self {
-            red: self.red * (rhs as f32),
-            green: self.green * (rhs as f32),
-            blue: self.blue * (rhs as f32),
+            red: self.red * (rhs as Float),
+            green: self.green * (rhs as Float),
+            blue: self.blue * (rhs as Float),
         }
     }
 }
 
-impl Mul<f32> for Color {
+impl Mul<Float> for Color {
     type Output = Self;
 
-    fn mul(self, rhs: f32) -> Self {
+    fn mul(self, rhs: Float) -> Self {
         Self {
             red: self.red * rhs,
             green: self.green * rhs,
@@ -136,7 +137,7 @@ impl Mul<Self> for Color {
 }
 
 #[cfg(test)]
-pub mod tests {
+mod tests {
     use super::*;
 
     #[test]
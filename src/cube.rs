@@ -0,0 +1,256 @@
+use super::color::Color;
+use super::encode::*;
+use super::error::*;
+use super::float_cmp::Float;
+use super::float_cmp::EPSILON;
+use super::intersection::*;
+use super::light::Light;
+use super::material::Material;
+use super::matrix::Matrix;
+use super::motion::Motion;
+use super::object::*;
+use super::ray::*;
+use super::sampling::Rng;
+use super::vec3::{Point3, Vector3};
+use std::io;
+
+// axis aligned cube from -1 to 1 along every axis in object space
+#[derive(Debug)]
+pub struct Cube {
+    id: usize,
+    transform: Matrix,
+    pub material: Material,
+    pub flags: ObjectFlags,
+    // moves the object while the camera shutter is open, see `Motion`
+    pub motion: Option<Motion>,
+    // inverse of `transform`, see `transform_inverse`
+    inverse: Matrix,
+}
+
+impl Object for Cube {
+    fn intersect_into(&self, actual_ray: &Ray, intersections: &mut Intersections) {
+        let ray = self.object_ray(actual_ray);
+        let (x_min, x_max) = check_axis(ray.origin.x, ray.direction.x);
+        let (y_min, y_max) = check_axis(ray.origin.y, ray.direction.y);
+        let (z_min, z_max) = check_axis(ray.origin.z, ray.direction.z);
+        let t_min = x_min.max(y_min).max(z_min);
+        let t_max = x_max.min(y_max).min(z_max);
+        if t_min <= t_max {
+            intersections.push(Intersection {
+                t: t_min,
+                obj_id: self.id,
+            });
+            intersections.push(Intersection {
+                t: t_max,
+                obj_id: self.id,
+            });
+        }
+    }
+    fn normal_at_time(&self, world_point: Point3, time: Float) -> Vector3 {
+        let inverse = self.inverse_transform_at(time);
+        let p = &inverse * world_point;
+        // the face is the one of the largest coordinate
+        let max = p.x.abs().max(p.y.abs()).max(p.z.abs());
+        let object_normal = if max == p.x.abs() {
+            Vector3::new(p.x, 0.0, 0.0)
+        } else if max == p.y.abs() {
+            Vector3::new(0.0, p.y, 0.0)
+        } else {
+            Vector3::new(0.0, 0.0, p.z)
+        };
+        (inverse.transpose() * object_normal).normalize()
+    }
+    fn lighting_at(
+        &self,
+        point: Point3,
+        eye_v: Vector3,
+        normal_v: Vector3,
+        light: Light,
+        in_shadow: bool,
+    ) -> Color {
+        let eye_v = eye_v.normalize();
+        self.material
+            .lighting(&self.transform, light, point, eye_v, normal_v, in_shadow)
+    }
+    fn mut_material(&mut self) -> &mut Material {
+        &mut self.material
+    }
+    fn material(&self) -> &Material {
+        &self.material
+    }
+    fn transform(&self) -> &Matrix {
+        &self.transform
+    }
+    fn inverse_transform(&self) -> &Matrix {
+        &self.inverse
+    }
+    fn try_set_transform(&mut self, transform: Matrix) -> Result<()> {
+        self.inverse = transform_inverse(&transform)?;
+        self.transform = transform;
+        Ok(())
+    }
+    fn motion(&self) -> Option<&Motion> {
+        self.motion.as_ref()
+    }
+    fn mut_motion(&mut self) -> &mut Option<Motion> {
+        &mut self.motion
+    }
+    fn flags(&self) -> &ObjectFlags {
+        &self.flags
+    }
+    fn mut_flags(&mut self) -> &mut ObjectFlags {
+        &mut self.flags
+    }
+    fn has_surface_samples(&self) -> bool {
+        true
+    }
+    fn sample_surface(&self, time: Float, rng: &mut Rng) -> Option<SurfaceSample> {
+        let transform = self.transform_at(time);
+        // faces stay parallelograms under the transform, a face of normal n
+        // covers 4 * |det(M)| * |M^-T n| of world space area
+        let inverse_transpose = transform.inverse_matrix().transpose();
+        let det = transform.determinant().abs();
+        let normals = [
+            &inverse_transpose * Vector3::new(1, 0, 0),
+            &inverse_transpose * Vector3::new(0, 1, 0),
+            &inverse_transpose * Vector3::new(0, 0, 1),
+        ];
+        let areas = normals.map(|normal| 4.0 * det * normal.magnitude());
+        let half_area = areas[0] + areas[1] + areas[2];
+        // a pair of opposite faces in proportion to its area, then one of them
+        let mut u = rng.next_float() * half_area;
+        let mut axis = 0;
+        while axis < 2 && u >= areas[axis] {
+            u -= areas[axis];
+            axis += 1;
+        }
+        let side = if rng.next_float() < 0.5 { 1.0 } else { -1.0 };
+        let mut p = [0.0; 3];
+        p[axis] = side;
+        p[(axis + 1) % 3] = 2.0 * rng.next_float() - 1.0;
+        p[(axis + 2) % 3] = 2.0 * rng.next_float() - 1.0;
+        Some(SurfaceSample {
+            point: &transform * Point3::new(p[0], p[1], p[2]),
+            normal: (normals[axis] * side).normalize(),
+            pdf: 1.0 / (2.0 * half_area),
+        })
+    }
+    fn encode_tagged(&self, writer: &mut dyn io::Write) -> io::Result<()> {
+        CUBE_TAG.encode(writer)?;
+        self.encode(writer)
+    }
+}
+
+// distances along the ray to the two planes at -1 and 1 of one axis
+fn check_axis(origin: Float, direction: Float) -> (Float, Float) {
+    let (t_min, t_max) = if direction.abs() >= EPSILON {
+        ((-1.0 - origin) / direction, (1.0 - origin) / direction)
+    } else {
+        // parallel to the planes, either always or never between them
+        (
+            (-1.0 - origin) * Float::INFINITY,
+            (1.0 - origin) * Float::INFINITY,
+        )
+    };
+    if t_min > t_max {
+        (t_max, t_min)
+    } else {
+        (t_min, t_max)
+    }
+}
+
+impl Cube {
+    pub fn new() -> Self {
+        let id = get_object_uid();
+        Self {
+            id,
+            transform: Matrix::identity_matrix(4),
+            material: Material::default(),
+            flags: ObjectFlags::default(),
+            motion: None,
+            inverse: Matrix::identity_matrix(4),
+        }
+    }
+    pub fn get_id(&self) -> usize {
+        self.id
+    }
+}
+
+impl Default for Cube {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::float_cmp;
+    use super::*;
+    #[test]
+    fn ray_intersects_cube() {
+        let cube = Cube::new();
+        let cases = [
+            (Point3::new(5.0, 0.5, 0.0), Vector3::new(-1, 0, 0), 4.0, 6.0),
+            (Point3::new(-5.0, 0.5, 0.0), Vector3::new(1, 0, 0), 4.0, 6.0),
+            (Point3::new(0.5, 5.0, 0.0), Vector3::new(0, -1, 0), 4.0, 6.0),
+            (Point3::new(0.5, 0.0, -5.0), Vector3::new(0, 0, 1), 4.0, 6.0),
+            (Point3::new(0.0, 0.5, 0.0), Vector3::new(0, 0, 1), -1.0, 1.0),
+        ];
+        for (origin, direction, t1, t2) in &cases {
+            let xs = cube.intersection(&Ray::new(*origin, *direction));
+            assert_eq!(xs.len(), 2);
+            assert_eq!(xs[0].t, *t1);
+            assert_eq!(xs[1].t, *t2);
+        }
+    }
+    #[test]
+    fn ray_misses_cube() {
+        let cube = Cube::new();
+        let cases = [
+            (Point3::new(-2, 0, 0), Vector3::new(0.2673, 0.5345, 0.8018)),
+            (Point3::new(0, -2, 0), Vector3::new(0.8018, 0.2673, 0.5345)),
+            (Point3::new(2, 0, 2), Vector3::new(0, 0, -1)),
+            (Point3::new(0, 2, 2), Vector3::new(0, -1, 0)),
+        ];
+        for (origin, direction) in &cases {
+            assert!(cube.intersection(&Ray::new(*origin, *direction)).is_empty());
+        }
+    }
+    #[test]
+    fn cube_normal() {
+        let mut cube = Cube::new();
+        assert_eq!(
+            cube.normal_at(Point3::new(1.0, 0.5, -0.8)),
+            Vector3::new(1, 0, 0)
+        );
+        assert_eq!(
+            cube.normal_at(Point3::new(-0.4, 0.3, -1.0)),
+            Vector3::new(0, 0, -1)
+        );
+        assert_eq!(cube.normal_at(Point3::new(1, 1, 1)), Vector3::new(1, 0, 0));
+        cube.set_transform(Matrix::translation(0.0, 2.0, 0.0));
+        assert_eq!(
+            cube.normal_at(Point3::new(0.3, 1.0, 0.1)),
+            Vector3::new(0, -1, 0)
+        );
+    }
+    #[test]
+    fn sample_surface_of_box() {
+        // 4 x 2 x 1 box with faces of area 8, 4 and 2
+        let mut cube = Cube::new();
+        cube.set_transform(Matrix::translation(1.0, 0.0, 0.0) * &Matrix::scaling(2.0, 1.0, 0.5));
+        let mut rng = Rng::new(7);
+        let n = 20000;
+        let mut on_top = 0;
+        for _ in 0..n {
+            let sample = cube.sample_surface(0.0, &mut rng).unwrap();
+            assert!(float_cmp::equal(sample.pdf, 1.0 / 28.0));
+            assert_eq!(sample.normal, cube.normal_at(sample.point));
+            if float_cmp::equal(sample.point.y, 1.0) {
+                on_top += 1;
+            }
+        }
+        // the top face has 4 of the 28 units of area
+        assert!((on_top as Float / n as Float - 4.0 / 28.0).abs() < 0.01);
+    }
+}
@@ -0,0 +1,213 @@
+use super::camera::Camera;
+use super::canvas::Canvas;
+use super::encode::*;
+use super::tile::*;
+use super::world::World;
+use std::collections::VecDeque;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+// Rendering split across worker processes over TCP.
+//
+// The coordinator connects to every worker, which announces its `FLOAT_BYTES`,
+// sends its own with the encoded camera and world once and then hands out one
+// tile at a time, every worker answers with the rendered tile. Both sides drop
+// connections to builds of the other float precision. Tiles of a worker that
+// fails are handed to the remaining ones.
+// A worker serves every connection on its own thread, so listing the same
+// address several times keeps several of its cores busy.
+
+// message tags sent from the coordinator to a worker
+const SCENE: u32 = 0;
+const TILE: u32 = 1;
+const DONE: u32 = 2;
+
+// a peer that stays silent this long is considered gone, generous since a
+// worker may spend minutes on a path traced tile and a coordinator keeps idle
+// connections open until the other workers finish their round
+const TIMEOUT: Duration = Duration::from_secs(600);
+// limit for reaching a worker and hearing its greeting, short so a dead
+// address doesn't hold up the render
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn configure(stream: &TcpStream) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))
+}
+
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+}
+
+impl Connection {
+    fn open(address: &SocketAddr, scene: &[u8]) -> io::Result<Self> {
+        let stream = TcpStream::connect_timeout(address, CONNECT_TIMEOUT)?;
+        stream.set_read_timeout(Some(CONNECT_TIMEOUT))?;
+        let mut connection = Self {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: BufWriter::new(stream.try_clone()?),
+        };
+        check_precision(&mut connection.reader)?;
+        configure(&stream)?;
+        SCENE.encode(&mut connection.writer)?;
+        FLOAT_BYTES.encode(&mut connection.writer)?;
+        connection.writer.write_all(scene)?;
+        connection.writer.flush()?;
+        Ok(connection)
+    }
+    fn render(&mut self, tile: Tile) -> io::Result<RenderedTile> {
+        TILE.encode(&mut self.writer)?;
+        tile.encode(&mut self.writer)?;
+        self.writer.flush()?;
+        let rendered = RenderedTile::decode_within(&mut self.reader, &tile)?;
+        if rendered.tile != tile {
+            return Err(invalid_data("worker answered with a different tile"));
+        }
+        Ok(rendered)
+    }
+    fn close(mut self) -> io::Result<()> {
+        DONE.encode(&mut self.writer)?;
+        self.writer.flush()
+    }
+}
+
+fn check_precision(reader: &mut impl io::Read) -> io::Result<()> {
+    if u32::decode(reader)? != FLOAT_BYTES {
+        return Err(invalid_data("peer uses a different float precision"));
+    }
+    Ok(())
+}
+
+// Renders `camera` on the given workers and assembles the tiles into a canvas.
+// Fails if the scene can't be encoded or once every worker has failed.
+pub fn render_distributed(
+    camera: &Camera,
+    world: &World,
+    settings: &TileSettings,
+    workers: &[SocketAddr],
+) -> io::Result<Canvas> {
+    let mut scene = Vec::new();
+    camera.encode(&mut scene)?;
+    world.encode(&mut scene)?;
+
+    let mut last_error = io::Error::new(io::ErrorKind::NotConnected, "no workers given");
+    let mut connections = Vec::new();
+    // connecting in parallel, unreachable workers time out together
+    let opened: Vec<io::Result<Connection>> = thread::scope(|scope| {
+        let handles: Vec<_> = workers
+            .iter()
+            .map(|address| {
+                let scene = &scene;
+                scope.spawn(move || Connection::open(address, scene))
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+    for result in opened {
+        match result {
+            Ok(connection) => connections.push(connection),
+            Err(e) => last_error = e,
+        }
+    }
+
+    let queue = Mutex::new(
+        Tile::split(camera.hsize, camera.vsize, settings)
+            .into_iter()
+            .collect::<VecDeque<_>>(),
+    );
+    let rendered = Mutex::new(Vec::new());
+    // tiles given back by a failing worker may be left once the others have
+    // drained the queue, so keep going in rounds with the surviving workers
+    while !queue.lock().unwrap().is_empty() {
+        if connections.is_empty() {
+            return Err(last_error);
+        }
+        let results: Vec<Result<Connection, io::Error>> = thread::scope(|scope| {
+            let handles: Vec<_> = connections
+                .drain(..)
+                .map(|mut connection| {
+                    let queue = &queue;
+                    let rendered = &rendered;
+                    scope.spawn(move || loop {
+                        let tile = match queue.lock().unwrap().pop_front() {
+                            Some(tile) => tile,
+                            None => return Ok(connection),
+                        };
+                        match connection.render(tile) {
+                            Ok(result) => rendered.lock().unwrap().push(result),
+                            Err(e) => {
+                                queue.lock().unwrap().push_back(tile);
+                                return Err(e);
+                            }
+                        }
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        for result in results {
+            match result {
+                Ok(connection) => connections.push(connection),
+                Err(e) => last_error = e,
+            }
+        }
+    }
+    for connection in connections {
+        // the image is complete, a worker going away now doesn't matter
+        let _ = connection.close();
+    }
+
+    let mut canvas = Canvas::new(camera.hsize, camera.vsize);
+    for tile in rendered.into_inner().unwrap().iter() {
+        canvas.set_tile(tile);
+    }
+    Ok(canvas)
+}
+
+// Serves a single coordinator until it is done with the connection.
+pub fn serve_worker(stream: TcpStream) -> io::Result<()> {
+    configure(&stream)?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+    FLOAT_BYTES.encode(&mut writer)?;
+    writer.flush()?;
+    let mut scene = None;
+    loop {
+        match u32::decode(&mut reader)? {
+            SCENE => {
+                check_precision(&mut reader)?;
+                let camera = Camera::decode(&mut reader)?;
+                let world = World::decode(&mut reader)?;
+                scene = Some((camera, world));
+            }
+            TILE => {
+                let tile = Tile::decode(&mut reader)?;
+                let (camera, world) = scene
+                    .as_ref()
+                    .ok_or_else(|| invalid_data("tile requested before the scene was sent"))?;
+                camera.render_tile(world, tile).encode(&mut writer)?;
+                writer.flush()?;
+            }
+            DONE => return Ok(()),
+            _ => return Err(invalid_data("unknown message")),
+        }
+    }
+}
+
+// Accepts coordinators forever, each connection is served on its own thread.
+pub fn run_worker(listener: &TcpListener) -> io::Result<()> {
+    for stream in listener.incoming() {
+        let stream = stream?;
+        thread::spawn(move || {
+            if let Err(e) = serve_worker(stream) {
+                eprintln!("worker connection failed: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
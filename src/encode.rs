@@ -0,0 +1,858 @@
+use super::ambient_occlusion::AmbientOcclusion;
+use super::background::*;
+use super::brdf::Brdf;
+use super::camera::*;
+use super::canvas::Canvas;
+use super::color::Color;
+use super::cube::Cube;
+use super::float_cmp::Float;
+use super::light::Light;
+use super::material::Material;
+use super::matrix::Matrix;
+use super::motion::Motion;
+use super::object::{Object, ObjectFlags};
+use super::path_tracer::Integrator;
+use super::pattern::*;
+use super::plane::Plane;
+use super::sphere::Sphere;
+use super::subsurface::Subsurface;
+use super::tile::*;
+use super::vec3::{Point3, Vector3};
+use super::volume::*;
+use super::world::World;
+use std::io::{self, Read, Write};
+
+// Little endian binary encoding of scene and render data,
+// used for checkpoint files and for sending work to render workers.
+// Floats are stored as `Float`, see `FLOAT_BYTES`.
+pub trait Encode: Sized {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()>;
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self>;
+}
+
+// size of an encoded `Float`, checkpoints and workers carry it so f32 and
+// f64 builds refuse each other's data instead of misreading it
+pub const FLOAT_BYTES: u32 = std::mem::size_of::<Float>() as u32;
+
+pub fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// tags identifying the concrete type behind a `dyn Object`
+pub const SPHERE_TAG: u32 = 0;
+pub const PLANE_TAG: u32 = 1;
+pub const CUBE_TAG: u32 = 2;
+
+impl Encode for u32 {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&self.to_le_bytes())
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        let mut bytes = [0; 4];
+        reader.read_exact(&mut bytes)?;
+        Ok(Self::from_le_bytes(bytes))
+    }
+}
+
+impl Encode for u64 {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&self.to_le_bytes())
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        let mut bytes = [0; 8];
+        reader.read_exact(&mut bytes)?;
+        Ok(Self::from_le_bytes(bytes))
+    }
+}
+
+impl Encode for f32 {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        self.to_bits().encode(writer)
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        Ok(Self::from_bits(u32::decode(reader)?))
+    }
+}
+
+impl Encode for f64 {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        self.to_bits().encode(writer)
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        Ok(Self::from_bits(u64::decode(reader)?))
+    }
+}
+
+impl Encode for bool {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        (*self as u32).encode(writer)
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        Ok(u32::decode(reader)? != 0)
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        (self.len() as u32).encode(writer)?;
+        for item in self {
+            item.encode(writer)?;
+        }
+        Ok(())
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        let len = u32::decode(reader)?;
+        let mut items = Vec::new();
+        for _ in 0..len {
+            items.push(T::decode(reader)?);
+        }
+        Ok(items)
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        match self {
+            Some(value) => {
+                true.encode(writer)?;
+                value.encode(writer)
+            }
+            None => false.encode(writer),
+        }
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        if bool::decode(reader)? {
+            Ok(Some(T::decode(reader)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl Encode for Color {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        self.red.encode(writer)?;
+        self.green.encode(writer)?;
+        self.blue.encode(writer)
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        Ok(Self::new(
+            Float::decode(reader)?,
+            Float::decode(reader)?,
+            Float::decode(reader)?,
+        ))
+    }
+}
+
+impl Encode for Point3 {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        self.x.encode(writer)?;
+        self.y.encode(writer)?;
+        self.z.encode(writer)
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        Ok(Self::new(
+            Float::decode(reader)?,
+            Float::decode(reader)?,
+            Float::decode(reader)?,
+        ))
+    }
+}
+
+impl Encode for Vector3 {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        self.x.encode(writer)?;
+        self.y.encode(writer)?;
+        self.z.encode(writer)
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        Ok(Self::new(
+            Float::decode(reader)?,
+            Float::decode(reader)?,
+            Float::decode(reader)?,
+        ))
+    }
+}
+
+impl Encode for Matrix {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        // rows as length prefixed lists, the same as a `Vec<Vec<Float>>`
+        (self.n_rows as u32).encode(writer)?;
+        for row in &self.cells[..self.n_rows] {
+            (self.n_cols as u32).encode(writer)?;
+            for cell in &row[..self.n_cols] {
+                cell.encode(writer)?;
+            }
+        }
+        Ok(())
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        Ok(Self::try_new(Vec::<Vec<Float>>::decode(reader)?)?)
+    }
+}
+
+impl Encode for Pattern {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        let kind = self
+            .kind()
+            .ok_or_else(|| invalid_data("custom pattern functions can't be encoded"))?;
+        (kind as u32).encode(writer)?;
+        self.colors().to_vec().encode(writer)?;
+        self.transform.encode(writer)
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        let kind = u32::decode(reader)?;
+        let colors = Vec::<Color>::decode(reader)?;
+        if colors.is_empty() {
+            return Err(invalid_data("pattern without colors"));
+        }
+        let mut pattern = match kind {
+            k if k == PatternKind::Stripe as u32 => Self::stripe(colors),
+            k if k == PatternKind::Gradient as u32 && colors.len() == 2 => Self::gradient(colors),
+            k if k == PatternKind::Ring as u32 => Self::ring(colors),
+            k if k == PatternKind::Checkers as u32 => Self::checkers(colors),
+            _ => return Err(invalid_data("unknown pattern")),
+        };
+        pattern.transform = Matrix::decode(reader)?;
+        Ok(pattern)
+    }
+}
+
+impl Encode for Material {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        self.color.encode(writer)?;
+        self.pattern.encode(writer)?;
+        self.ambient.encode(writer)?;
+        self.diffuse.encode(writer)?;
+        self.specular.encode(writer)?;
+        self.shininess.encode(writer)?;
+        self.reflective.encode(writer)?;
+        self.reflection_roughness.encode(writer)?;
+        self.transparency.encode(writer)?;
+        self.refractive_index.encode(writer)?;
+        self.medium.encode(writer)?;
+        self.subsurface.encode(writer)?;
+        self.emissive.encode(writer)?;
+        self.brdf.encode(writer)
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        Ok(Self {
+            color: Color::decode(reader)?,
+            pattern: Option::<Pattern>::decode(reader)?,
+            ambient: Float::decode(reader)?,
+            diffuse: Float::decode(reader)?,
+            specular: Float::decode(reader)?,
+            shininess: Float::decode(reader)?,
+            reflective: Float::decode(reader)?,
+            reflection_roughness: Float::decode(reader)?,
+            transparency: Float::decode(reader)?,
+            refractive_index: Float::decode(reader)?,
+            medium: Option::<Medium>::decode(reader)?,
+            subsurface: Option::<Subsurface>::decode(reader)?,
+            emissive: Color::decode(reader)?,
+            brdf: Brdf::decode(reader)?,
+        })
+    }
+}
+
+impl Encode for Light {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        self.position.encode(writer)?;
+        self.intensity.encode(writer)
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        let position = Point3::decode(reader)?;
+        Ok(Self::new(position, Color::decode(reader)?))
+    }
+}
+
+impl Encode for ObjectFlags {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        self.casts_shadow.encode(writer)?;
+        self.receives_shadow.encode(writer)?;
+        self.visible_to_camera.encode(writer)?;
+        self.visible_in_reflections.encode(writer)
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        Ok(Self {
+            casts_shadow: bool::decode(reader)?,
+            receives_shadow: bool::decode(reader)?,
+            visible_to_camera: bool::decode(reader)?,
+            visible_in_reflections: bool::decode(reader)?,
+        })
+    }
+}
+
+impl Encode for Motion {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        (self.keyframes().len() as u32).encode(writer)?;
+        for (time, transform) in self.keyframes() {
+            time.encode(writer)?;
+            transform.encode(writer)?;
+        }
+        Ok(())
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        let n_keyframes = u32::decode(reader)?;
+        let mut keyframes = Vec::new();
+        for _ in 0..n_keyframes {
+            keyframes.push((Float::decode(reader)?, Matrix::decode(reader)?));
+        }
+        let increasing = keyframes.windows(2).all(|pair| pair[0].0 < pair[1].0);
+        if keyframes.is_empty() || !increasing {
+            return Err(invalid_data("bad motion keyframes"));
+        }
+        Ok(Self::new(keyframes))
+    }
+}
+
+impl Encode for Sphere {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        self.transform().encode(writer)?;
+        self.material.encode(writer)?;
+        self.flags.encode(writer)?;
+        self.motion.encode(writer)
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        let mut sphere = Self::new();
+        sphere.try_set_transform(Matrix::decode(reader)?)?;
+        sphere.material = Material::decode(reader)?;
+        sphere.flags = ObjectFlags::decode(reader)?;
+        sphere.motion = Option::<Motion>::decode(reader)?;
+        Ok(sphere)
+    }
+}
+
+impl Encode for Plane {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        self.transform().encode(writer)?;
+        self.material.encode(writer)?;
+        self.flags.encode(writer)?;
+        self.motion.encode(writer)
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        let mut plane = Self::new();
+        plane.try_set_transform(Matrix::decode(reader)?)?;
+        plane.material = Material::decode(reader)?;
+        plane.flags = ObjectFlags::decode(reader)?;
+        plane.motion = Option::<Motion>::decode(reader)?;
+        Ok(plane)
+    }
+}
+
+impl Encode for Cube {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        self.transform().encode(writer)?;
+        self.material.encode(writer)?;
+        self.flags.encode(writer)?;
+        self.motion.encode(writer)
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        let mut cube = Self::new();
+        cube.try_set_transform(Matrix::decode(reader)?)?;
+        cube.material = Material::decode(reader)?;
+        cube.flags = ObjectFlags::decode(reader)?;
+        cube.motion = Option::<Motion>::decode(reader)?;
+        Ok(cube)
+    }
+}
+
+impl Encode for World {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        self.lights.encode(writer)?;
+        self.light_samples.encode(writer)?;
+        self.reflection_samples.encode(writer)?;
+        self.background.encode(writer)?;
+        self.ambient_occlusion.encode(writer)?;
+        self.fog.encode(writer)?;
+        (self.objects.len() as u32).encode(writer)?;
+        // sorted by id so equal worlds encode to equal bytes
+        let mut ids: Vec<&usize> = self.objects.keys().collect();
+        ids.sort();
+        for id in ids {
+            let mut bytes = Vec::new();
+            self.objects[id].encode_tagged(&mut bytes)?;
+            writer.write_all(&bytes)?;
+        }
+        Ok(())
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        let mut world = Self::new();
+        world.lights = Vec::<Light>::decode(reader)?;
+        world.light_samples = u32::decode(reader)?;
+        world.reflection_samples = u32::decode(reader)?;
+        world.background = Background::decode(reader)?;
+        world.ambient_occlusion = Option::<AmbientOcclusion>::decode(reader)?;
+        world.fog = Option::<Fog>::decode(reader)?;
+        let n_objects = u32::decode(reader)?;
+        for _ in 0..n_objects {
+            match u32::decode(reader)? {
+                SPHERE_TAG => {
+                    let sphere = Sphere::decode(reader)?;
+                    world.objects.insert(sphere.get_id(), Box::new(sphere));
+                }
+                PLANE_TAG => {
+                    let plane = Plane::decode(reader)?;
+                    world.objects.insert(plane.get_id(), Box::new(plane));
+                }
+                CUBE_TAG => {
+                    let cube = Cube::decode(reader)?;
+                    world.objects.insert(cube.get_id(), Box::new(cube));
+                }
+                _ => return Err(invalid_data("unknown object type")),
+            }
+        }
+        Ok(world)
+    }
+}
+
+impl Encode for Projection {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        match *self {
+            Self::Perspective { field_of_view } => {
+                0u32.encode(writer)?;
+                field_of_view.encode(writer)
+            }
+            Self::Orthographic { view_size } => {
+                1u32.encode(writer)?;
+                view_size.encode(writer)
+            }
+            Self::Fisheye { field_of_view } => {
+                2u32.encode(writer)?;
+                field_of_view.encode(writer)
+            }
+            Self::Equirectangular => 3u32.encode(writer),
+        }
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        match u32::decode(reader)? {
+            0 => Ok(Self::Perspective {
+                field_of_view: Float::decode(reader)?,
+            }),
+            1 => Ok(Self::Orthographic {
+                view_size: Float::decode(reader)?,
+            }),
+            2 => Ok(Self::Fisheye {
+                field_of_view: Float::decode(reader)?,
+            }),
+            3 => Ok(Self::Equirectangular),
+            _ => Err(invalid_data("unknown projection")),
+        }
+    }
+}
+
+impl Encode for Camera {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        self.hsize.encode(writer)?;
+        self.vsize.encode(writer)?;
+        self.transform.encode(writer)?;
+        self.pixel_size.encode(writer)?;
+        self.half_width.encode(writer)?;
+        self.half_height.encode(writer)?;
+        self.projection.encode(writer)?;
+        self.integrator.encode(writer)?;
+        self.shutter_open.encode(writer)?;
+        self.shutter_close.encode(writer)
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        let mut camera = Self::new(1, 1, 1.0);
+        camera.hsize = u32::decode(reader)?;
+        camera.vsize = u32::decode(reader)?;
+        camera.transform = Matrix::decode(reader)?;
+        camera.pixel_size = Float::decode(reader)?;
+        camera.half_width = Float::decode(reader)?;
+        camera.half_height = Float::decode(reader)?;
+        camera.projection = Projection::decode(reader)?;
+        camera.integrator = Integrator::decode(reader)?;
+        camera.shutter_open = Float::decode(reader)?;
+        camera.shutter_close = Float::decode(reader)?;
+        Ok(camera)
+    }
+}
+
+impl Encode for Brdf {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        match *self {
+            Self::Phong => 0u32.encode(writer),
+            Self::Microfacet {
+                metallic,
+                roughness,
+            } => {
+                1u32.encode(writer)?;
+                metallic.encode(writer)?;
+                roughness.encode(writer)
+            }
+        }
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        match u32::decode(reader)? {
+            0 => Ok(Self::Phong),
+            1 => Ok(Self::Microfacet {
+                metallic: Float::decode(reader)?,
+                roughness: Float::decode(reader)?,
+            }),
+            _ => Err(invalid_data("unknown brdf")),
+        }
+    }
+}
+
+impl Encode for Canvas {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        self.width.encode(writer)?;
+        self.height.encode(writer)?;
+        self.grid.encode(writer)
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        let width = u32::decode(reader)?;
+        let height = u32::decode(reader)?;
+        let grid = Vec::<Color>::decode(reader)?;
+        if Some(grid.len()) != (width as usize).checked_mul(height as usize) {
+            return Err(invalid_data("canvas size doesn't match its pixels"));
+        }
+        Ok(Self {
+            width,
+            height,
+            grid,
+        })
+    }
+}
+
+impl Encode for Background {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        match self {
+            Self::Solid(color) => {
+                0u32.encode(writer)?;
+                color.encode(writer)
+            }
+            Self::Gradient { bottom, top } => {
+                1u32.encode(writer)?;
+                bottom.encode(writer)?;
+                top.encode(writer)
+            }
+            Self::Environment(map) => {
+                2u32.encode(writer)?;
+                map.image().encode(writer)
+            }
+        }
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        match u32::decode(reader)? {
+            0 => Ok(Self::Solid(Color::decode(reader)?)),
+            1 => Ok(Self::Gradient {
+                bottom: Color::decode(reader)?,
+                top: Color::decode(reader)?,
+            }),
+            2 => {
+                let image = Canvas::decode(reader)?;
+                if image.width == 0 || image.height == 0 {
+                    return Err(invalid_data("environment image is empty"));
+                }
+                Ok(Self::Environment(EnvironmentMap::new(image)))
+            }
+            _ => Err(invalid_data("unknown background")),
+        }
+    }
+}
+
+impl Encode for AmbientOcclusion {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        self.samples.encode(writer)?;
+        self.max_distance.encode(writer)
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        Ok(Self {
+            samples: u32::decode(reader)?,
+            max_distance: Float::decode(reader)?,
+        })
+    }
+}
+
+impl Encode for Fog {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        self.color.encode(writer)?;
+        self.density.encode(writer)
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        Ok(Self {
+            color: Color::decode(reader)?,
+            density: Float::decode(reader)?,
+        })
+    }
+}
+
+impl Encode for Medium {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        self.color.encode(writer)?;
+        self.absorption.encode(writer)?;
+        self.scattering.encode(writer)?;
+        self.steps.encode(writer)
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        Ok(Self {
+            color: Color::decode(reader)?,
+            absorption: Float::decode(reader)?,
+            scattering: Float::decode(reader)?,
+            steps: u32::decode(reader)?,
+        })
+    }
+}
+
+impl Encode for Subsurface {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        self.albedo.encode(writer)?;
+        self.mean_free_path.encode(writer)?;
+        self.samples.encode(writer)
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        Ok(Self {
+            albedo: Color::decode(reader)?,
+            mean_free_path: Float::decode(reader)?,
+            samples: u32::decode(reader)?,
+        })
+    }
+}
+
+impl Encode for Integrator {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        match *self {
+            Self::Whitted => 0u32.encode(writer),
+            Self::AmbientOcclusion => 2u32.encode(writer),
+            Self::PathTracer { samples, max_depth } => {
+                1u32.encode(writer)?;
+                samples.encode(writer)?;
+                max_depth.encode(writer)
+            }
+        }
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        match u32::decode(reader)? {
+            0 => Ok(Self::Whitted),
+            2 => Ok(Self::AmbientOcclusion),
+            1 => Ok(Self::PathTracer {
+                samples: u32::decode(reader)?,
+                max_depth: u32::decode(reader)?,
+            }),
+            _ => Err(invalid_data("unknown integrator")),
+        }
+    }
+}
+
+impl Encode for Tile {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        self.col.encode(writer)?;
+        self.row.encode(writer)?;
+        self.width.encode(writer)?;
+        self.height.encode(writer)
+    }
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        Ok(Self::new(
+            u32::decode(reader)?,
+            u32::decode(reader)?,
+            u32::decode(reader)?,
+            u32::decode(reader)?,
+        ))
+    }
+}
+
+impl Encode for RenderedTile {
+    fn encode<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        self.tile.encode(writer)?;
+        self.samples.encode(writer)?;
+        for color in &self.pixels {
+            color.encode(writer)?;
+        }
+        Ok(())
+    }
+    // the size isn't known to be sane yet, so pixels are only stored as they
+    // are read, use `decode_within` to check it up front
+    fn decode<R: Read + ?Sized>(reader: &mut R) -> io::Result<Self> {
+        let tile = Tile::decode(reader)?;
+        Self::decode_pixels(reader, tile, Vec::new())
+    }
+}
+
+impl RenderedTile {
+    // Decodes a tile that has to lie inside `bounds`, the image or the tile
+    // that was asked for, rejecting anything else before allocating its pixels.
+    pub fn decode_within<R: Read + ?Sized>(reader: &mut R, bounds: &Tile) -> io::Result<Self> {
+        let tile = Tile::decode(reader)?;
+        if tile.intersect(bounds) != Some(tile) {
+            return Err(invalid_data("tile lies outside of its bounds"));
+        }
+        Self::decode_pixels(reader, tile, Vec::with_capacity(tile.n_pixels()))
+    }
+    fn decode_pixels<R: Read + ?Sized>(
+        reader: &mut R,
+        tile: Tile,
+        mut pixels: Vec<Color>,
+    ) -> io::Result<Self> {
+        let samples = u32::decode(reader)?;
+        let n_pixels = (tile.width as usize)
+            .checked_mul(tile.height as usize)
+            .ok_or_else(|| invalid_data("tile has too many pixels"))?;
+        for _ in 0..n_pixels {
+            pixels.push(Color::decode(reader)?);
+        }
+        Ok(Self {
+            tile,
+            pixels,
+            samples,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::color::*;
+    use super::*;
+
+    fn roundtrip<T: Encode>(value: &T) -> T {
+        let mut bytes = Vec::new();
+        value.encode(&mut bytes).unwrap();
+        let mut reader = bytes.as_slice();
+        let decoded = T::decode(&mut reader).unwrap();
+        assert!(reader.is_empty());
+        decoded
+    }
+    #[test]
+    fn encode_camera() {
+        let mut camera = Camera::fisheye(64, 48, 3.0);
+        camera.transform = Matrix::view_transformation(
+            Point3::new(1, 2, 3),
+            Point3::new(0, 0, 0),
+            Vector3::new(0, 1, 0),
+        );
+        camera.shutter_open = 0.25;
+        camera.shutter_close = 0.75;
+        let decoded = roundtrip(&camera);
+        assert_eq!((decoded.shutter_open, decoded.shutter_close), (0.25, 0.75));
+        assert_eq!(
+            decoded.projection,
+            Projection::Fisheye { field_of_view: 3.0 }
+        );
+        assert_eq!(decoded.transform, camera.transform);
+        assert_eq!(decoded.ray_for_pixel(3, 7), camera.ray_for_pixel(3, 7));
+    }
+    #[test]
+    fn encode_world() {
+        let mut world = World::default();
+        let mut plane = Plane::new();
+        plane.material.pattern = Some(Pattern::checkers(vec![WHITE, BLACK]));
+        plane.material.reflective = 0.5;
+        world.objects.insert(plane.get_id(), Box::new(plane));
+        world.light_samples = 7;
+        world.reflection_samples = 3;
+        world.ambient_occlusion = Some(AmbientOcclusion::new(5, 2.5));
+        world.fog = Some(Fog::new(Color::new(0.5, 0.6, 0.7), 0.02));
+        let mut smoke = Cube::new();
+        smoke.material.medium = Some(Medium::new(WHITE, 0.1, 0.3));
+        world.objects.insert(smoke.get_id(), Box::new(smoke));
+        let mut sky = Canvas::new(2, 1);
+        sky.set_pixel(0, 1, Color::new(4.0, 5.0, 6.0));
+        world.background = Background::Environment(EnvironmentMap::new(sky));
+        let decoded = roundtrip(&world);
+        match &decoded.background {
+            Background::Environment(map) => {
+                assert_eq!(map.image().pixel_at(0, 1), Color::new(4.0, 5.0, 6.0))
+            }
+            _ => panic!("background changed"),
+        }
+        assert_eq!(decoded.objects.len(), 4);
+        assert_eq!(decoded.lights, world.lights);
+        assert_eq!(decoded.light_samples, 7);
+        assert_eq!(decoded.reflection_samples, 3);
+        assert_eq!(decoded.ambient_occlusion, world.ambient_occlusion);
+        assert_eq!(decoded.fog, world.fog);
+        let media: Vec<_> = decoded
+            .objects
+            .values()
+            .filter_map(|object| object.material().medium)
+            .collect();
+        assert_eq!(media, vec![Medium::new(WHITE, 0.1, 0.3)]);
+
+        let mut bytes = Vec::new();
+        world.encode(&mut bytes).unwrap();
+        let mut decoded_bytes = Vec::new();
+        decoded.encode(&mut decoded_bytes).unwrap();
+        assert_eq!(bytes, decoded_bytes);
+    }
+    #[test]
+    fn encode_microfacet_material() {
+        let mut material = Material::metallic_roughness(RED, 0.25, 0.75);
+        material.emissive = Color::new(0.5, 0.0, 2.0);
+        material.reflection_roughness = 0.3;
+        material.transparency = 0.6;
+        material.refractive_index = 1.33;
+        material.subsurface = Some(Subsurface::new(Color::new(0.9, 0.6, 0.5), 0.25));
+        let decoded = roundtrip(&material);
+        assert_eq!(decoded.reflection_roughness, 0.3);
+        assert_eq!(decoded.transparency, 0.6);
+        assert_eq!(decoded.refractive_index, 1.33);
+        assert_eq!(decoded.subsurface, material.subsurface);
+        assert_eq!(decoded.brdf, Brdf::microfacet(0.25, 0.75));
+        assert_eq!(decoded.color, RED);
+        assert_eq!(decoded.emissive, material.emissive);
+        assert_eq!(roundtrip(&Material::default()).brdf, Brdf::Phong);
+    }
+    #[test]
+    fn encode_integrators() {
+        for integrator in &[
+            Integrator::Whitted,
+            Integrator::AmbientOcclusion,
+            Integrator::PathTracer {
+                samples: 8,
+                max_depth: 3,
+            },
+        ] {
+            assert_eq!(roundtrip(integrator), *integrator);
+        }
+    }
+    #[test]
+    fn encode_object_flags() {
+        let mut sphere = Sphere::new();
+        sphere.flags.casts_shadow = false;
+        sphere.flags.visible_in_reflections = false;
+        assert_eq!(roundtrip(&sphere).flags, sphere.flags);
+        let mut plane = Plane::new();
+        plane.flags.visible_to_camera = false;
+        assert_eq!(roundtrip(&plane).flags, plane.flags);
+    }
+    #[test]
+    fn encode_motion() {
+        let mut cube = Cube::new();
+        cube.motion = Some(Motion::new(vec![
+            (0.0, Matrix::identity_matrix(4)),
+            (0.5, Matrix::translation(1.0, 2.0, 3.0)),
+            (1.0, Matrix::rotation_y(0.5)),
+        ]));
+        assert_eq!(roundtrip(&cube).motion, cube.motion);
+        assert_eq!(roundtrip(&Sphere::new()).motion, None);
+    }
+    #[test]
+    fn reject_oversized_tiles_and_canvases() {
+        let mut bytes = Vec::new();
+        Tile::new(0, 0, u32::MAX, u32::MAX)
+            .encode(&mut bytes)
+            .unwrap();
+        1u32.encode(&mut bytes).unwrap();
+        let bounds = Tile::new(0, 0, 64, 64);
+        let error = RenderedTile::decode_within(&mut bytes.as_slice(), &bounds).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        // without bounds decoding stops when the pixels run out
+        assert!(RenderedTile::decode(&mut bytes.as_slice()).is_err());
+
+        let mut bytes = Vec::new();
+        0x1_0000u32.encode(&mut bytes).unwrap();
+        0x1_0000u32.encode(&mut bytes).unwrap();
+        0u32.encode(&mut bytes).unwrap();
+        assert!(Canvas::decode(&mut bytes.as_slice()).is_err());
+    }
+    #[test]
+    fn custom_pattern_is_not_encodable() {
+        fn solid(_: Point3, colors: &[Color]) -> Color {
+            colors[0]
+        }
+        let pattern = Pattern::new(vec![RED], solid);
+        assert!(pattern.encode(&mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,122 @@
+use super::float_cmp::Float;
+use std::cmp::Ordering;
+use std::fmt;
+use std::io;
+
+// Invalid input to the renderer, returned by the `try_` variants of functions
+// that panic on bad input. Lets a service reject a bad scene instead of crashing.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    ValueOutOfRange { name: &'static str, value: Float },
+    InvalidDimensions(&'static str),
+    NotInvertible,
+    PixelOutOfBounds { row: u32, col: u32 },
+    // no object with the id in the world
+    UnknownObject(usize),
+    // no light at the index of `World::lights`
+    UnknownLight(usize),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ValueOutOfRange { name, value } => {
+                write!(f, "{} value {} is out of bounds", name, value)
+            }
+            Self::InvalidDimensions(what) => write!(f, "invalid dimensions, {}", what),
+            Self::NotInvertible => write!(f, "matrix is not invertible"),
+            Self::PixelOutOfBounds { row, col } => {
+                write!(f, "pixel ({}, {}) lies outside of the canvas", row, col)
+            }
+            Self::UnknownObject(obj_id) => write!(f, "no object with id {}", obj_id),
+            Self::UnknownLight(index) => write!(f, "no light with index {}", index),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+// lets decoders reject scenes the constructors reject
+impl From<Error> for io::Error {
+    fn from(error: Error) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, error.to_string())
+    }
+}
+
+// `value` if it lies within `range`
+pub fn check_range(
+    name: &'static str,
+    value: Float,
+    range: std::ops::RangeInclusive<Float>,
+) -> Result<Float> {
+    if range.contains(&value) {
+        Ok(value)
+    } else {
+        Err(Error::ValueOutOfRange { name, value })
+    }
+}
+
+// `value` if it is greater than zero
+pub fn check_positive(name: &'static str, value: Float) -> Result<Float> {
+    if value > 0.0 {
+        Ok(value)
+    } else {
+        Err(Error::ValueOutOfRange { name, value })
+    }
+}
+
+// Checks that there is at least one keyframe and that their times increase,
+// `missing` is the error message without keyframes.
+pub fn check_keyframes<T>(missing: &'static str, keys: &[(Float, T)]) -> Result<()> {
+    if keys.is_empty() {
+        return Err(Error::InvalidDimensions(missing));
+    }
+    match keys
+        .windows(2)
+        .find(|pair| pair[0].0.partial_cmp(&pair[1].0) != Some(Ordering::Less))
+    {
+        Some(pair) => Err(Error::ValueOutOfRange {
+            name: "keyframe time",
+            value: pair[1].0,
+        }),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn error_messages() {
+        let error = Error::ValueOutOfRange {
+            name: "ambient",
+            value: 1.5,
+        };
+        assert_eq!(error.to_string(), "ambient value 1.5 is out of bounds");
+        let io_error: io::Error = Error::UnknownObject(7).into();
+        assert_eq!(io_error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(io_error.to_string(), "no object with id 7");
+    }
+    #[test]
+    fn keyframes_in_order() {
+        assert_eq!(check_keyframes("no keys", &[(0.0, ()), (1.0, ())]), Ok(()));
+        assert_eq!(
+            check_keyframes::<()>("no keys", &[]),
+            Err(Error::InvalidDimensions("no keys"))
+        );
+        assert!(check_keyframes("no keys", &[(1.0, ()), (Float::NAN, ())]).is_err());
+    }
+    #[test]
+    fn values_in_range() {
+        assert_eq!(check_range("ambient", 0.5, 0.0..=1.0), Ok(0.5));
+        assert_eq!(
+            check_range("ambient", 1.5, 0.0..=1.0),
+            Err(Error::ValueOutOfRange {
+                name: "ambient",
+                value: 1.5
+            })
+        );
+    }
+}
@@ -1,13 +1,51 @@
-pub const EPSILON: f32 = 0.0001;
-pub const SQRT2: f32 = std::f32::consts::SQRT_2;
-pub const SQRT3: f32 = 1.732_050_8;
-pub const INVSQRT2: f32 = std::f32::consts::FRAC_1_SQRT_2;
+// Floating point type of all the math, f32 unless the `f64` feature is
+// enabled for large scenes that show precision artifacts. Encoded scenes and
+// checkpoints store `Float`, they only load with the precision they were
+// written with.
+#[cfg(not(feature = "f64"))]
+pub type Float = f32;
+#[cfg(feature = "f64")]
+pub type Float = f64;
+#[cfg(not(feature = "f64"))]
+pub use std::f32::consts;
+#[cfg(feature = "f64")]
+pub use std::f64::consts;
 
-pub fn equal(a: f32, b: f32) -> bool {
+// tolerance of the comparisons below
+pub const EPSILON: Float = 0.0001;
+// Distance secondary rays start off a surface so they don't hit it again
+// through rounding, see `State::over_point`. It shrinks with the precision.
+#[cfg(not(feature = "f64"))]
+pub const SURFACE_OFFSET: Float = 15.0 * EPSILON;
+#[cfg(feature = "f64")]
+pub const SURFACE_OFFSET: Float = 1.0e-6;
+// hits this close behind the origin of a ray still count, see `Intersections::hit`
+#[cfg(not(feature = "f64"))]
+pub const HIT_TOLERANCE: Float = EPSILON;
+#[cfg(feature = "f64")]
+pub const HIT_TOLERANCE: Float = 1.0e-9;
+pub const SQRT2: Float = consts::SQRT_2;
+#[cfg(not(feature = "f64"))]
+pub const SQRT3: Float = 1.732_050_8;
+#[cfg(feature = "f64")]
+pub const SQRT3: Float = 1.732_050_807_568_877_2;
+pub const INVSQRT2: Float = consts::FRAC_1_SQRT_2;
+
+// bits of `x` rounded to f32, the same in both precisions
+#[cfg(not(feature = "f64"))]
+pub fn f32_bits(x: Float) -> u32 {
+    x.to_bits()
+}
+#[cfg(feature = "f64")]
+pub fn f32_bits(x: Float) -> u32 {
+    (x as f32).to_bits()
+}
+
+pub fn equal(a: Float, b: Float) -> bool {
     (a - b).abs() < EPSILON
 }
 
-pub fn equal_debug(a: f32, b: f32) -> bool {
+pub fn equal_debug(a: Float, b: Float) -> bool {
     if (a - b).abs() < EPSILON {
         true
     } else {
@@ -16,6 +54,6 @@ pub fn equal_debug(a: f32, b: f32) -> bool {
     }
 }
 
-pub fn greater(a: f32, b: f32) -> bool {
+pub fn greater(a: Float, b: Float) -> bool {
     (a - b) > EPSILON
 }
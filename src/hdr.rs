@@ -0,0 +1,233 @@
+use super::canvas::Canvas;
+use super::color::Color;
+use super::encode::invalid_data;
+use super::float_cmp::Float;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+// largest image accepted by `read_hdr`, an 8K by 2K environment map. The
+// header is checked against it before anything is allocated
+pub const MAX_HDR_PIXELS: usize = 1 << 24;
+
+// Radiance RGBE images, the usual format for high dynamic range environment maps.
+//
+// Each pixel is stored as three 8 bit mantissas sharing one exponent byte.
+// Scanlines may be run length encoded per channel, which is how most tools
+// write them, this module writes them flat which every reader accepts.
+impl Canvas {
+    pub fn read_hdr(reader: &mut impl BufRead) -> io::Result<Self> {
+        Self::read_hdr_limited(reader, MAX_HDR_PIXELS)
+    }
+    // like `read_hdr` but rejects images with more than `max_pixels` pixels
+    pub fn read_hdr_limited(reader: &mut impl BufRead, max_pixels: usize) -> io::Result<Self> {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        if !line.starts_with("#?") {
+            return Err(invalid_data("not a radiance hdr file"));
+        }
+        // header lines end with an empty line
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(invalid_data("hdr header is not terminated"));
+            }
+            let line = line.trim();
+            if line.is_empty() {
+                break;
+            }
+            if line.starts_with("FORMAT=") && line != "FORMAT=32-bit_rle_rgbe" {
+                return Err(invalid_data("unsupported hdr pixel format"));
+            }
+        }
+        line.clear();
+        reader.read_line(&mut line)?;
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (height, width) = match fields.as_slice() {
+            ["-Y", height, "+X", width] => (
+                height
+                    .parse::<u32>()
+                    .map_err(|_| invalid_data("bad hdr height"))?,
+                width
+                    .parse::<u32>()
+                    .map_err(|_| invalid_data("bad hdr width"))?,
+            ),
+            _ => return Err(invalid_data("unsupported hdr orientation")),
+        };
+        match (width as usize).checked_mul(height as usize) {
+            Some(n_pixels) if n_pixels > 0 && n_pixels <= max_pixels => {}
+            _ => return Err(invalid_data("unsupported hdr image size")),
+        }
+
+        let mut canvas = Self::new(width, height);
+        let mut scanline = vec![[0u8; 4]; width as usize];
+        for row in 0..height {
+            read_scanline(reader, &mut scanline)?;
+            for (col, rgbe) in scanline.iter().enumerate() {
+                canvas.set_pixel(row, col as u32, from_rgbe(*rgbe));
+            }
+        }
+        Ok(canvas)
+    }
+    pub fn write_hdr(&self, writer: &mut impl Write) -> io::Result<()> {
+        write!(
+            writer,
+            "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+            self.height, self.width
+        )?;
+        for color in &self.grid {
+            writer.write_all(&to_rgbe(*color))?;
+        }
+        Ok(())
+    }
+    pub fn load_hdr(path: &Path) -> io::Result<Self> {
+        Self::read_hdr(&mut BufReader::new(File::open(path)?))
+    }
+    pub fn save_as_hdr(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_hdr(&mut writer)?;
+        writer.flush()
+    }
+}
+
+fn read_scanline(reader: &mut impl Read, scanline: &mut [[u8; 4]]) -> io::Result<()> {
+    let width = scanline.len();
+    let mut start = [0u8; 4];
+    reader.read_exact(&mut start)?;
+    let is_rle = (8..0x8000).contains(&width) && start[0] == 2 && start[1] == 2 && start[2] < 128;
+    if !is_rle {
+        scanline[0] = start;
+        for pixel in &mut scanline[1..] {
+            reader.read_exact(pixel)?;
+        }
+        return Ok(());
+    }
+    if (usize::from(start[2]) << 8 | usize::from(start[3])) != width {
+        return Err(invalid_data("hdr scanline width mismatch"));
+    }
+    // every channel is encoded separately as runs and literal spans
+    for channel in 0..4 {
+        let mut col = 0;
+        while col < width {
+            let mut count = [0u8; 1];
+            reader.read_exact(&mut count)?;
+            let (count, is_run) = if count[0] > 128 {
+                (usize::from(count[0] - 128), true)
+            } else {
+                (usize::from(count[0]), false)
+            };
+            if count == 0 || col + count > width {
+                return Err(invalid_data("bad hdr run length"));
+            }
+            let mut value = [0u8; 1];
+            if is_run {
+                reader.read_exact(&mut value)?;
+            }
+            for pixel in &mut scanline[col..col + count] {
+                if !is_run {
+                    reader.read_exact(&mut value)?;
+                }
+                pixel[channel] = value[0];
+            }
+            col += count;
+        }
+    }
+    Ok(())
+}
+
+fn from_rgbe(rgbe: [u8; 4]) -> Color {
+    if rgbe[3] == 0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+    let scale = (2.0 as Float).powi(i32::from(rgbe[3]) - (128 + 8));
+    Color::new(
+        Float::from(rgbe[0]) * scale,
+        Float::from(rgbe[1]) * scale,
+        Float::from(rgbe[2]) * scale,
+    )
+}
+
+fn to_rgbe(color: Color) -> [u8; 4] {
+    let max = color.red.max(color.green).max(color.blue);
+    if max < 1.0e-32 {
+        return [0; 4];
+    }
+    // max = mantissa * 2^exponent with the mantissa in [0.5, 1)
+    let mut exponent = max.log2().floor() as i32 + 1;
+    if max / (2.0 as Float).powi(exponent) >= 1.0 {
+        exponent += 1;
+    }
+    let scale = 256.0 / (2.0 as Float).powi(exponent);
+    let byte = |c: Float| (c.max(0.0) * scale).min(255.0) as u8;
+    [
+        byte(color.red),
+        byte(color.green),
+        byte(color.blue),
+        (exponent + 128).clamp(0, 255) as u8,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::color::*;
+    use super::*;
+    #[test]
+    fn hdr_roundtrip() {
+        let mut canvas = Canvas::new(3, 2);
+        canvas.set_pixel(0, 0, Color::new(1.0, 0.5, 0.25));
+        canvas.set_pixel(0, 2, Color::new(100.0, 20.0, 0.0));
+        canvas.set_pixel(1, 1, Color::new(0.001, 0.002, 0.003));
+        let mut bytes = Vec::new();
+        canvas.write_hdr(&mut bytes).unwrap();
+        let loaded = Canvas::read_hdr(&mut bytes.as_slice()).unwrap();
+        assert_eq!(loaded.width, 3);
+        assert_eq!(loaded.height, 2);
+        for (a, b) in canvas.grid.iter().zip(&loaded.grid) {
+            // 8 bit mantissas keep about two significant digits
+            let max = a.red.max(a.green).max(a.blue);
+            assert!((a.red - b.red).abs() <= max / 100.0);
+            assert!((a.green - b.green).abs() <= max / 100.0);
+            assert!((a.blue - b.blue).abs() <= max / 100.0);
+        }
+        assert_eq!(loaded.pixel_at(1, 0), BLACK);
+    }
+    #[test]
+    fn read_run_length_encoded_scanline() {
+        let mut bytes = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 8\n".to_vec();
+        bytes.extend_from_slice(&[2, 2, 0, 8]);
+        // red: a run of 8, green: 8 literals, blue: two runs of 4, exponent: a run of 8
+        bytes.extend_from_slice(&[128 + 8, 128]);
+        bytes.extend_from_slice(&[8, 0, 16, 32, 48, 64, 80, 96, 112]);
+        bytes.extend_from_slice(&[128 + 4, 0, 128 + 4, 64]);
+        bytes.extend_from_slice(&[128 + 8, 129]);
+        let canvas = Canvas::read_hdr(&mut bytes.as_slice()).unwrap();
+        // mantissa / 256 * 2^(129 - 128)
+        assert_eq!(canvas.pixel_at(0, 0), Color::new(1.0, 0.0, 0.0));
+        assert_eq!(canvas.pixel_at(0, 7), Color::new(1.0, 0.875, 0.5));
+    }
+    #[test]
+    fn reject_invalid_hdr() {
+        assert!(Canvas::read_hdr(&mut &b"P3\n2 2\n255\n"[..]).is_err());
+        assert!(
+            Canvas::read_hdr(&mut &b"#?RADIANCE\nFORMAT=32-bit_rle_xyze\n\n-Y 1 +X 1\n"[..])
+                .is_err()
+        );
+        assert!(Canvas::read_hdr(&mut &b"#?RADIANCE\n\n+Y 1 +X 1\n\0\0\0\0"[..]).is_err());
+        for size in &[
+            "-Y 0 +X 4",
+            "-Y 4 +X 0",
+            "-Y 65536 +X 65536",
+            "-Y 4097 +X 4096",
+            "-Y 4294967295 +X 2",
+        ] {
+            let header = format!("#?RADIANCE\n\n{}\n", size);
+            let error = Canvas::read_hdr(&mut header.as_bytes()).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+        let header = b"#?RADIANCE\n\n-Y 2 +X 3\n";
+        assert!(Canvas::read_hdr_limited(&mut &header[..], 5).is_err());
+        // truncated pixel data
+        assert!(Canvas::read_hdr(&mut &b"#?RADIANCE\n\n-Y 1 +X 2\n\0\0\0\0"[..]).is_err());
+    }
+}
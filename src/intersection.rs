@@ -1,36 +1,51 @@
+use super::error::*;
 use super::float_cmp;
-use super::float_cmp::EPSILON;
+use super::float_cmp::{Float, HIT_TOLERANCE, SURFACE_OFFSET};
 use super::ray::Ray;
-use super::vec3::Vec3;
+use super::vec3::{Point3, Vector3};
 use super::world::World;
 use std::cmp::Ordering;
 use std::ops::Index;
 
 #[derive(Debug)]
 pub struct State {
-    pub t: f32,
+    pub t: Float,
     pub obj_id: usize,
-    pub point: Vec3,
-    pub over_point: Vec3,
-    pub eyev: Vec3,
-    pub normalv: Vec3,
-    pub reflectv: Vec3,
+    pub point: Point3,
+    pub over_point: Point3,
+    // just below the surface, where refracted rays start
+    pub under_point: Point3,
+    pub eyev: Vector3,
+    pub normalv: Vector3,
+    pub reflectv: Vector3,
     pub inside: bool,
+    // refractive indices on the side the ray comes from and the side it enters,
+    // objects are assumed to be surrounded by air
+    pub n1: Float,
+    pub n2: Float,
+    // time of the ray, rays leaving the point are traced at the same time
+    pub time: Float,
 }
 
 #[derive(Debug, Copy, Clone)]
 pub struct Intersection {
-    pub t: f32,
+    pub t: Float,
     pub obj_id: usize,
 }
 
 impl Intersection {
-    pub fn new(t: f32, obj_id: usize) -> Self {
+    pub fn new(t: Float, obj_id: usize) -> Self {
         Self { t, obj_id }
     }
     pub fn compute_state(&self, ray: &Ray, world: &World) -> State {
+        self.try_compute_state(ray, world)
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+    // fails when the intersected object isn't part of `world`
+    pub fn try_compute_state(&self, ray: &Ray, world: &World) -> Result<State> {
         let point = ray.position(self.t);
-        let mut normalv = world.objects.get(&self.obj_id).unwrap().normal_at(point);
+        let object = world.object(self.obj_id)?;
+        let mut normalv = object.normal_at_time(point, ray.time);
         let eyev = -ray.direction;
         let reflectv = ray.direction.reflect(normalv);
         // checking for ray originating from inside the object
@@ -44,18 +59,43 @@ impl Intersection {
 
         let point = ray.position(self.t);
         // required to prevent intersection to be treated as shadow
-        let over_point = point + normalv * 15.0 * EPSILON;
+        let over_point = point + normalv * SURFACE_OFFSET;
+        let under_point = point - normalv * SURFACE_OFFSET;
+        let index = object.material().refractive_index;
+        let (n1, n2) = if inside { (index, 1.0) } else { (1.0, index) };
 
-        State {
+        Ok(State {
             t: self.t,
             obj_id: self.obj_id,
             point,
             over_point,
+            under_point,
             eyev,
             normalv,
             reflectv,
             inside,
+            n1,
+            n2,
+            time: ray.time,
+        })
+    }
+}
+
+impl State {
+    // Schlick's approximation of the share of light reflected by a transparent
+    // surface, 1 on total internal reflection
+    pub fn schlick(&self) -> Float {
+        let mut cos = self.eyev.dot(self.normalv);
+        if self.n1 > self.n2 {
+            let eta = self.n1 / self.n2;
+            let sin2_t = eta * eta * (1.0 - cos * cos);
+            if sin2_t > 1.0 {
+                return 1.0;
+            }
+            cos = (1.0 - sin2_t).sqrt();
         }
+        let r0 = ((self.n1 - self.n2) / (self.n1 + self.n2)).powi(2);
+        r0 + (1.0 - r0) * (1.0 - cos).powi(5)
     }
 }
 
@@ -110,22 +150,11 @@ impl Intersections {
         self.crossings.len() == 0
     }
     pub fn hit(&self) -> Option<Intersection> {
-        // intersection with lowest nonnegative t value
-        if self.is_empty() {
-            return None;
-        }
-        let ray_origin = Intersection::new(0.0, self.crossings[0].obj_id);
-        let r = self.crossings.binary_search(&ray_origin);
-        match r {
-            Ok(i) => Some(self.crossings[i]),
-            Err(i) => {
-                if i < self.len() {
-                    Some(self.crossings[i])
-                } else {
-                    None
-                }
-            }
-        }
+        // intersection with lowest nonnegative t value, up to rounding
+        self.crossings
+            .iter()
+            .find(|x| x.t > -HIT_TOLERANCE)
+            .copied()
     }
 }
 
@@ -138,13 +167,25 @@ impl Index<usize> for Intersections {
 }
 
 #[cfg(test)]
-pub mod tests {
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
     use super::super::matrix::Matrix;
+    use super::super::object::Object;
     use super::super::plane::Plane;
     use super::super::sphere::Sphere;
     use super::float_cmp::*;
     use super::*;
 
+    #[test]
+    fn state_of_unknown_object() {
+        let world = World::new();
+        let ray = Ray::new(Point3::new(0, 0, -5), Vector3::new(0, 0, 1));
+        let hit = Intersection::new(4.0, usize::MAX);
+        assert_eq!(
+            hit.try_compute_state(&ray, &world).unwrap_err(),
+            Error::UnknownObject(usize::MAX)
+        );
+    }
     #[test]
     fn create_intersection_object() {
         let sphere = Sphere::new();
@@ -154,7 +195,7 @@ pub mod tests {
     }
     #[test]
     fn precompute_intersection_state() {
-        let ray = Ray::new(Vec3::point(0, 0, -5), Vec3::vector(0, 0, 1));
+        let ray = Ray::new(Point3::new(0, 0, -5), Vector3::new(0, 0, 1));
         let sphere = Sphere::new();
         let id = sphere.get_id();
         let mut world = World::new();
@@ -163,14 +204,14 @@ pub mod tests {
         let state = intersection.compute_state(&ray, &world);
         assert_eq!(state.t, intersection.t);
         assert_eq!(state.obj_id, intersection.obj_id);
-        assert_eq!(state.point, Vec3::point(0, 0, -1));
-        assert_eq!(state.eyev, Vec3::vector(0, 0, -1));
-        assert_eq!(state.normalv, Vec3::vector(0, 0, -1));
+        assert_eq!(state.point, Point3::new(0, 0, -1));
+        assert_eq!(state.eyev, Vector3::new(0, 0, -1));
+        assert_eq!(state.normalv, Vector3::new(0, 0, -1));
         assert_eq!(state.inside, false);
     }
     #[test]
     fn compute_state_of_hit_inside_object() {
-        let ray = Ray::new(Vec3::point(0, 0, 0), Vec3::vector(0, 0, 1));
+        let ray = Ray::new(Point3::new(0, 0, 0), Vector3::new(0, 0, 1));
         let sphere = Sphere::new();
         let id = sphere.get_id();
         let mut world = World::new();
@@ -179,22 +220,22 @@ pub mod tests {
         let state = intersection.compute_state(&ray, &world);
         assert_eq!(state.t, intersection.t);
         assert_eq!(state.obj_id, intersection.obj_id);
-        assert_eq!(state.point, Vec3::point(0, 0, 1));
-        assert_eq!(state.eyev, Vec3::vector(0, 0, -1));
-        assert_eq!(state.normalv, Vec3::vector(0, 0, -1));
+        assert_eq!(state.point, Point3::new(0, 0, 1));
+        assert_eq!(state.eyev, Vector3::new(0, 0, -1));
+        assert_eq!(state.normalv, Vector3::new(0, 0, -1));
         assert_eq!(state.inside, true);
     }
     #[test]
     fn hit_should_offset_point() {
         let mut world = World::new();
-        let ray = Ray::new(Vec3::point(0, 0, -5), Vec3::vector(0, 0, 1));
+        let ray = Ray::new(Point3::new(0, 0, -5), Vector3::new(0, 0, 1));
         let mut shape = Sphere::new();
-        shape.transform = Matrix::translation(0.0, 0.0, 1.0);
+        shape.set_transform(Matrix::translation(0.0, 0.0, 1.0));
         let shape_id = shape.get_id();
         world.objects.insert(shape_id, Box::new(shape));
         let xs = Intersection::new(5.0, shape_id);
         let comps = xs.compute_state(&ray, &world);
-        assert!(comps.over_point.z < -EPSILON / 2.0);
+        assert!(comps.over_point.z < -SURFACE_OFFSET / 2.0);
         assert!(comps.point.z > comps.over_point.z);
     }
     #[test]
@@ -204,11 +245,48 @@ pub mod tests {
         let shape_id = shape.get_id();
         world.objects.insert(shape_id, Box::new(shape));
         let ray = Ray::new(
-            Vec3::point(0, 1, -1),
-            Vec3::vector(0.0, -INVSQRT2, INVSQRT2),
+            Point3::new(0, 1, -1),
+            Vector3::new(0.0, -INVSQRT2, INVSQRT2),
         );
         let xs = Intersection::new(SQRT2, shape_id);
         let comps = xs.compute_state(&ray, &world);
-        assert_eq!(comps.reflectv, Vec3::vector(0.0, INVSQRT2, INVSQRT2));
+        assert_eq!(comps.reflectv, Vector3::new(0.0, INVSQRT2, INVSQRT2));
+    }
+    fn glass_sphere(world: &mut World) -> usize {
+        let mut sphere = Sphere::new();
+        sphere.material.transparency = 1.0;
+        sphere.material.refractive_index = 1.5;
+        let id = sphere.get_id();
+        world.objects.insert(id, Box::new(sphere));
+        id
+    }
+    #[test]
+    fn under_point_is_below_the_surface() {
+        let mut world = World::new();
+        let id = glass_sphere(&mut world);
+        let ray = Ray::new(Point3::new(0, 0, -5), Vector3::new(0, 0, 1));
+        let comps = Intersection::new(4.0, id).compute_state(&ray, &world);
+        assert!(comps.under_point.z > SURFACE_OFFSET / 2.0 - 1.0);
+        assert!(comps.point.z < comps.under_point.z);
+        assert_eq!((comps.n1, comps.n2), (1.0, 1.5));
+        let comps = Intersection::new(6.0, id).compute_state(&ray, &world);
+        assert_eq!((comps.n1, comps.n2), (1.5, 1.0));
+    }
+    #[test]
+    fn schlick_approximation() {
+        let mut world = World::new();
+        let id = glass_sphere(&mut world);
+        // total internal reflection
+        let ray = Ray::new(Point3::new(0.0, 0.0, INVSQRT2), Vector3::new(0, 1, 0));
+        let comps = Intersection::new(INVSQRT2, id).compute_state(&ray, &world);
+        assert_eq!(comps.schlick(), 1.0);
+        // perpendicular viewing angle
+        let ray = Ray::new(Point3::new(0, 0, 0), Vector3::new(0, 1, 0));
+        let comps = Intersection::new(1.0, id).compute_state(&ray, &world);
+        assert!(float_cmp::equal(comps.schlick(), 0.04));
+        // small angle with n2 > n1
+        let ray = Ray::new(Point3::new(0.0, 0.99, -2.0), Vector3::new(0, 0, 1));
+        let comps = Intersection::new(1.8589, id).compute_state(&ray, &world);
+        assert!((comps.schlick() - 0.48873).abs() < 1.0e-3);
     }
 }
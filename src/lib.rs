@@ -3,6 +3,8 @@
 #![allow(clippy::cast_possible_truncation)]
 #![allow(clippy::cast_sign_loss)]
 
+pub mod error;
+pub use error::*;
 pub mod vec3;
 pub use vec3::*;
 pub mod color;
@@ -11,6 +13,8 @@ pub mod canvas;
 pub use canvas::*;
 pub mod matrix;
 pub use matrix::*;
+pub mod quaternion;
+pub use quaternion::*;
 pub mod world;
 pub use world::*;
 pub mod ray;
@@ -19,6 +23,8 @@ pub mod light;
 pub use light::*;
 pub mod camera;
 pub use camera::*;
+pub mod camera_rig;
+pub use camera_rig::*;
 pub mod material;
 pub use material::*;
 pub mod object;
@@ -31,5 +37,36 @@ pub mod sphere;
 pub use sphere::*;
 pub mod plane;
 pub use plane::*;
+pub mod cube;
+pub use cube::*;
+pub mod motion;
+pub use motion::*;
+pub mod tile;
+pub use tile::*;
+pub mod checkpoint;
+pub use checkpoint::*;
+pub mod encode;
+pub use encode::*;
+pub mod distributed;
+pub use distributed::*;
+pub mod sampling;
+pub use sampling::*;
+pub mod simd;
+pub use simd::*;
+pub mod path_tracer;
+pub use path_tracer::*;
+pub mod brdf;
+pub use brdf::*;
+pub mod background;
+pub use background::*;
+pub mod ambient_occlusion;
+pub mod animation;
 pub mod float_cmp;
+pub mod hdr;
+pub mod subsurface;
+pub mod volume;
+pub use ambient_occlusion::*;
+pub use animation::*;
 pub use float_cmp::*;
+pub use subsurface::*;
+pub use volume::*;
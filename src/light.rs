@@ -1,15 +1,14 @@
 use super::color::Color;
-use super::vec3::Vec3;
+use super::vec3::Point3;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Light {
-    pub position: Vec3,
+    pub position: Point3,
     pub intensity: Color,
 }
 
 impl Light {
-    pub fn new(position: Vec3, intensity: Color) -> Self {
-        assert!(position.is_point(), "position has to be Point type");
+    pub fn new(position: Point3, intensity: Color) -> Self {
         Self {
             position,
             intensity,
@@ -18,11 +17,11 @@ impl Light {
 }
 
 #[cfg(test)]
-pub mod tests {
+mod tests {
     use super::*;
     #[test]
     fn create_light_source() {
-        let position = Vec3::point(0, 0, 0);
+        let position = Point3::new(0, 0, 0);
         let intensity = Color::new(1.0, 1.0, 1.0);
         let light = Light::new(position, intensity);
         assert_eq!(light.position, position);
@@ -1,23 +1,46 @@
+use super::brdf::Brdf;
 use super::color::*;
+use super::error::*;
+use super::float_cmp::consts::{FRAC_PI_2, PI};
+use super::float_cmp::Float;
 use super::light::Light;
 use super::matrix::Matrix;
 use super::pattern::Pattern;
-use super::vec3::Vec3;
+use super::subsurface::Subsurface;
+use super::vec3::{Point3, Vector3};
+use super::volume::Medium;
 
 #[derive(Debug, Clone)]
 pub struct Material {
     pub color: Color,
     pub pattern: Option<Pattern>,
     // Phong Reflection Model
-    pub ambient: f32,
-    pub diffuse: f32,
-    pub specular: f32,
-    pub shininess: f32,
-    pub reflective: f32,
+    pub ambient: Float,
+    pub diffuse: Float,
+    pub specular: Float,
+    pub shininess: Float,
+    pub reflective: Float,
+    // blur of reflections, 0 is a perfect mirror and 1 spreads the reflected
+    // rays over a cone of 90 degrees around the mirror direction
+    pub reflection_roughness: Float,
+    // share of the light passing through the surface, filtered by its color,
+    // and the index of refraction bending it
+    pub transparency: Float,
+    pub refractive_index: Float,
+    // makes the object the boundary of a volume filled with the medium,
+    // its surface is no longer shaded
+    pub medium: Option<Medium>,
+    // diffuse light scattered below the surface, added to `lighting`
+    pub subsurface: Option<Subsurface>,
+    // light given off by the surface itself, independent of any light source
+    pub emissive: Color,
+    // how diffuse and specular light is reflected, Phong uses `diffuse`,
+    // `specular` and `shininess` while the microfacet model has its own parameters
+    pub brdf: Brdf,
 }
 
-impl Material {
-    pub fn default() -> Self {
+impl Default for Material {
+    fn default() -> Self {
         Self {
             color: Color::new(1.0, 1.0, 1.0),
             pattern: None,
@@ -26,99 +49,188 @@ impl Material {
             specular: 0.9,
             shininess: 200.0,
             reflective: 0.0,
+            reflection_roughness: 0.0,
+            transparency: 0.0,
+            refractive_index: 1.0,
+            medium: None,
+            subsurface: None,
+            emissive: BLACK,
+            brdf: Brdf::Phong,
         }
     }
+}
+
+impl Material {
     pub fn new(
         color: Color,
-        ambient: f32,
-        diffuse: f32,
-        specular: f32,
-        shininess: f32,
-        reflective: f32,
+        ambient: Float,
+        diffuse: Float,
+        specular: Float,
+        shininess: Float,
+        reflective: Float,
         pattern: Option<Pattern>,
     ) -> Self {
-        assert!(
-            (0.0 <= ambient) && (ambient <= 1.0),
-            "ambient value is out of bounds"
-        );
-        assert!(
-            (0.0 <= diffuse) && (diffuse <= 1.0),
-            "diffuse value is out of bounds"
-        );
-        assert!(
-            (0.0 <= specular) && (specular <= 1.0),
-            "specular value is out of bounds"
-        );
-        assert!(
-            (10.0 <= shininess) && (shininess <= 200.0),
-            "shininess value is out of bounds"
-        );
-        Self {
+        Self::try_new(
+            color, ambient, diffuse, specular, shininess, reflective, pattern,
+        )
+        .unwrap_or_else(|error| panic!("{}", error))
+    }
+    pub fn try_new(
+        color: Color,
+        ambient: Float,
+        diffuse: Float,
+        specular: Float,
+        shininess: Float,
+        reflective: Float,
+        pattern: Option<Pattern>,
+    ) -> Result<Self> {
+        Ok(Self {
             color,
             pattern,
-            ambient,
-            diffuse,
-            specular,
-            shininess,
+            ambient: check_range("ambient", ambient, 0.0..=1.0)?,
+            diffuse: check_range("diffuse", diffuse, 0.0..=1.0)?,
+            specular: check_range("specular", specular, 0.0..=1.0)?,
+            shininess: check_range("shininess", shininess, 10.0..=200.0)?,
             reflective,
+            reflection_roughness: 0.0,
+            transparency: 0.0,
+            refractive_index: 1.0,
+            medium: None,
+            subsurface: None,
+            emissive: BLACK,
+            brdf: Brdf::Phong,
+        })
+    }
+    // clear glass tinted by `color`, it mostly refracts and reflects
+    pub fn glass(color: Color) -> Self {
+        Self {
+            color,
+            ambient: 0.0,
+            diffuse: 0.1,
+            reflective: 0.9,
+            transparency: 0.9,
+            refractive_index: 1.5,
+            ..Self::default()
+        }
+    }
+    // physically based material with `color` as base color
+    pub fn metallic_roughness(color: Color, metallic: Float, roughness: Float) -> Self {
+        Self {
+            color,
+            brdf: Brdf::microfacet(metallic, roughness),
+            ..Self::default()
+        }
+    }
+    // half angle of the cone reflected rays are sampled in
+    pub fn reflection_cone(&self) -> Float {
+        self.reflection_roughness.clamp(0.0, 1.0) * FRAC_PI_2
+    }
+    // surface color at `point`, taking the pattern into account
+    pub fn color_at(&self, point: Point3, obj_transform: &Matrix) -> Color {
+        if let Some(pattern) = &self.pattern {
+            pattern.pattern_at(point, obj_transform)
+        } else {
+            self.color
         }
     }
     pub fn lighting(
         &self,
         obj_transform: &Matrix,
         light: Light,
-        point: Vec3,
-        eye_v: Vec3,
-        normal_v: Vec3,
+        point: Point3,
+        eye_v: Vector3,
+        normal_v: Vector3,
         in_shadow: bool,
     ) -> Color {
-        let material_color;
-        if let Some(pattern) = &self.pattern {
-            material_color = pattern.pattern_at(point, obj_transform);
+        let ambient = self.ambient_lighting(obj_transform, light, point);
+        if in_shadow {
+            ambient
+        } else if self.brdf == Brdf::Phong {
+            let (diffuse, specular) =
+                self.phong_lighting(obj_transform, light, point, eye_v, normal_v);
+            ambient + diffuse + specular
         } else {
-            material_color = self.color;
+            ambient + self.direct_lighting(obj_transform, light, point, eye_v, normal_v)
         }
-
-        let effective_color = material_color * light.intensity;
-        let ambient = effective_color * self.ambient;
-
+    }
+    // ambient term of `lighting`, reaches the surface regardless of shadows
+    pub fn ambient_lighting(&self, obj_transform: &Matrix, light: Light, point: Point3) -> Color {
+        self.color_at(point, obj_transform) * light.intensity * self.ambient
+    }
+    // diffuse and specular terms of `lighting`, the light is assumed to be visible
+    pub fn direct_lighting(
+        &self,
+        obj_transform: &Matrix,
+        light: Light,
+        point: Point3,
+        eye_v: Vector3,
+        normal_v: Vector3,
+    ) -> Color {
+        if self.brdf == Brdf::Phong {
+            let (diffuse, specular) =
+                self.phong_lighting(obj_transform, light, point, eye_v, normal_v);
+            return diffuse + specular;
+        }
+        // light intensities are irradiance without falloff, scaled by PI so
+        // a white lambertian surface reflects all of it like Phong with diffuse 1
+        let light_v = (light.position - point).normalize();
+        let color = self.color_at(point, obj_transform);
+        let cos = light_v.dot(normal_v).max(0.0);
+        self.brdf.eval(self, color, light_v, eye_v, normal_v) * light.intensity * (PI * cos)
+    }
+    // classic Phong diffuse and specular terms, kept apart from `Brdf::eval` so
+    // Whitted renders of Phong materials don't change
+    fn phong_lighting(
+        &self,
+        obj_transform: &Matrix,
+        light: Light,
+        point: Point3,
+        eye_v: Vector3,
+        normal_v: Vector3,
+    ) -> (Color, Color) {
+        let effective_color = self.color_at(point, obj_transform) * light.intensity;
         let light_v = (light.position - point).normalize();
-        let diffuse;
-        let specular;
-
         // light_dot_normal represents the cosine of the angle between the light vector and the normal vector.
         // A negative number means the light is on the other side of the surface.
         let light_dot_normal = light_v.dot(normal_v);
-
-        if light_dot_normal < 0.0 || in_shadow {
-            diffuse = Color::new(0.0, 0.0, 0.0);
-            specular = Color::new(0.0, 0.0, 0.0);
-        } else {
-            diffuse = effective_color * self.diffuse * light_dot_normal;
-            // reflect_dot_eye represents the cosine of the angle between the
-            // reflection vector and the eye vector.
-            // A negative number means the light reflects away from the eye.
-
-            let reflect_v = -light_v.reflect(normal_v);
-            let reflect_dot_eye = reflect_v.dot(eye_v);
-
-            if reflect_dot_eye <= 0.0 {
-                specular = Color::new(0.0, 0.0, 0.0);
-            } else {
-                let factor = reflect_dot_eye.powf(self.shininess);
-                specular = light.intensity * self.specular * factor;
-            }
+        if light_dot_normal < 0.0 {
+            return (BLACK, BLACK);
         }
-
-        ambient + diffuse + specular
+        let diffuse = effective_color * self.diffuse * light_dot_normal;
+        // reflect_dot_eye represents the cosine of the angle between the
+        // reflection vector and the eye vector.
+        // A negative number means the light reflects away from the eye.
+        let reflect_v = -light_v.reflect(normal_v);
+        let reflect_dot_eye = reflect_v.dot(eye_v);
+        let specular = if reflect_dot_eye <= 0.0 {
+            BLACK
+        } else {
+            let factor = reflect_dot_eye.powf(self.shininess);
+            light.intensity * self.specular * factor
+        };
+        (diffuse, specular)
     }
 }
 
 #[cfg(test)]
-pub mod tests {
+#[allow(clippy::neg_multiply)]
+mod tests {
     use super::super::float_cmp;
     use super::*;
     #[test]
+    fn material_values_out_of_bounds() {
+        let material = Material::try_new(WHITE, 0.1, 1.5, 0.9, 200.0, 0.0, None);
+        assert_eq!(
+            material.unwrap_err(),
+            Error::ValueOutOfRange {
+                name: "diffuse",
+                value: 1.5
+            }
+        );
+        assert!(Material::try_new(WHITE, 0.1, 0.9, 0.9, 5.0, 0.0, None).is_err());
+        assert!(Material::try_new(WHITE, 0.1, 0.9, 0.9, 200.0, 0.0, None).is_ok());
+    }
+    #[test]
     fn create_material() {
         let m = Material::new(Color::new(1.0, 1.0, 1.0), 0.1, 0.9, 0.9, 200.0, 0.0, None);
         let d = Material::default();
@@ -129,13 +241,43 @@ pub mod tests {
         assert!(float_cmp::equal(m.shininess, d.shininess));
         assert!(float_cmp::equal(m.reflective, d.reflective));
     }
+    fn microfacet_lighting(metallic: Float, roughness: Float, light_position: Point3) -> Color {
+        let m = Material::metallic_roughness(WHITE, metallic, roughness);
+        let light = Light::new(light_position, WHITE);
+        m.lighting(
+            &Matrix::identity_matrix(4),
+            light,
+            Point3::new(0, 0, 0),
+            Vector3::new(0, 0, -1),
+            Vector3::new(0, 0, -1),
+            false,
+        )
+    }
+    #[test]
+    fn microfacet_highlight_sharpens_with_smoothness() {
+        let head_on = Point3::new(0, 0, -10);
+        let off_axis = Point3::new(0, 5, -10);
+        let smooth = microfacet_lighting(1.0, 0.2, head_on);
+        let rough = microfacet_lighting(1.0, 0.8, head_on);
+        assert!(smooth.red > rough.red);
+        assert!(microfacet_lighting(1.0, 0.2, off_axis).red < smooth.red * 0.01);
+        assert!(microfacet_lighting(1.0, 0.8, off_axis).red > rough.red * 0.1);
+    }
+    #[test]
+    fn microfacet_rough_dielectric_is_close_to_lambert() {
+        // ambient 0.1 plus nearly all of the light reflected diffusely
+        let result = microfacet_lighting(0.0, 1.0, Point3::new(0, 0, -10));
+        assert!((result.red - 1.1).abs() < 0.15);
+        let behind = microfacet_lighting(0.0, 1.0, Point3::new(0, 0, 10));
+        assert!(behind.equals(Color::new(0.1, 0.1, 0.1)));
+    }
     #[test]
     fn lighting_light_eye_surface() {
         let m = Material::default();
-        let position = Vec3::point(0, 0, 0);
-        let eyev = Vec3::vector(0, 0, -1);
-        let normalv = Vec3::vector(0, 0, -1);
-        let light = Light::new(Vec3::point(0, 0, -10), Color::new(1.0, 1.0, 1.0));
+        let position = Point3::new(0, 0, 0);
+        let eyev = Vector3::new(0, 0, -1);
+        let normalv = Vector3::new(0, 0, -1);
+        let light = Light::new(Point3::new(0, 0, -10), Color::new(1.0, 1.0, 1.0));
         let result = m.lighting(
             &Matrix::identity_matrix(4),
             light,
@@ -149,10 +291,10 @@ pub mod tests {
     #[test]
     fn lighting_light_eye_offset_surface() {
         let m = Material::default();
-        let position = Vec3::point(0, 0, 0);
-        let eyev = Vec3::vector(0.0, f32::sqrt(2.0) / 2.0, -1.0 * f32::sqrt(2.0) / 2.0);
-        let normalv = Vec3::vector(0, 0, -1);
-        let light = Light::new(Vec3::point(0, 0, -10), Color::new(1.0, 1.0, 1.0));
+        let position = Point3::new(0, 0, 0);
+        let eyev = Vector3::new(0.0, Float::sqrt(2.0) / 2.0, -1.0 * Float::sqrt(2.0) / 2.0);
+        let normalv = Vector3::new(0, 0, -1);
+        let light = Light::new(Point3::new(0, 0, -10), Color::new(1.0, 1.0, 1.0));
         let result = m.lighting(
             &Matrix::identity_matrix(4),
             light,
@@ -166,10 +308,10 @@ pub mod tests {
     #[test]
     fn lighting_light_offset_eye_surface() {
         let m = Material::default();
-        let position = Vec3::point(0, 0, 0);
-        let eyev = Vec3::vector(0, 0, -1);
-        let normalv = Vec3::vector(0, 0, -1);
-        let light = Light::new(Vec3::point(0, 10, -10), Color::new(1.0, 1.0, 1.0));
+        let position = Point3::new(0, 0, 0);
+        let eyev = Vector3::new(0, 0, -1);
+        let normalv = Vector3::new(0, 0, -1);
+        let light = Light::new(Point3::new(0, 10, -10), Color::new(1.0, 1.0, 1.0));
         let result = m.lighting(
             &Matrix::identity_matrix(4),
             light,
@@ -178,19 +320,24 @@ pub mod tests {
             normalv,
             false,
         );
-        assert_eq!(result, Color::new(0.7363961, 0.7363961, 0.7363961));
+        // the golden value is the f32 result, f64 only agrees to float_cmp precision
+        if cfg!(feature = "f64") {
+            assert!(result.equals(Color::new(0.7363961, 0.7363961, 0.7363961)));
+        } else {
+            assert_eq!(result, Color::new(0.7363961, 0.7363961, 0.7363961));
+        }
     }
     #[test]
     fn lighting_eye_in_path_of_reflectionv() {
         let m = Material::default();
-        let position = Vec3::point(0, 0, 0);
-        let eyev = Vec3::vector(
+        let position = Point3::new(0, 0, 0);
+        let eyev = Vector3::new(
             0.0,
-            -1.0 * f32::sqrt(2.0) / 2.0,
-            -1.0 * f32::sqrt(2.0) / 2.0,
+            -1.0 * Float::sqrt(2.0) / 2.0,
+            -1.0 * Float::sqrt(2.0) / 2.0,
         );
-        let normalv = Vec3::vector(0, 0, -1);
-        let light = Light::new(Vec3::point(0, 10, -10), Color::new(1.0, 1.0, 1.0));
+        let normalv = Vector3::new(0, 0, -1);
+        let light = Light::new(Point3::new(0, 10, -10), Color::new(1.0, 1.0, 1.0));
         let result = m.lighting(
             &Matrix::identity_matrix(4),
             light,
@@ -199,15 +346,20 @@ pub mod tests {
             normalv,
             false,
         );
-        assert_eq!(result, Color::new(1.6363853, 1.6363853, 1.6363853));
+        // the golden value carries the rounding error of f32 powf
+        if cfg!(feature = "f64") {
+            assert!(result.equals(Color::new(1.636396, 1.636396, 1.636396)));
+        } else {
+            assert_eq!(result, Color::new(1.6363853, 1.6363853, 1.6363853));
+        }
     }
     #[test]
     fn lighting_light_behind_surface() {
         let m = Material::default();
-        let position = Vec3::point(0, 0, 0);
-        let eyev = Vec3::vector(0, 0, -1);
-        let normalv = Vec3::vector(0, 0, -1);
-        let light = Light::new(Vec3::point(0, 0, 10), Color::new(1.0, 1.0, 1.0));
+        let position = Point3::new(0, 0, 0);
+        let eyev = Vector3::new(0, 0, -1);
+        let normalv = Vector3::new(0, 0, -1);
+        let light = Light::new(Point3::new(0, 0, 10), Color::new(1.0, 1.0, 1.0));
         let result = m.lighting(
             &Matrix::identity_matrix(4),
             light,
@@ -221,10 +373,10 @@ pub mod tests {
     #[test]
     fn lighting_with_surface_in_shadow() {
         let m = Material::default();
-        let position = Vec3::point(0, 0, 0);
-        let eyev = Vec3::vector(0, 0, -1);
-        let normalv = Vec3::vector(0, 0, -1);
-        let light = Light::new(Vec3::point(0, 0, -10), Color::new(1.0, 1.0, 1.0));
+        let position = Point3::new(0, 0, 0);
+        let eyev = Vector3::new(0, 0, -1);
+        let normalv = Vector3::new(0, 0, -1);
+        let light = Light::new(Point3::new(0, 0, -10), Color::new(1.0, 1.0, 1.0));
         let in_shadow = true;
         let result = m.lighting(
             &Matrix::identity_matrix(4),
@@ -247,13 +399,13 @@ pub mod tests {
             0.0,
             Some(Pattern::stripe(vec![WHITE, BLACK])),
         );
-        let eyev = Vec3::vector(0, 0, -1);
-        let normalv = Vec3::vector(0, 0, -1);
-        let light = Light::new(Vec3::point(0, 0, -10), Color::new(1.0, 1.0, 1.0));
+        let eyev = Vector3::new(0, 0, -1);
+        let normalv = Vector3::new(0, 0, -1);
+        let light = Light::new(Point3::new(0, 0, -10), Color::new(1.0, 1.0, 1.0));
         let c1 = m.lighting(
             &Matrix::identity_matrix(4),
             light,
-            Vec3::point(0.9, 0.0, 0.0),
+            Point3::new(0.9, 0.0, 0.0),
             eyev,
             normalv,
             false,
@@ -261,7 +413,7 @@ pub mod tests {
         let c2 = m.lighting(
             &Matrix::identity_matrix(4),
             light,
-            Vec3::point(1.1, 0.0, 0.0),
+            Point3::new(1.1, 0.0, 0.0),
             eyev,
             normalv,
             false,
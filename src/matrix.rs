@@ -1,81 +1,89 @@
+use super::error::*;
 use super::float_cmp;
-use super::vec3::Vec3;
+use super::float_cmp::Float;
+use super::simd::Float4;
+use super::vec3::{Point3, Vector3};
 use std::ops::{Index, Mul};
 
+// most rows and columns a matrix can have, enough for transforms
+pub const MAX_DIM: usize = 4;
+
 #[derive(Debug, Clone)]
 pub struct Matrix {
-    pub cells: Vec<Vec<f32>>,
+    // fixed size so transforms don't allocate, cells outside of
+    // `n_rows` x `n_cols` are zero
+    pub cells: [[Float; MAX_DIM]; MAX_DIM],
     pub n_rows: usize,
     pub n_cols: usize,
 }
 
 impl Matrix {
-    pub fn new(matrix: Vec<Vec<f32>>) -> Self {
-        let n = matrix[0].len();
-        let m = matrix.len();
-        for a in &matrix {
-            assert_eq!(n, a.len(), "Not a rectangular array");
-        }
-        Self {
-            cells: matrix,
-            n_rows: m,
-            n_cols: n,
-        }
+    pub fn new(matrix: Vec<Vec<Float>>) -> Self {
+        Self::try_new(matrix).unwrap_or_else(|error| panic!("{}", error))
     }
-    pub fn row_matrix(vector: Vec<f32>) -> Self {
-        let n = vector.len();
-        let mut matrix = Vec::new();
-        matrix.push(vector);
-        Self {
-            cells: matrix,
-            n_rows: 1,
-            n_cols: n,
+    pub fn try_new(matrix: Vec<Vec<Float>>) -> Result<Self> {
+        if matrix.is_empty() || matrix.iter().any(|row| row.len() != matrix[0].len()) {
+            return Err(Error::InvalidDimensions("matrix is not rectangular"));
         }
-    }
-    pub fn column_matrix(vector: &[f32]) -> Self {
-        let m = vector.len();
-        let mut matrix = Vec::new();
-        for ele in vector {
-            let mut vec = Vec::new();
-            vec.push(*ele);
-            matrix.push(vec);
+        if matrix.len() > MAX_DIM || matrix[0].is_empty() || matrix[0].len() > MAX_DIM {
+            return Err(Error::InvalidDimensions(
+                "matrix needs between 1 and 4 rows and columns",
+            ));
         }
+        let mut cells = [[0.0; MAX_DIM]; MAX_DIM];
+        for (cells, row) in cells.iter_mut().zip(&matrix) {
+            cells[..row.len()].copy_from_slice(row);
+        }
+        Ok(Self {
+            cells,
+            n_rows: matrix.len(),
+            n_cols: matrix[0].len(),
+        })
+    }
+    // 4x4 matrix of the given rows
+    pub fn from_rows(rows: [[Float; MAX_DIM]; MAX_DIM]) -> Self {
         Self {
-            cells: matrix,
-            n_rows: m,
-            n_cols: 1,
+            cells: rows,
+            n_rows: MAX_DIM,
+            n_cols: MAX_DIM,
         }
     }
-    pub fn get_tuple(self) -> Vec<f32> {
+    pub fn row_matrix(vector: Vec<Float>) -> Self {
+        Self::new(vec![vector])
+    }
+    pub fn column_matrix(vector: &[Float]) -> Self {
+        Self::new(vector.iter().map(|ele| vec![*ele]).collect())
+    }
+    pub fn get_tuple(self) -> Vec<Float> {
         assert_eq!(self.n_cols, 1);
-        let mut vector = Vec::new();
-        for row in &self.cells {
-            vector.push(row[0]);
-        }
-        vector
+        self.cells[..self.n_rows].iter().map(|row| row[0]).collect()
     }
-    pub fn columns(&self) -> Vec<Vec<f32>> {
-        let mut columns = Vec::new();
-        for j in 0..self.n_cols {
-            let mut column_vector = Vec::new();
-            for i in 0..self.n_rows {
-                column_vector.push(self.cells[i][j]);
-            }
-            columns.push(column_vector);
+    // 4x4 matrix times the column `tuple`
+    pub fn transform_tuple(&self, tuple: [Float; 4]) -> [Float; 4] {
+        assert!(
+            self.n_rows == 4 && self.n_cols == 4,
+            "only 4x4 matrices transform points and vectors"
+        );
+        let c = &self.cells;
+        let mut product = Float4::splat(0.0);
+        for (k, value) in tuple.iter().enumerate() {
+            let column = Float4::new([c[0][k], c[1][k], c[2][k], c[3][k]]);
+            product = product + column * *value;
         }
-        columns
+        product.to_array()
+    }
+    pub fn columns(&self) -> Vec<Vec<Float>> {
+        (0..self.n_cols)
+            .map(|j| (0..self.n_rows).map(|i| self.cells[i][j]).collect())
+            .collect()
     }
     pub fn zero_matrix(rows: usize, cols: usize) -> Self {
-        let mut row_vector = Vec::new();
-        for _ in 0..cols {
-            row_vector.push(0.0);
-        }
-        let mut matrix = Vec::new();
-        for _ in 0..rows {
-            matrix.push(row_vector.clone());
-        }
+        assert!(
+            (1..=MAX_DIM).contains(&rows) && (1..=MAX_DIM).contains(&cols),
+            "matrix needs between 1 and 4 rows and columns"
+        );
         Self {
-            cells: matrix,
+            cells: [[0.0; MAX_DIM]; MAX_DIM],
             n_rows: rows,
             n_cols: cols,
         }
@@ -88,9 +96,15 @@ impl Matrix {
         matrix
     }
     pub fn transpose(&self) -> Self {
-        Self::new(self.columns())
+        let mut matrix = Self::zero_matrix(self.n_cols, self.n_rows);
+        for i in 0..self.n_rows {
+            for j in 0..self.n_cols {
+                matrix.cells[j][i] = self.cells[i][j];
+            }
+        }
+        matrix
     }
-    pub fn determinant(&self) -> f32 {
+    pub fn determinant(&self) -> Float {
         assert_eq!(
             self.n_rows, self.n_cols,
             "can't computer determinant for non square matrix"
@@ -110,31 +124,20 @@ impl Matrix {
             (row < self.n_rows) && (col < self.n_cols),
             "submatrix arguments overflow matrix dimensions"
         );
-        let mut matrix = Vec::new();
-        for i in 0..self.n_rows {
-            if i == row {
-                continue;
-            }
-            let mut row_vector = Vec::new();
-            for j in 0..self.n_cols {
-                if j == col {
-                    continue;
-                }
-                row_vector.push(self.cells[i][j]);
+        let mut matrix = Self::zero_matrix(self.n_rows - 1, self.n_cols - 1);
+        let rows = (0..self.n_rows).filter(|i| *i != row);
+        for (cells, i) in matrix.cells.iter_mut().zip(rows) {
+            let cols = (0..self.n_cols).filter(|j| *j != col);
+            for (cell, j) in cells.iter_mut().zip(cols) {
+                *cell = self.cells[i][j];
             }
-            matrix.push(row_vector);
-        }
-
-        Self {
-            cells: matrix,
-            n_rows: self.n_rows - 1,
-            n_cols: self.n_cols - 1,
         }
+        matrix
     }
-    pub fn minor(&self, row: usize, col: usize) -> f32 {
+    pub fn minor(&self, row: usize, col: usize) -> Float {
         self.sub_matrix(row, col).determinant()
     }
-    pub fn cofactor(&self, row: usize, col: usize) -> f32 {
+    pub fn cofactor(&self, row: usize, col: usize) -> Float {
         let sign = if (row + col) % 2 == 1 { -1.0 } else { 1.0 };
         sign * self.minor(row, col)
     }
@@ -142,7 +145,13 @@ impl Matrix {
         self.determinant() != 0.0
     }
     pub fn inverse_matrix(&self) -> Self {
-        assert!(self.is_invertible(), "matrix is not invertible");
+        self.try_inverse()
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+    pub fn try_inverse(&self) -> Result<Self> {
+        if !self.is_invertible() {
+            return Err(Error::NotInvertible);
+        }
         let mut matrix = Self::zero_matrix(self.n_rows, self.n_cols);
         let det = self.determinant();
         for i in 0..self.n_rows {
@@ -150,29 +159,44 @@ impl Matrix {
                 matrix.cells[j][i] = self.cofactor(i, j) / det;
             }
         }
+        Ok(matrix)
+    }
+    // element wise blend from `self` at 0 to `other` at 1
+    pub fn lerp(&self, other: &Self, t: Float) -> Self {
+        assert_eq!(
+            (self.n_rows, self.n_cols),
+            (other.n_rows, other.n_cols),
+            "Cannot blend matrices of different dimensions"
+        );
+        let mut matrix = self.clone();
+        for (cells, others) in matrix.cells.iter_mut().zip(&other.cells) {
+            for (a, b) in cells.iter_mut().zip(others) {
+                *a += (b - *a) * t;
+            }
+        }
         matrix
     }
-    pub fn translation(x: f32, y: f32, z: f32) -> Self {
+    pub fn translation(x: Float, y: Float, z: Float) -> Self {
         let mut matrix = Self::identity_matrix(4);
         matrix.cells[0][3] = x;
         matrix.cells[1][3] = y;
         matrix.cells[2][3] = z;
         matrix
     }
-    pub fn translate(&self, x: f32, y: f32, z: f32) -> Self {
+    pub fn translate(&self, x: Float, y: Float, z: Float) -> Self {
         Self::translation(x, y, z) * self
     }
-    pub fn scaling(x: f32, y: f32, z: f32) -> Self {
+    pub fn scaling(x: Float, y: Float, z: Float) -> Self {
         let mut matrix = Self::identity_matrix(4);
         matrix.cells[0][0] = x;
         matrix.cells[1][1] = y;
         matrix.cells[2][2] = z;
         matrix
     }
-    pub fn scale(&self, x: f32, y: f32, z: f32) -> Self {
+    pub fn scale(&self, x: Float, y: Float, z: Float) -> Self {
         Self::scaling(x, y, z) * self
     }
-    pub fn rotation_x(rads: f32) -> Self {
+    pub fn rotation_x(rads: Float) -> Self {
         let mut matrix = Self::identity_matrix(4);
         matrix.cells[1][1] = rads.cos();
         matrix.cells[1][2] = -rads.sin();
@@ -180,10 +204,10 @@ impl Matrix {
         matrix.cells[2][2] = rads.cos();
         matrix
     }
-    pub fn rotate_x(&self, rads: f32) -> Self {
+    pub fn rotate_x(&self, rads: Float) -> Self {
         Self::rotation_x(rads) * self
     }
-    pub fn rotation_y(rads: f32) -> Self {
+    pub fn rotation_y(rads: Float) -> Self {
         let mut matrix = Self::identity_matrix(4);
         matrix.cells[0][0] = rads.cos();
         matrix.cells[0][2] = rads.sin();
@@ -191,10 +215,10 @@ impl Matrix {
         matrix.cells[2][2] = rads.cos();
         matrix
     }
-    pub fn rotate_y(&self, rads: f32) -> Self {
+    pub fn rotate_y(&self, rads: Float) -> Self {
         Self::rotation_y(rads) * self
     }
-    pub fn rotation_z(rads: f32) -> Self {
+    pub fn rotation_z(rads: Float) -> Self {
         let mut matrix = Self::identity_matrix(4);
         matrix.cells[0][0] = rads.cos();
         matrix.cells[0][1] = -rads.sin();
@@ -202,10 +226,10 @@ impl Matrix {
         matrix.cells[1][1] = rads.cos();
         matrix
     }
-    pub fn rotate_z(&self, rads: f32) -> Self {
+    pub fn rotate_z(&self, rads: Float) -> Self {
         Self::rotation_z(rads) * self
     }
-    pub fn shearing(xy: f32, xz: f32, yx: f32, yz: f32, zx: f32, zy: f32) -> Self {
+    pub fn shearing(xy: Float, xz: Float, yx: Float, yz: Float, zx: Float, zy: Float) -> Self {
         let mut matrix = Self::identity_matrix(4);
         matrix.cells[0][1] = xy;
         matrix.cells[0][2] = xz;
@@ -215,31 +239,30 @@ impl Matrix {
         matrix.cells[2][1] = zy;
         matrix
     }
-    pub fn shear(&self, xy: f32, xz: f32, yx: f32, yz: f32, zx: f32, zy: f32) -> Self {
+    pub fn shear(&self, xy: Float, xz: Float, yx: Float, yz: Float, zx: Float, zy: Float) -> Self {
         Self::shearing(xy, xz, yx, yz, zx, zy) * self
     }
-    pub fn view_transformation(from: Vec3, to: Vec3, up: Vec3) -> Self {
+    pub fn view_transformation(from: Point3, to: Point3, up: Vector3) -> Self {
         let forward = (to - from).normalize();
         let upnorm = up.normalize();
         let left = forward.cross(upnorm);
         let true_up = left.cross(forward);
 
-        let matrix = vec![
-            vec![left.x, left.y, left.z, 0.0],
-            vec![true_up.x, true_up.y, true_up.z, 0.0],
-            vec![-forward.x, -forward.y, -forward.z, 0.0],
-            vec![0.0, 0.0, 0.0, 1.0],
-        ];
-        let orientation = Self::new(matrix);
+        let orientation = Self::from_rows([
+            [left.x, left.y, left.z, 0.0],
+            [true_up.x, true_up.y, true_up.z, 0.0],
+            [-forward.x, -forward.y, -forward.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
         orientation * &Self::translation(-from.x, -from.y, -from.z)
     }
 }
 
 impl Index<usize> for Matrix {
-    type Output = [f32];
+    type Output = [Float];
 
     fn index(&self, index: usize) -> &Self::Output {
-        &self.cells[index]
+        &self.cells[..self.n_rows][index][..self.n_cols]
     }
 }
 
@@ -247,77 +270,77 @@ impl Mul<&Self> for Matrix {
     type Output = Self;
 
     fn mul(self, rhs: &Self) -> Self {
-        assert_eq!(self.n_cols, rhs.n_rows, "cannot multiply given matrices");
-        let m = self.n_rows;
-        let n = rhs.n_cols;
-        let mut matrix = Self::zero_matrix(m, n);
-        for (i, row) in self.cells.iter().enumerate() {
-            for (j, col) in rhs.columns().iter().enumerate() {
-                let mut dot_product = 0.0;
-                for k in 0..row.len() {
-                    dot_product += row[k] * col[k];
-                }
-                matrix.cells[i][j] = dot_product;
-            }
-        }
-        matrix
+        &self * rhs
     }
 }
 
-impl<'a, 'b> Mul<&'b Matrix> for &'a Matrix {
+// every row of the product is a sum of rows of `rhs`, added up four cells at a time
+impl<'b> Mul<&'b Matrix> for &Matrix {
     type Output = Matrix;
 
     fn mul(self, rhs: &'b Matrix) -> Matrix {
         assert_eq!(self.n_cols, rhs.n_rows, "cannot multiply given matrices");
-        let m = self.n_rows;
-        let n = rhs.n_cols;
-        let mut matrix = Matrix::zero_matrix(m, n);
-        for (i, row) in self.cells.iter().enumerate() {
-            for (j, col) in rhs.columns().iter().enumerate() {
-                let mut dot_product = 0.0;
-                for k in 0..row.len() {
-                    dot_product += row[k] * col[k];
-                }
-                matrix.cells[i][j] = dot_product;
+        let mut matrix = Matrix::zero_matrix(self.n_rows, rhs.n_cols);
+        for (cells, row) in matrix.cells.iter_mut().zip(&self.cells[..self.n_rows]) {
+            let mut product = Float4::splat(0.0);
+            for (value, rhs_row) in row[..self.n_cols].iter().zip(&rhs.cells) {
+                product = product + Float4::new(*rhs_row) * *value;
             }
+            *cells = product.to_array();
         }
         matrix
     }
 }
 
-impl Mul<Vec<f32>> for Matrix {
+impl Mul<Vec<Float>> for Matrix {
     type Output = Self;
 
-    fn mul(self, rhs: Vec<f32>) -> Self {
+    fn mul(self, rhs: Vec<Float>) -> Self {
         let other_matrix = Self::column_matrix(&rhs);
         self * &other_matrix
     }
 }
 
-impl<'a> Mul<Vec<f32>> for &'a Matrix {
+impl Mul<Vec<Float>> for &Matrix {
     type Output = Matrix;
 
-    fn mul(self, rhs: Vec<f32>) -> Matrix {
+    fn mul(self, rhs: Vec<Float>) -> Matrix {
         let other_matrix = Matrix::column_matrix(&rhs);
         self * &other_matrix
     }
 }
 
-impl Mul<Vec3> for Matrix {
-    type Output = Vec3;
+impl Mul<Point3> for Matrix {
+    type Output = Point3;
 
-    fn mul(self, rhs: Vec3) -> Vec3 {
-        let v = (self * rhs.as_vec()).get_tuple();
-        Vec3::new(&v)
+    fn mul(self, rhs: Point3) -> Point3 {
+        &self * rhs
     }
 }
 
-impl<'a> Mul<Vec3> for &'a Matrix {
-    type Output = Vec3;
+impl Mul<Point3> for &Matrix {
+    type Output = Point3;
 
-    fn mul(self, rhs: Vec3) -> Vec3 {
-        let v = (self * rhs.as_vec()).get_tuple();
-        Vec3::new(&v)
+    fn mul(self, rhs: Point3) -> Point3 {
+        let [x, y, z, _] = self.transform_tuple([rhs.x, rhs.y, rhs.z, 1.0]);
+        Point3::new(x, y, z)
+    }
+}
+
+impl Mul<Vector3> for Matrix {
+    type Output = Vector3;
+
+    fn mul(self, rhs: Vector3) -> Vector3 {
+        &self * rhs
+    }
+}
+
+impl Mul<Vector3> for &Matrix {
+    type Output = Vector3;
+
+    fn mul(self, rhs: Vector3) -> Vector3 {
+        let [x, y, z, _] = self.transform_tuple([rhs.x, rhs.y, rhs.z, 0.0]);
+        Vector3::new(x, y, z)
     }
 }
 
@@ -338,9 +361,20 @@ impl PartialEq for Matrix {
 }
 
 #[cfg(test)]
-pub mod tests {
+mod tests {
     use super::*;
     #[test]
+    fn invalid_matrices() {
+        assert!(Matrix::try_new(vec![]).is_err());
+        assert!(Matrix::try_new(vec![vec![1.0, 2.0], vec![3.0]]).is_err());
+        assert!(Matrix::try_new(vec![vec![1.0; 5]; 5]).is_err());
+        assert!(Matrix::try_new(vec![vec![]]).is_err());
+        assert_eq!(
+            Matrix::scaling(0.0, 1.0, 1.0).try_inverse(),
+            Err(Error::NotInvertible)
+        );
+    }
+    #[test]
     fn create_matrix() {
         let row1 = vec![1.0, 2.0, 3.0, 4.0];
         let row2 = vec![-1.0, -2.0, -3.0, -4.0];
@@ -348,7 +382,7 @@ pub mod tests {
         let row4 = vec![1.0, 1.0, -1.0, -1.0];
         let matrix = vec![row1, row2, row3, row4];
         let i = Matrix::new(matrix);
-        assert_eq!(i.cells[0], vec![1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(i[0], [1.0, 2.0, 3.0, 4.0]);
     }
     #[test]
     fn indexing_matrix() {
@@ -533,36 +567,36 @@ pub mod tests {
     #[test]
     fn default_orientation() {
         let t = Matrix::view_transformation(
-            Vec3::point(0, 0, 0),
-            Vec3::point(0, 0, -1),
-            Vec3::vector(0, 1, 0),
+            Point3::new(0, 0, 0),
+            Point3::new(0, 0, -1),
+            Vector3::new(0, 1, 0),
         );
         assert_eq!(t, Matrix::identity_matrix(4));
     }
     #[test]
     fn view_transformation_pos_z() {
         let t = Matrix::view_transformation(
-            Vec3::point(0, 0, 0),
-            Vec3::point(0, 0, 1),
-            Vec3::vector(0, 1, 0),
+            Point3::new(0, 0, 0),
+            Point3::new(0, 0, 1),
+            Vector3::new(0, 1, 0),
         );
         assert_eq!(t, Matrix::scaling(-1.0, 1.0, -1.0));
     }
     #[test]
     fn view_tranformation_move_world() {
         let t = Matrix::view_transformation(
-            Vec3::point(0, 0, 8),
-            Vec3::point(0, 0, 0),
-            Vec3::vector(0, 1, 0),
+            Point3::new(0, 0, 8),
+            Point3::new(0, 0, 0),
+            Vector3::new(0, 1, 0),
         );
         assert_eq!(t, Matrix::translation(0.0, 0.0, -8.0));
     }
     #[test]
     fn arbitrary_view_transformation() {
         let t = Matrix::view_transformation(
-            Vec3::point(1, 3, 2),
-            Vec3::point(4, -2, 8),
-            Vec3::vector(1, 1, 0),
+            Point3::new(1, 3, 2),
+            Point3::new(4, -2, 8),
+            Vector3::new(1, 1, 0),
         );
         let matrix = vec![
             vec![-0.50709254, 0.50709254, 0.6761234, -2.366432],
@@ -573,4 +607,15 @@ pub mod tests {
         let A = Matrix::new(matrix);
         assert_eq!(t, A);
     }
+    #[test]
+    fn blend_matrices() {
+        let a = Matrix::translation(0.0, 2.0, 0.0);
+        let b = Matrix::translation(4.0, 2.0, 0.0) * &Matrix::scaling(3.0, 3.0, 3.0);
+        assert_eq!(a.lerp(&b, 0.0), a);
+        assert_eq!(a.lerp(&b, 1.0), b);
+        assert_eq!(
+            a.lerp(&b, 0.5),
+            Matrix::translation(2.0, 2.0, 0.0) * &Matrix::scaling(2.0, 2.0, 2.0)
+        );
+    }
 }
@@ -0,0 +1,133 @@
+use super::error::*;
+use super::float_cmp::Float;
+use super::matrix::Matrix;
+use super::quaternion::Decomposition;
+
+// Transform of a moving object, set through the object's `motion`, which then
+// replaces its static `transform` for intersections and normals. Keyframe
+// times are usually within the camera shutter interval, before the first
+// and after the last keyframe the object stands still.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Motion {
+    keyframes: Vec<(Float, Matrix)>,
+    // decomposition of every keyframe, None if it doesn't decompose exactly,
+    // computed once instead of for every ray
+    parts: Vec<Option<Decomposition>>,
+}
+
+impl Motion {
+    pub fn new(keyframes: Vec<(Float, Matrix)>) -> Self {
+        Self::try_new(keyframes).unwrap_or_else(|error| panic!("{}", error))
+    }
+    // fails if there are no keyframes or their times aren't increasing
+    pub fn try_new(keyframes: Vec<(Float, Matrix)>) -> Result<Self> {
+        check_keyframes("motion needs at least one keyframe", &keyframes)?;
+        let parts = keyframes
+            .iter()
+            .map(|(_, m)| m.decompose().filter(|parts| parts.to_matrix() == *m))
+            .collect();
+        Ok(Self { keyframes, parts })
+    }
+    // moves from `start` at time 0 to `end` at time 1
+    pub fn linear(start: Matrix, end: Matrix) -> Self {
+        Self::new(vec![(0.0, start), (1.0, end)])
+    }
+    pub fn keyframes(&self) -> &[(Float, Matrix)] {
+        &self.keyframes
+    }
+    // Transform at `time`, blended between the surrounding keyframes.
+    // Translation and scale are blended linearly and rotations with
+    // `Quaternion::slerp`, transforms that don't decompose into these parts
+    // are blended element wise.
+    pub fn transform_at(&self, time: Float) -> Matrix {
+        let next = self.keyframes.partition_point(|(t, _)| *t <= time);
+        if next == 0 {
+            return self.keyframes[0].1.clone();
+        }
+        if next == self.keyframes.len() {
+            return self.keyframes[next - 1].1.clone();
+        }
+        let (t0, m0) = &self.keyframes[next - 1];
+        let (t1, m1) = &self.keyframes[next];
+        let t = (time - t0) / (t1 - t0);
+        match (&self.parts[next - 1], &self.parts[next]) {
+            (Some(a), Some(b)) => a.interpolate(b, t).to_matrix(),
+            _ => m0.lerp(m1, t),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::float_cmp::consts::{FRAC_PI_2, FRAC_PI_4};
+    use super::*;
+    #[test]
+    fn interpolate_between_keyframes() {
+        let motion = Motion::new(vec![
+            (0.0, Matrix::translation(0.0, 0.0, 0.0)),
+            (0.5, Matrix::translation(2.0, 0.0, 0.0)),
+            (1.0, Matrix::translation(2.0, 4.0, 0.0)),
+        ]);
+        assert_eq!(
+            motion.transform_at(0.25),
+            Matrix::translation(1.0, 0.0, 0.0)
+        );
+        assert_eq!(motion.transform_at(0.5), Matrix::translation(2.0, 0.0, 0.0));
+        assert_eq!(
+            motion.transform_at(0.75),
+            Matrix::translation(2.0, 2.0, 0.0)
+        );
+        // held still outside of the keyframes
+        assert_eq!(
+            motion.transform_at(-1.0),
+            Matrix::translation(0.0, 0.0, 0.0)
+        );
+        assert_eq!(motion.transform_at(3.0), Matrix::translation(2.0, 4.0, 0.0));
+    }
+    #[test]
+    fn rotating_objects_keep_their_size() {
+        let motion = Motion::linear(
+            Matrix::scaling(2.0, 2.0, 2.0),
+            Matrix::rotation_z(FRAC_PI_2) * &Matrix::scaling(2.0, 2.0, 2.0),
+        );
+        assert_eq!(
+            motion.transform_at(0.5),
+            Matrix::rotation_z(FRAC_PI_4) * &Matrix::scaling(2.0, 2.0, 2.0)
+        );
+        // sheared keyframes are blended element wise
+        let motion = Motion::linear(
+            Matrix::identity_matrix(4),
+            Matrix::shearing(1.0, 0.0, 0.0, 0.0, 0.0, 0.0),
+        );
+        assert_eq!(
+            motion.transform_at(0.5),
+            Matrix::shearing(0.5, 0.0, 0.0, 0.0, 0.0, 0.0)
+        );
+    }
+    #[test]
+    fn single_keyframe_stands_still() {
+        let motion = Motion::new(vec![(0.3, Matrix::scaling(2.0, 2.0, 2.0))]);
+        assert_eq!(motion.transform_at(0.0), Matrix::scaling(2.0, 2.0, 2.0));
+        assert_eq!(motion.transform_at(1.0), Matrix::scaling(2.0, 2.0, 2.0));
+    }
+    #[test]
+    fn invalid_keyframes() {
+        assert!(Motion::try_new(vec![]).is_err());
+        let still = Matrix::identity_matrix(4);
+        assert_eq!(
+            Motion::try_new(vec![(0.5, still.clone()), (0.5, still)]),
+            Err(Error::ValueOutOfRange {
+                name: "keyframe time",
+                value: 0.5
+            })
+        );
+    }
+    #[test]
+    #[should_panic]
+    fn keyframes_out_of_order() {
+        Motion::new(vec![
+            (1.0, Matrix::identity_matrix(4)),
+            (0.0, Matrix::identity_matrix(4)),
+        ]);
+    }
+}
@@ -1,28 +1,178 @@
 use super::color::Color;
+use super::error::*;
+use super::float_cmp::Float;
 use super::intersection::Intersections;
 use super::light::Light;
 use super::material::Material;
-use super::ray::Ray;
-use super::vec3::Vec3;
+use super::matrix::Matrix;
+use super::motion::Motion;
+use super::ray::{Ray, RayPacket};
+use super::sampling::Rng;
+use super::simd::LANES;
+use super::vec3::{Point3, Vector3};
+use std::io;
 use std::sync::atomic;
 
-static mut ID: atomic::AtomicUsize = atomic::AtomicUsize::new(0);
+static ID: atomic::AtomicUsize = atomic::AtomicUsize::new(0);
 
 pub fn get_object_uid() -> usize {
-    unsafe { ID.fetch_add(1, atomic::Ordering::SeqCst) }
+    ID.fetch_add(1, atomic::Ordering::SeqCst)
+}
+
+// point on the surface of an object used to sample it as a light source
+#[derive(Debug, Copy, Clone)]
+pub struct SurfaceSample {
+    pub point: Point3,
+    // outward facing unit normal
+    pub normal: Vector3,
+    // probability density per unit of world space area
+    pub pdf: Float,
+}
+
+// Inverse of an object's transform, worked out when the transform is set so
+// intersecting a ray never inverts a matrix. The transform has to be an
+// invertible 4x4 matrix.
+pub fn transform_inverse(transform: &Matrix) -> Result<Matrix> {
+    if (transform.n_rows, transform.n_cols) != (4, 4) {
+        return Err(Error::InvalidDimensions("transform has to be 4x4"));
+    }
+    transform.try_inverse()
+}
+
+// what a ray is traced for, decides which objects it can hit
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RayKind {
+    // rays leaving the camera
+    Camera,
+    // mirror, glossy and indirect bounces
+    Reflection,
+    // visibility tests towards lights and ambient occlusion rays
+    Shadow,
+}
+
+// Per object render settings, everything is enabled by default.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ObjectFlags {
+    // blocks light from reaching other objects
+    pub casts_shadow: bool,
+    // is darkened by objects between it and the lights
+    pub receives_shadow: bool,
+    // seen directly by the camera
+    pub visible_to_camera: bool,
+    // seen in reflections and indirect bounces
+    pub visible_in_reflections: bool,
+}
+
+impl Default for ObjectFlags {
+    fn default() -> Self {
+        Self {
+            casts_shadow: true,
+            receives_shadow: true,
+            visible_to_camera: true,
+            visible_in_reflections: true,
+        }
+    }
+}
+
+impl ObjectFlags {
+    pub fn visible_to(&self, kind: RayKind) -> bool {
+        match kind {
+            RayKind::Camera => self.visible_to_camera,
+            RayKind::Reflection => self.visible_in_reflections,
+            RayKind::Shadow => self.casts_shadow,
+        }
+    }
 }
 
 pub trait Object {
-    fn intersection(&self, ray: &Ray) -> Intersections;
-    fn normal_at(&self, point: Vec3) -> Vec3;
+    // Adds the crossings of `ray` to `intersections`, which can be a buffer
+    // reused between rays so intersecting doesn't allocate.
+    fn intersect_into(&self, ray: &Ray, intersections: &mut Intersections);
+    fn intersection(&self, ray: &Ray) -> Intersections {
+        let mut intersections = Intersections::new();
+        self.intersect_into(ray, &mut intersections);
+        intersections
+    }
+    // appends the crossings of every ray of the packet to its lane of
+    // `intersections`, objects with a SIMD version override this
+    fn intersect_packet_into(
+        &self,
+        packet: &RayPacket,
+        intersections: &mut [Intersections; LANES],
+    ) {
+        for (lane, xs) in intersections.iter_mut().enumerate() {
+            self.intersect_into(&packet.ray(lane), xs);
+        }
+    }
+    // the intersections of every ray of the packet
+    fn intersect_packet(&self, packet: &RayPacket) -> [Intersections; LANES] {
+        let mut intersections: [Intersections; LANES] = Default::default();
+        self.intersect_packet_into(packet, &mut intersections);
+        intersections
+    }
+    // surface normal at `point` with the object where it is at `time`
+    fn normal_at_time(&self, point: Point3, time: Float) -> Vector3;
+    fn normal_at(&self, point: Point3) -> Vector3 {
+        self.normal_at_time(point, 0.0)
+    }
     fn lighting_at(
         &self,
-        point: Vec3,
-        eye_v: Vec3,
-        normal_v: Vec3,
+        point: Point3,
+        eye_v: Vector3,
+        normal_v: Vector3,
         light: Light,
         in_shadow: bool,
     ) -> Color;
     fn mut_material(&mut self) -> &mut Material;
     fn material(&self) -> &Material;
+    fn transform(&self) -> &Matrix;
+    // inverse of `transform`, kept up to date by `try_set_transform`
+    fn inverse_transform(&self) -> &Matrix;
+    fn set_transform(&mut self, transform: Matrix) {
+        self.try_set_transform(transform)
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+    // the transform has to be an invertible 4x4 matrix, see `transform_inverse`
+    fn try_set_transform(&mut self, transform: Matrix) -> Result<()>;
+    fn motion(&self) -> Option<&Motion>;
+    fn mut_motion(&mut self) -> &mut Option<Motion>;
+    // transform at `time`, moving objects follow their `Motion`
+    fn transform_at(&self, time: Float) -> Matrix {
+        match self.motion() {
+            Some(motion) => motion.transform_at(time),
+            None => self.transform().clone(),
+        }
+    }
+    // inverse of the transform at `time`, moving objects follow their `Motion`
+    fn inverse_transform_at(&self, time: Float) -> Matrix {
+        match self.motion() {
+            Some(motion) => motion.transform_at(time).inverse_matrix(),
+            None => self.inverse_transform().clone(),
+        }
+    }
+    // `ray` in object space, moving objects are still inverted for every ray
+    fn object_ray(&self, ray: &Ray) -> Ray {
+        match self.motion() {
+            Some(_) => ray.transform(&self.inverse_transform_at(ray.time)),
+            None => ray.transform(self.inverse_transform()),
+        }
+    }
+    fn object_packet(&self, packet: &RayPacket) -> RayPacket {
+        match self.motion() {
+            Some(_) => packet.transform(&self.inverse_transform_at(packet.time)),
+            None => packet.transform(self.inverse_transform()),
+        }
+    }
+    fn flags(&self) -> &ObjectFlags;
+    fn mut_flags(&mut self) -> &mut ObjectFlags;
+    // uniformly distributed point on the surface at `time`, None for unbounded surfaces
+    fn sample_surface(&self, _time: Float, _rng: &mut Rng) -> Option<SurfaceSample> {
+        None
+    }
+    // whether `sample_surface` returns samples, without drawing one
+    fn has_surface_samples(&self) -> bool {
+        false
+    }
+    // writes the object type tag followed by the encoded object, see `encode`
+    fn encode_tagged(&self, writer: &mut dyn io::Write) -> io::Result<()>;
 }
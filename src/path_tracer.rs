@@ -0,0 +1,431 @@
+use super::background::Background;
+use super::color::*;
+use super::float_cmp::consts::PI;
+use super::float_cmp::Float;
+use super::object::RayKind;
+use super::ray::Ray;
+use super::sampling::*;
+use super::vec3::{Point3, Vector3};
+use super::world::*;
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Integrator {
+    // Phong direct lighting plus perfect mirror reflections, see `World::color_at`
+    #[default]
+    Whitted,
+    // Monte Carlo global illumination, see `World::path_trace`
+    PathTracer {
+        samples: u32,
+        max_depth: u32,
+    },
+    // grayscale ambient occlusion only, for compositing, see `World::occlusion_color`
+    AmbientOcclusion,
+}
+
+impl Integrator {
+    // number of samples averaged into every pixel
+    pub fn samples(&self) -> u32 {
+        match *self {
+            Self::Whitted | Self::AmbientOcclusion => 1,
+            Self::PathTracer { samples, .. } => samples.max(1),
+        }
+    }
+}
+
+// bounces before russian roulette may terminate a path
+const MIN_BOUNCES: u32 = 3;
+
+impl World {
+    // Estimates the light arriving along `ray` by following a single random path.
+    // Point lights, area lights and environment maps are sampled directly at
+    // every vertex, so hitting the latter two after a diffuse bounce adds nothing.
+    pub fn path_trace(&self, ray: &Ray, max_depth: u32, rng: &mut Rng) -> Color {
+        let mut radiance = BLACK;
+        let mut throughput = WHITE;
+        let mut ray = *ray;
+        let mut after_diffuse_bounce = false;
+        for depth in 0..max_depth {
+            let kind = if depth == 0 {
+                RayKind::Camera
+            } else {
+                RayKind::Reflection
+            };
+            let xs = self.intersect_visible(&ray, kind);
+            let hit = self.surface_hit(&xs);
+            let distance = hit.map_or(Float::INFINITY, |hit| hit.t);
+            let (transmittance, scattered) = self.volumes_along(&ray, &xs, distance);
+            radiance = radiance + throughput * scattered;
+            throughput = throughput * transmittance;
+            let hit = match hit {
+                Some(hit) => hit,
+                None => {
+                    let sampled = matches!(self.background, Background::Environment(_));
+                    if !(after_diffuse_bounce && sampled) {
+                        radiance = radiance + throughput * self.background.color_in(ray.direction);
+                    }
+                    break;
+                }
+            };
+            let state = hit.compute_state(&ray, self);
+            let object = &self.objects[&state.obj_id];
+            let material = object.material();
+            if !(after_diffuse_bounce && object.has_surface_samples()) {
+                radiance = radiance + throughput * material.emissive;
+            }
+            radiance = radiance + throughput * self.subsurface_lighting(&state);
+
+            let receives_shadow = object.flags().receives_shadow;
+            let color = material.color_at(state.point, &object.transform_at(state.time));
+            let brdf = |light_v: Vector3| {
+                material
+                    .brdf
+                    .eval(material, color, light_v, state.eyev, state.normalv)
+                    * ((1.0 - material.reflective) * (1.0 - material.transparency))
+            };
+            let transmission_to = |target: Point3, margin: Float| {
+                if receives_shadow {
+                    self.transmission_to(state.over_point, target, margin, state.time)
+                } else {
+                    WHITE
+                }
+            };
+            for light in &self.lights {
+                let light_v = (light.position - state.over_point).normalize();
+                let light_dot_normal = light_v.dot(state.normalv);
+                if light_dot_normal > 0.0 {
+                    radiance = radiance
+                        + throughput
+                            * brdf(light_v)
+                            * light.intensity
+                            * transmission_to(light.position, 0.0)
+                            * (PI * light_dot_normal);
+                }
+            }
+            for emitter in self.emitters() {
+                if let Some(sample) = emitter.sample_surface(state.time, rng) {
+                    let to_light = sample.point - state.over_point;
+                    let distance_squared = to_light.dot(to_light);
+                    let light_v = to_light.normalize();
+                    let cos_surface = light_v.dot(state.normalv);
+                    let cos_light = light_v.dot(sample.normal).abs();
+                    if cos_surface > 0.0 && cos_light > 0.0 {
+                        radiance = radiance
+                            + throughput
+                                * brdf(light_v)
+                                * emitter.material().emissive
+                                * transmission_to(sample.point, OCCLUSION_MARGIN)
+                                * (cos_surface * cos_light / (distance_squared * sample.pdf));
+                    }
+                }
+            }
+            if let Background::Environment(map) = &self.background {
+                if let Some(sample) = map.sample(rng) {
+                    let cos = sample.direction.dot(state.normalv);
+                    if cos > 0.0 {
+                        let transmission = if receives_shadow {
+                            self.transmission(
+                                &Ray::new(state.over_point, sample.direction).with_time(state.time),
+                                Float::INFINITY,
+                            )
+                        } else {
+                            WHITE
+                        };
+                        radiance = radiance
+                            + throughput
+                                * brdf(sample.direction)
+                                * sample.radiance
+                                * transmission
+                                * (cos / sample.pdf);
+                    }
+                }
+            }
+
+            let mut origin = state.over_point;
+            let direction =
+                if material.transparency > 0.0 && rng.next_float() < material.transparency {
+                    let refracted = (-state.eyev).refract(state.normalv, state.n1 / state.n2);
+                    match refracted {
+                        Some(direction) if rng.next_float() >= state.schlick() => {
+                            throughput = throughput * color;
+                            origin = state.under_point;
+                            direction
+                        }
+                        _ => {
+                            after_diffuse_bounce = false;
+                            state.reflectv
+                        }
+                    }
+                } else if rng.next_float() < material.reflective {
+                    after_diffuse_bounce = false;
+                    let direction = uniform_cone(state.reflectv, material.reflection_cone(), rng);
+                    if direction.dot(state.normalv) > 0.0 {
+                        direction
+                    } else {
+                        state.reflectv
+                    }
+                } else {
+                    after_diffuse_bounce = true;
+                    match material
+                        .brdf
+                        .sample(material, color, state.eyev, state.normalv, rng)
+                    {
+                        Some((direction, weight)) => {
+                            throughput = throughput * weight;
+                            direction
+                        }
+                        None => break,
+                    }
+                };
+
+            if depth + 1 >= MIN_BOUNCES {
+                let survival = throughput
+                    .red
+                    .max(throughput.green)
+                    .max(throughput.blue)
+                    .min(0.95);
+                if rng.next_float() >= survival {
+                    break;
+                }
+                throughput = throughput * (1.0 / survival);
+            }
+            ray = Ray::new(origin, direction).with_time(state.time);
+        }
+        radiance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::background::EnvironmentMap;
+    use super::super::camera::Camera;
+    use super::super::canvas::Canvas;
+    use super::super::cube::Cube;
+    use super::super::float_cmp::consts;
+    use super::super::light::Light;
+    use super::super::matrix::Matrix;
+    use super::super::object::Object;
+    use super::super::plane::Plane;
+    use super::super::sphere::Sphere;
+    use super::*;
+
+    fn average(world: &World, ray: &Ray, max_depth: u32, n: u32) -> Color {
+        let mut sum = BLACK;
+        for i in 0..n {
+            sum = sum + world.path_trace(ray, max_depth, &mut Rng::new(u64::from(i)));
+        }
+        sum * (1.0 / n as Float)
+    }
+    #[test]
+    fn path_misses_everything() {
+        let world = World::default();
+        let ray = Ray::new(Point3::new(0, 0, -5), Vector3::new(0, 1, 0));
+        assert_eq!(world.path_trace(&ray, 5, &mut Rng::new(1)), BLACK);
+    }
+    #[test]
+    fn emissive_surface_seen_directly() {
+        let mut world = World::new();
+        let mut sphere = Sphere::new();
+        sphere.material.emissive = Color::new(0.5, 1.0, 2.0);
+        sphere.material.diffuse = 0.0;
+        world.objects.insert(sphere.get_id(), Box::new(sphere));
+        let ray = Ray::new(Point3::new(0, 0, -5), Vector3::new(0, 0, 1));
+        let color = world.path_trace(&ray, 5, &mut Rng::new(1));
+        assert!(color.equals(Color::new(0.5, 1.0, 2.0)));
+    }
+    #[test]
+    fn furnace_inside_emissive_sphere() {
+        // every bounce sees emission 1 and keeps half of the energy,
+        // so the radiance converges to 1 + 1/2 + 1/4 + ... = 2
+        let mut world = World::new();
+        let mut sphere = Sphere::new();
+        sphere.material.color = WHITE;
+        sphere.material.diffuse = 0.5;
+        sphere.material.specular = 0.0;
+        sphere.material.emissive = WHITE;
+        world.objects.insert(sphere.get_id(), Box::new(sphere));
+        let ray = Ray::new(Point3::new(0, 0, 0), Vector3::new(0, 0, 1));
+        let color = average(&world, &ray, 64, 4000);
+        assert!((color.red - 2.0).abs() < 0.05);
+        assert!((color.green - 2.0).abs() < 0.05);
+        assert!((color.blue - 2.0).abs() < 0.05);
+    }
+    #[test]
+    fn direct_light_matches_lambert() {
+        let mut world = World::new();
+        world.lights.push(Light::new(Point3::new(0, 10, 0), WHITE));
+        let mut floor = Plane::new();
+        floor.material.color = Color::new(0.5, 0.5, 0.5);
+        floor.material.diffuse = 0.8;
+        floor.material.specular = 0.0;
+        world.objects.insert(floor.get_id(), Box::new(floor));
+        let ray = Ray::new(Point3::new(0, 1, 0), Vector3::new(0, -1, 0));
+        // bounced rays never come back to an infinite floor
+        let color = world.path_trace(&ray, 5, &mut Rng::new(3));
+        assert!(color.equals(Color::new(0.4, 0.4, 0.4)));
+    }
+    #[test]
+    fn direct_light_has_phong_highlight() {
+        let mut world = World::new();
+        world.lights.push(Light::new(Point3::new(0, 10, 0), WHITE));
+        let mut floor = Plane::new();
+        floor.material.color = Color::new(0.5, 0.5, 0.5);
+        floor.material.diffuse = 0.8;
+        floor.material.specular = 0.5;
+        world.objects.insert(floor.get_id(), Box::new(floor));
+        // looking down the mirror direction of the light the normalized lobe
+        // adds specular * (shininess + 2) / 2 to the diffuse 0.4
+        let ray = Ray::new(Point3::new(0, 1, 0), Vector3::new(0, -1, 0));
+        let color = world.path_trace(&ray, 5, &mut Rng::new(3));
+        assert!(color.equals(Color::new(50.9, 50.9, 50.9)));
+    }
+    #[test]
+    fn sphere_light_is_sampled_once() {
+        // the floor reflects diffuse * Le * (r / h)^2 = 0.9 of the light above it,
+        // diffuse bounces that hit the light must not add its emission again
+        let mut world = World::new();
+        let mut floor = Plane::new();
+        floor.material.color = WHITE;
+        floor.material.diffuse = 0.9;
+        floor.material.specular = 0.0;
+        world.objects.insert(floor.get_id(), Box::new(floor));
+        let mut light = Sphere::new();
+        light.set_transform(Matrix::translation(0.0, 5.0, 0.0) * &Matrix::scaling(0.5, 0.5, 0.5));
+        light.material.emissive = Color::new(100.0, 100.0, 100.0);
+        world.objects.insert(light.get_id(), Box::new(light));
+        let ray = Ray::new(Point3::new(0, 1, 0), Vector3::new(0, -1, 0));
+        let direct = average(&world, &ray, 1, 4000);
+        let bounced = average(&world, &ray, 4, 4000);
+        assert!((direct.red - 0.9).abs() < 0.05);
+        assert!((bounced.red - 0.9).abs() < 0.05);
+    }
+    #[test]
+    fn cube_light_is_sampled() {
+        // only the bottom face of the cube is visible from below, it covers
+        // 0.04858 sr of cosine weighted solid angle over the floor
+        let mut world = World::new();
+        let mut floor = Plane::new();
+        floor.material.color = WHITE;
+        floor.material.diffuse = 0.9;
+        floor.material.specular = 0.0;
+        world.objects.insert(floor.get_id(), Box::new(floor));
+        let mut light = Cube::new();
+        light.set_transform(Matrix::translation(0.0, 5.0, 0.0) * &Matrix::scaling(0.5, 0.5, 0.5));
+        light.material.emissive = Color::new(100.0, 100.0, 100.0);
+        world.objects.insert(light.get_id(), Box::new(light));
+        let ray = Ray::new(Point3::new(0, 1, 0), Vector3::new(0, -1, 0));
+        let expected = 0.9 / PI * 100.0 * 0.04858;
+        let direct = average(&world, &ray, 1, 4000);
+        let bounced = average(&world, &ray, 4, 4000);
+        assert!((direct.red - expected).abs() < 0.05 * expected);
+        assert!((bounced.red - expected).abs() < 0.05 * expected);
+    }
+    #[test]
+    fn color_bleeds_between_surfaces() {
+        let mut world = World::new();
+        world.lights.push(Light::new(Point3::new(0, 5, -5), WHITE));
+        let mut floor = Plane::new();
+        floor.material.color = WHITE;
+        world.objects.insert(floor.get_id(), Box::new(floor));
+        let mut wall = Plane::new();
+        wall.set_transform(
+            Matrix::translation(0.0, 0.0, 1.0) * &Matrix::rotation_x(consts::FRAC_PI_2),
+        );
+        wall.material.color = RED;
+        world.objects.insert(wall.get_id(), Box::new(wall));
+
+        let mut camera = Camera::new(1, 1, 0.1);
+        camera.transform = Matrix::view_transformation(
+            Point3::new(0, 1, -1),
+            Point3::new(0.0, 0.0, 0.5),
+            Vector3::new(0, 1, 0),
+        );
+        let ray = camera.ray_for_pixel(0, 0);
+        let whitted = world.color_at(&ray);
+        let traced = average(&world, &ray, 4, 500);
+        // the white floor picks up red light from the wall next to it
+        assert!((whitted.red - whitted.green).abs() < 0.0001);
+        assert!(traced.red > traced.green + 0.05);
+    }
+    #[test]
+    fn camera_path_tracer_is_deterministic() {
+        let world = World::default();
+        let mut camera = Camera::new(5, 5, consts::PI / 2.0);
+        camera.transform = Matrix::view_transformation(
+            Point3::new(0, 0, -5),
+            Point3::new(0, 0, 0),
+            Vector3::new(0, 1, 0),
+        );
+        camera.integrator = Integrator::PathTracer {
+            samples: 4,
+            max_depth: 4,
+        };
+        let a = camera.render(&world);
+        let b = camera.render(&world);
+        assert_eq!(a.grid, b.grid);
+        assert!(a.pixel_at(2, 2).red > 0.0);
+        assert_eq!(
+            camera
+                .render_tile(&world, super::super::tile::Tile::new(0, 0, 1, 1))
+                .samples,
+            4
+        );
+    }
+    #[test]
+    fn sky_lights_the_scene() {
+        // a white floor under a uniform sky of radiance 1 reflects its albedo
+        let mut world = World::new();
+        world.background = Background::Solid(WHITE);
+        let mut floor = Plane::new();
+        floor.material.color = WHITE;
+        floor.material.diffuse = 0.5;
+        floor.material.specular = 0.0;
+        world.objects.insert(floor.get_id(), Box::new(floor));
+        let ray = Ray::new(Point3::new(0, 1, 0), Vector3::new(0, -1, 0));
+        let color = average(&world, &ray, 2, 100);
+        assert!(color.equals(Color::new(0.5, 0.5, 0.5)));
+    }
+    #[test]
+    fn environment_map_lights_the_scene() {
+        // same as a uniform sky, but the light comes from sampling the map
+        let mut sky = Canvas::new(16, 8);
+        sky.grid = vec![WHITE; 16 * 8];
+        let mut world = World::new();
+        world.background = Background::Environment(EnvironmentMap::new(sky));
+        let mut floor = Plane::new();
+        floor.material.color = WHITE;
+        floor.material.diffuse = 0.5;
+        floor.material.specular = 0.0;
+        world.objects.insert(floor.get_id(), Box::new(floor));
+        let ray = Ray::new(Point3::new(0, 1, 0), Vector3::new(0, -1, 0));
+        let color = average(&world, &ray, 4, 4000);
+        assert!((color.red - 0.5).abs() < 0.03);
+    }
+    #[test]
+    fn paths_pass_through_glass() {
+        let mut world = World::new();
+        world.background = Background::Solid(BLUE);
+        let mut sphere = Sphere::new();
+        sphere.material.color = Color::new(0.5, 1.0, 1.0);
+        sphere.material.transparency = 1.0;
+        world.objects.insert(sphere.get_id(), Box::new(sphere));
+        let ray = Ray::new(Point3::new(0.2, 0.1, -5.0), Vector3::new(0, 0, 1));
+        assert!(world.path_trace(&ray, 4, &mut Rng::new(3)).equals(BLUE));
+    }
+    #[test]
+    fn colored_glass_tints_direct_light() {
+        let mut world = World::new();
+        world.lights.push(Light::new(Point3::new(0, 10, 0), WHITE));
+        let mut floor = Plane::new();
+        floor.material.diffuse = 1.0;
+        floor.material.specular = 0.0;
+        let mut glass = Sphere::new();
+        glass.set_transform(Matrix::translation(0.0, 3.0, 0.0));
+        glass.material.color = Color::new(1.0, 0.5, 0.0);
+        glass.material.transparency = 1.0;
+        world.objects.insert(floor.get_id(), Box::new(floor));
+        world.objects.insert(glass.get_id(), Box::new(glass));
+        let ray = Ray::new(Point3::new(0, 1, 0), Vector3::new(0, -1, 0));
+        let color = world.path_trace(&ray, 1, &mut Rng::new(5));
+        assert!(color.equals(Color::new(1.0, 0.5, 0.0)));
+    }
+}
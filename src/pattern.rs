@@ -4,10 +4,21 @@ use super::matrix::Matrix;
 use super::vec3::*;
 use std::fmt;
 
+// predefined pattern functions, discriminants are used by `encode`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PatternKind {
+    Stripe = 0,
+    Gradient = 1,
+    Ring = 2,
+    Checkers = 3,
+}
+
 #[derive(Clone)]
 pub struct Pattern {
     colors: Vec<Color>,
-    function: fn(Vec3, &[Color]) -> Color,
+    function: fn(Point3, &[Color]) -> Color,
+    // None for patterns with a user supplied function
+    kind: Option<PatternKind>,
     pub transform: Matrix,
 }
 
@@ -18,17 +29,24 @@ impl fmt::Debug for Pattern {
 }
 
 impl Pattern {
-    pub fn new(colors: Vec<Color>, function: fn(Vec3, &[Color]) -> Color) -> Self {
+    pub fn new(colors: Vec<Color>, function: fn(Point3, &[Color]) -> Color) -> Self {
         Self {
             colors,
             function,
+            kind: None,
             transform: Matrix::identity_matrix(4),
         }
     }
-    pub fn color_at(&self, point_in_space: Vec3) -> Color {
+    pub fn kind(&self) -> Option<PatternKind> {
+        self.kind
+    }
+    pub fn colors(&self) -> &[Color] {
+        &self.colors
+    }
+    pub fn color_at(&self, point_in_space: Point3) -> Color {
         (self.function)(point_in_space, &self.colors)
     }
-    pub fn pattern_at(&self, world_point: Vec3, obj_transform: &Matrix) -> Color {
+    pub fn pattern_at(&self, world_point: Point3, obj_transform: &Matrix) -> Color {
         let obj_point = obj_transform.inverse_matrix() * world_point;
         let pattern_point = self.transform.inverse_matrix() * obj_point;
 
@@ -36,18 +54,19 @@ impl Pattern {
     }
     // predefined patterns
     pub fn stripe(colors: Vec<Color>) -> Self {
-        fn stripe_fn(point: Vec3, colors: &[Color]) -> Color {
+        fn stripe_fn(point: Point3, colors: &[Color]) -> Color {
             let n = colors.len();
-            colors[point.x.floor() as usize % n]
+            colors[(point.x.floor() as i32).rem_euclid(n as i32) as usize]
         }
         Self {
             colors,
             function: stripe_fn,
+            kind: Some(PatternKind::Stripe),
             transform: Matrix::identity_matrix(4),
         }
     }
     pub fn gradient(colors: Vec<Color>) -> Self {
-        fn gradient_fn(point: Vec3, gradient: &[Color]) -> Color {
+        fn gradient_fn(point: Point3, gradient: &[Color]) -> Color {
             let distance = gradient[1] - gradient[0];
             let fraction = point.x - point.x.floor();
             gradient[0] + distance * fraction
@@ -60,22 +79,24 @@ impl Pattern {
         Self {
             colors,
             function: gradient_fn,
+            kind: Some(PatternKind::Gradient),
             transform: Matrix::identity_matrix(4),
         }
     }
     pub fn ring(colors: Vec<Color>) -> Self {
-        fn ring_fn(point: Vec3, colors: &[Color]) -> Color {
+        fn ring_fn(point: Point3, colors: &[Color]) -> Color {
             let n = colors.len();
             colors[((point.x * point.x) + (point.z * point.z)).sqrt().floor() as usize % n]
         }
         Self {
             colors,
             function: ring_fn,
+            kind: Some(PatternKind::Ring),
             transform: Matrix::identity_matrix(4),
         }
     }
     pub fn checkers(colors: Vec<Color>) -> Self {
-        fn checkers_fn(point: Vec3, colors: &[Color]) -> Color {
+        fn checkers_fn(point: Point3, colors: &[Color]) -> Color {
             let n = colors.len();
             let mut p = point;
             if float_cmp::equal(p.x, 0.0) {
@@ -91,11 +112,12 @@ impl Pattern {
             // which on floor operation gives -1 which is clearly not desirable
             // hence manually checking for apparent zero value with lesser precision than f32::EPSILON
             // and setting it to zero
-            colors[(p.x.floor() + p.y.floor() + p.z.floor()) as usize % n]
+            colors[((p.x.floor() + p.y.floor() + p.z.floor()) as i32).rem_euclid(n as i32) as usize]
         }
         Self {
             colors,
             function: checkers_fn,
+            kind: Some(PatternKind::Checkers),
             transform: Matrix::identity_matrix(4),
         }
         // TODO: UV mapping for spherical texture mapping
@@ -103,7 +125,7 @@ impl Pattern {
 }
 
 #[cfg(test)]
-pub mod tests {
+mod tests {
     use super::*;
     #[test]
     fn create_stripe_pattern() {
@@ -117,33 +139,33 @@ pub mod tests {
         let colors = vec![WHITE, BLACK];
         let pattern = Pattern::stripe(colors);
         // constant in y
-        assert_eq!(pattern.color_at(Vec3::point(0, 0, 0)), WHITE);
-        assert_eq!(pattern.color_at(Vec3::point(0, 2, 0)), WHITE);
-        assert_eq!(pattern.color_at(Vec3::point(0, 1, 0)), WHITE);
+        assert_eq!(pattern.color_at(Point3::new(0, 0, 0)), WHITE);
+        assert_eq!(pattern.color_at(Point3::new(0, 2, 0)), WHITE);
+        assert_eq!(pattern.color_at(Point3::new(0, 1, 0)), WHITE);
         // constant in z
-        assert_eq!(pattern.color_at(Vec3::point(0, 0, 0)), WHITE);
-        assert_eq!(pattern.color_at(Vec3::point(0, 0, 1)), WHITE);
-        assert_eq!(pattern.color_at(Vec3::point(0, 0, 2)), WHITE);
+        assert_eq!(pattern.color_at(Point3::new(0, 0, 0)), WHITE);
+        assert_eq!(pattern.color_at(Point3::new(0, 0, 1)), WHITE);
+        assert_eq!(pattern.color_at(Point3::new(0, 0, 2)), WHITE);
         // alternates in x
-        assert_eq!(pattern.color_at(Vec3::point(-1, 2, 0)), BLACK);
-        assert_eq!(pattern.color_at(Vec3::point(0, 0, 0)), WHITE);
-        assert_eq!(pattern.color_at(Vec3::point(1, 2, 0)), BLACK);
-        assert_eq!(pattern.color_at(Vec3::point(2, 1, 0)), WHITE);
-        assert_eq!(pattern.color_at(Vec3::point(2.3, 1.0, 0.0)), WHITE);
+        assert_eq!(pattern.color_at(Point3::new(-1, 2, 0)), BLACK);
+        assert_eq!(pattern.color_at(Point3::new(0, 0, 0)), WHITE);
+        assert_eq!(pattern.color_at(Point3::new(1, 2, 0)), BLACK);
+        assert_eq!(pattern.color_at(Point3::new(2, 1, 0)), WHITE);
+        assert_eq!(pattern.color_at(Point3::new(2.3, 1.0, 0.0)), WHITE);
     }
     #[test]
     fn stripes_with_pattern_transformation() {
         let colors = vec![WHITE, BLACK];
         let mut pattern = Pattern::stripe(colors);
         pattern.transform = Matrix::scaling(2.0, 2.0, 2.0);
-        let color = pattern.pattern_at(Vec3::point(1.5, 0.0, 0.0), &Matrix::identity_matrix(4));
+        let color = pattern.pattern_at(Point3::new(1.5, 0.0, 0.0), &Matrix::identity_matrix(4));
         assert_eq!(color, WHITE);
     }
     #[test]
     fn stripes_with_obj_transformation() {
         let colors = vec![WHITE, BLACK];
         let pattern = Pattern::stripe(colors);
-        let color = pattern.pattern_at(Vec3::point(1.5, 0.0, 0.0), &Matrix::scaling(2.0, 2.0, 2.0));
+        let color = pattern.pattern_at(Point3::new(1.5, 0.0, 0.0), &Matrix::scaling(2.0, 2.0, 2.0));
         assert_eq!(color, WHITE);
     }
     #[test]
@@ -151,46 +173,62 @@ pub mod tests {
         let colors = vec![WHITE, BLACK];
         let mut pattern = Pattern::stripe(colors);
         pattern.transform = Matrix::translation(0.5, 0.0, 0.0);
-        let color = pattern.pattern_at(Vec3::point(2.5, 0.0, 0.0), &Matrix::scaling(2.0, 2.0, 2.0));
+        let color = pattern.pattern_at(Point3::new(2.5, 0.0, 0.0), &Matrix::scaling(2.0, 2.0, 2.0));
         assert_eq!(color, WHITE);
     }
     #[test]
     fn gradient_pattern() {
         let pattern = Pattern::gradient(vec![WHITE, BLACK]);
-        assert_eq!(pattern.color_at(Vec3::point(0, 0, 0)), WHITE);
+        assert_eq!(pattern.color_at(Point3::new(0, 0, 0)), WHITE);
         assert_eq!(
-            pattern.color_at(Vec3::point(0.25, 0.0, 0.0)),
+            pattern.color_at(Point3::new(0.25, 0.0, 0.0)),
             Color::new(0.75, 0.75, 0.75)
         );
         assert_eq!(
-            pattern.color_at(Vec3::point(0.5, 0.0, 0.0)),
+            pattern.color_at(Point3::new(0.5, 0.0, 0.0)),
             Color::new(0.5, 0.5, 0.5)
         );
         assert_eq!(
-            pattern.color_at(Vec3::point(0.75, 0.0, 0.0)),
+            pattern.color_at(Point3::new(0.75, 0.0, 0.0)),
             Color::new(0.25, 0.25, 0.25)
         );
     }
     #[test]
     fn ring_pattern() {
         let pattern = Pattern::ring(vec![WHITE, BLACK]);
-        assert_eq!(pattern.color_at(Vec3::point(0, 0, 0)), WHITE);
-        assert_eq!(pattern.color_at(Vec3::point(1, 0, 0)), BLACK);
-        assert_eq!(pattern.color_at(Vec3::point(0, 0, 1)), BLACK);
-        assert_eq!(pattern.color_at(Vec3::point(0.708, 0.0, 0.708)), BLACK);
+        assert_eq!(pattern.color_at(Point3::new(0, 0, 0)), WHITE);
+        assert_eq!(pattern.color_at(Point3::new(1, 0, 0)), BLACK);
+        assert_eq!(pattern.color_at(Point3::new(0, 0, 1)), BLACK);
+        assert_eq!(pattern.color_at(Point3::new(0.708, 0.0, 0.708)), BLACK);
     }
     #[test]
     fn checkers_pattern() {
         let pattern = Pattern::checkers(vec![WHITE, BLACK]);
-        assert_eq!(pattern.color_at(Vec3::point(0, 0, 0)), WHITE);
+        assert_eq!(pattern.color_at(Point3::new(0, 0, 0)), WHITE);
         // repeat in x
-        assert_eq!(pattern.color_at(Vec3::point(0.99, 0.0, 0.0)), WHITE);
-        assert_eq!(pattern.color_at(Vec3::point(1.01, 0.0, 0.0)), BLACK);
+        assert_eq!(pattern.color_at(Point3::new(0.99, 0.0, 0.0)), WHITE);
+        assert_eq!(pattern.color_at(Point3::new(1.01, 0.0, 0.0)), BLACK);
         // repeat in y
-        assert_eq!(pattern.color_at(Vec3::point(0.0, 0.99, 0.0)), WHITE);
-        assert_eq!(pattern.color_at(Vec3::point(0.0, 1.01, 0.0)), BLACK);
+        assert_eq!(pattern.color_at(Point3::new(0.0, 0.99, 0.0)), WHITE);
+        assert_eq!(pattern.color_at(Point3::new(0.0, 1.01, 0.0)), BLACK);
         // repeat in z
-        assert_eq!(pattern.color_at(Vec3::point(0.0, 0.0, 0.99)), WHITE);
-        assert_eq!(pattern.color_at(Vec3::point(0.0, 0.0, 1.01)), BLACK);
+        assert_eq!(pattern.color_at(Point3::new(0.0, 0.0, 0.99)), WHITE);
+        assert_eq!(pattern.color_at(Point3::new(0.0, 0.0, 1.01)), BLACK);
+    }
+    #[test]
+    fn patterns_repeat_for_negative_coordinates() {
+        // casting a negative floor to usize saturates to zero, which would
+        // stretch the first color over the whole negative half space
+        let red = Color::new(1.0, 0.0, 0.0);
+        let pattern = Pattern::stripe(vec![WHITE, BLACK, red]);
+        assert_eq!(pattern.color_at(Point3::new(-0.5, 0.0, 0.0)), red);
+        assert_eq!(pattern.color_at(Point3::new(-1.5, 0.0, 0.0)), BLACK);
+        assert_eq!(pattern.color_at(Point3::new(-2.5, 0.0, 0.0)), WHITE);
+        assert_eq!(pattern.color_at(Point3::new(-3.5, 0.0, 0.0)), red);
+        let checkers = Pattern::checkers(vec![WHITE, BLACK]);
+        assert_eq!(checkers.color_at(Point3::new(-0.5, 0.0, 0.0)), BLACK);
+        assert_eq!(checkers.color_at(Point3::new(-1.5, 0.0, 0.0)), WHITE);
+        assert_eq!(checkers.color_at(Point3::new(-0.5, -0.5, 0.0)), WHITE);
+        assert_eq!(checkers.color_at(Point3::new(-0.5, -0.5, -0.5)), BLACK);
     }
 }
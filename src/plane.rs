@@ -1,42 +1,63 @@
 use super::color::Color;
+use super::encode::*;
+use super::error::*;
 use super::float_cmp;
+use super::float_cmp::Float;
 use super::intersection::*;
 use super::light::Light;
 use super::material::Material;
 use super::matrix::Matrix;
+use super::motion::Motion;
 use super::object::*;
 use super::ray::*;
-use super::vec3::Vec3;
+use super::simd::LANES;
+use super::vec3::{Point3, Vector3};
+use std::io;
 
 #[derive(Debug)]
 pub struct Plane {
     id: usize,
-    pub transform: Matrix,
+    transform: Matrix,
     pub material: Material,
+    pub flags: ObjectFlags,
+    // moves the object while the camera shutter is open, see `Motion`
+    pub motion: Option<Motion>,
+    // inverse of `transform`, see `transform_inverse`
+    inverse: Matrix,
 }
 
 impl Object for Plane {
-    fn intersection(&self, actual_ray: &Ray) -> Intersections {
-        let ray = actual_ray.transform(&self.transform.inverse_matrix());
-        let mut intersections = Intersections::new();
+    fn intersect_into(&self, actual_ray: &Ray, intersections: &mut Intersections) {
+        let ray = self.object_ray(actual_ray);
         if !float_cmp::equal(ray.direction.y, 0.0) {
             let t = -ray.origin.y / ray.direction.y;
             intersections.push(Intersection { t, obj_id: self.id });
         }
-        intersections
     }
-    fn normal_at(&self, _world_point: Vec3) -> Vec3 {
-        let object_normal = Vec3::vector(0, 1, 0);
-        let mut world_normal =
-            (self.transform.inverse_matrix().transpose() * object_normal.as_vec()).get_tuple();
-        world_normal[3] = 0.0;
-        Vec3::new(&world_normal).normalize()
+    fn intersect_packet_into(
+        &self,
+        actual_packet: &RayPacket,
+        intersections: &mut [Intersections; LANES],
+    ) {
+        let packet = self.object_packet(actual_packet);
+        let dy = packet.direction[1].to_array();
+        let t = (-packet.origin[1] / packet.direction[1]).to_array();
+        for (lane, xs) in intersections.iter_mut().enumerate() {
+            if !float_cmp::equal(dy[lane], 0.0) {
+                xs.push(Intersection::new(t[lane], self.id));
+            }
+        }
+    }
+    fn normal_at_time(&self, _world_point: Point3, time: Float) -> Vector3 {
+        let inverse = self.inverse_transform_at(time);
+        let object_normal = Vector3::new(0, 1, 0);
+        (inverse.transpose() * object_normal).normalize()
     }
     fn lighting_at(
         &self,
-        point: Vec3,
-        eye_v: Vec3,
-        normal_v: Vec3,
+        point: Point3,
+        eye_v: Vector3,
+        normal_v: Vector3,
         light: Light,
         in_shadow: bool,
     ) -> Color {
@@ -50,6 +71,33 @@ impl Object for Plane {
     fn material(&self) -> &Material {
         &self.material
     }
+    fn transform(&self) -> &Matrix {
+        &self.transform
+    }
+    fn inverse_transform(&self) -> &Matrix {
+        &self.inverse
+    }
+    fn try_set_transform(&mut self, transform: Matrix) -> Result<()> {
+        self.inverse = transform_inverse(&transform)?;
+        self.transform = transform;
+        Ok(())
+    }
+    fn motion(&self) -> Option<&Motion> {
+        self.motion.as_ref()
+    }
+    fn mut_motion(&mut self) -> &mut Option<Motion> {
+        &mut self.motion
+    }
+    fn flags(&self) -> &ObjectFlags {
+        &self.flags
+    }
+    fn mut_flags(&mut self) -> &mut ObjectFlags {
+        &mut self.flags
+    }
+    fn encode_tagged(&self, writer: &mut dyn io::Write) -> io::Result<()> {
+        PLANE_TAG.encode(writer)?;
+        self.encode(writer)
+    }
 }
 
 impl Plane {
@@ -59,6 +107,9 @@ impl Plane {
             id,
             transform: Matrix::identity_matrix(4),
             material: Material::default(),
+            flags: ObjectFlags::default(),
+            motion: None,
+            inverse: Matrix::identity_matrix(4),
         }
     }
     pub fn get_id(&self) -> usize {
@@ -73,16 +124,35 @@ impl Default for Plane {
 }
 
 #[cfg(test)]
-pub mod tests {
+mod tests {
     use super::*;
     #[test]
     fn plane_normal() {
         let plane = Plane::new();
-        let n1 = plane.normal_at(Vec3::point(0, 0, 0));
-        let n2 = plane.normal_at(Vec3::point(10, 0, -10));
-        let n3 = plane.normal_at(Vec3::point(-5, 0, 150));
-        assert_eq!(n1, Vec3::vector(0, 1, 0));
-        assert_eq!(n2, Vec3::vector(0, 1, 0));
-        assert_eq!(n3, Vec3::vector(0, 1, 0));
+        let n1 = plane.normal_at(Point3::new(0, 0, 0));
+        let n2 = plane.normal_at(Point3::new(10, 0, -10));
+        let n3 = plane.normal_at(Point3::new(-5, 0, 150));
+        assert_eq!(n1, Vector3::new(0, 1, 0));
+        assert_eq!(n2, Vector3::new(0, 1, 0));
+        assert_eq!(n3, Vector3::new(0, 1, 0));
+    }
+    #[test]
+    fn packet_intersections_match_single_rays() {
+        let mut plane = Plane::new();
+        plane.set_transform(Matrix::rotation_x(0.3).translate(0.0, -1.0, 0.0));
+        let rays = [
+            Ray::new(Point3::new(0, 1, 0), Vector3::new(0, -1, 0)),
+            Ray::new(Point3::new(0, -3, 0), Vector3::new(0.0, 0.6, 0.8)),
+            Ray::new(Point3::new(0, 1, 0), Vector3::new(1, 0, 0)),
+            Ray::new(Point3::new(2, 5, -1), Vector3::new(0, 1, 0)),
+        ];
+        let packet = plane.intersect_packet(&RayPacket::new(&rays));
+        for (ray, xs) in rays.iter().zip(packet.iter()) {
+            let expected = plane.intersection(ray);
+            assert_eq!(xs.len(), expected.len());
+            for (x, e) in xs.crossings.iter().zip(&expected.crossings) {
+                assert_eq!((x.t, x.obj_id), (e.t, e.obj_id));
+            }
+        }
     }
 }
@@ -0,0 +1,379 @@
+use super::float_cmp;
+use super::float_cmp::Float;
+use super::matrix::Matrix;
+use super::vec3::Vector3;
+use std::ops::{Mul, Neg};
+
+// Rotation as a unit quaternion w + xi + yj + zk. Unlike the angles of
+// `Matrix::rotation_x/y/z` it has no gimbal lock and blends smoothly, see `slerp`.
+#[derive(Debug, Copy, Clone)]
+pub struct Quaternion {
+    pub w: Float,
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
+}
+
+impl Quaternion {
+    pub fn new(w: Float, x: Float, y: Float, z: Float) -> Self {
+        Self { w, x, y, z }
+    }
+    pub fn identity() -> Self {
+        Self::new(1.0, 0.0, 0.0, 0.0)
+    }
+    // rotation by `rads` around `axis`, counterclockwise looking down the axis
+    // like `Matrix::rotation_x/y/z`
+    pub fn from_axis_angle(axis: Vector3, rads: Float) -> Self {
+        let axis = axis.normalize();
+        let (sin, cos) = (rads / 2.0).sin_cos();
+        Self::new(cos, axis.x * sin, axis.y * sin, axis.z * sin)
+    }
+    // axis and angle in 0..=2π of the rotation, the x axis for no rotation
+    pub fn to_axis_angle(&self) -> (Vector3, Float) {
+        let q = self.normalize();
+        let sin = (1.0 - q.w * q.w).max(0.0).sqrt();
+        if sin < float_cmp::EPSILON {
+            return (Vector3::new(1, 0, 0), 0.0);
+        }
+        (
+            Vector3::new(q.x / sin, q.y / sin, q.z / sin),
+            2.0 * q.w.clamp(-1.0, 1.0).acos(),
+        )
+    }
+    pub fn dot(&self, other: &Self) -> Float {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+    pub fn magnitude(&self) -> Float {
+        self.dot(self).sqrt()
+    }
+    pub fn normalize(&self) -> Self {
+        let m = self.magnitude();
+        Self::new(self.w / m, self.x / m, self.y / m, self.z / m)
+    }
+    // inverse rotation of a unit quaternion
+    pub fn conjugate(&self) -> Self {
+        Self::new(self.w, -self.x, -self.y, -self.z)
+    }
+    pub fn rotate(&self, v: Vector3) -> Vector3 {
+        let p = Self::new(0.0, v.x, v.y, v.z);
+        let r = *self * p * self.conjugate();
+        Vector3::new(r.x, r.y, r.z)
+    }
+    // Rotation from `self` at 0 to `other` at 1 along the shorter way round at
+    // constant angular speed.
+    pub fn slerp(&self, other: &Self, t: Float) -> Self {
+        let mut other = *other;
+        let mut cos = self.dot(&other);
+        // q and -q are the same rotation
+        if cos < 0.0 {
+            other = -other;
+            cos = -cos;
+        }
+        let (a, b) = if cos > 0.9995 {
+            // nearly equal, blending linearly avoids dividing by sin ~ 0
+            (1.0 - t, t)
+        } else {
+            let angle = cos.acos();
+            let sin = angle.sin();
+            (((1.0 - t) * angle).sin() / sin, (t * angle).sin() / sin)
+        };
+        Self::new(
+            self.w * a + other.w * b,
+            self.x * a + other.x * b,
+            self.y * a + other.y * b,
+            self.z * a + other.z * b,
+        )
+        .normalize()
+    }
+    // 4x4 rotation matrix
+    pub fn to_matrix(&self) -> Matrix {
+        let Self { w, x, y, z } = self.normalize();
+        Matrix::new(vec![
+            vec![
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            vec![
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            vec![
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            vec![0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+    // Rotation of the upper 3x3 part of `m`, which has to be a pure rotation,
+    // see `Matrix::decompose` for matrices that also scale.
+    pub fn from_matrix(m: &Matrix) -> Self {
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        // pivot on the largest component to stay away from dividing by ~0
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Self::new(
+                s / 4.0,
+                (m[2][1] - m[1][2]) / s,
+                (m[0][2] - m[2][0]) / s,
+                (m[1][0] - m[0][1]) / s,
+            )
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt() * 2.0;
+            Self::new(
+                (m[2][1] - m[1][2]) / s,
+                s / 4.0,
+                (m[0][1] + m[1][0]) / s,
+                (m[0][2] + m[2][0]) / s,
+            )
+        } else if m[1][1] > m[2][2] {
+            let s = (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt() * 2.0;
+            Self::new(
+                (m[0][2] - m[2][0]) / s,
+                (m[0][1] + m[1][0]) / s,
+                s / 4.0,
+                (m[1][2] + m[2][1]) / s,
+            )
+        } else {
+            let s = (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt() * 2.0;
+            Self::new(
+                (m[1][0] - m[0][1]) / s,
+                (m[0][2] + m[2][0]) / s,
+                (m[1][2] + m[2][1]) / s,
+                s / 4.0,
+            )
+        };
+        q.normalize()
+    }
+}
+
+// rotating by `rhs` first, then by `self`
+impl Mul for Quaternion {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self::new(
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+        )
+    }
+}
+
+impl Neg for Quaternion {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.w, -self.x, -self.y, -self.z)
+    }
+}
+
+// equal as rotations, q and -q rotate the same way
+impl PartialEq for Quaternion {
+    fn eq(&self, other: &Self) -> bool {
+        let same = |a: &Self, b: &Self| {
+            float_cmp::equal(a.w, b.w)
+                && float_cmp::equal(a.x, b.x)
+                && float_cmp::equal(a.y, b.y)
+                && float_cmp::equal(a.z, b.z)
+        };
+        same(self, other) || same(self, &-*other)
+    }
+}
+
+// Parts of an affine transform, applied as scale, then rotation, then
+// translation. Shearing has no part and is lost in `Matrix::decompose`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Decomposition {
+    pub translation: Vector3,
+    pub rotation: Quaternion,
+    pub scale: Vector3,
+}
+
+impl Decomposition {
+    pub fn to_matrix(&self) -> Matrix {
+        let t = self.translation;
+        let s = self.scale;
+        Matrix::translation(t.x, t.y, t.z)
+            * &self.rotation.to_matrix()
+            * &Matrix::scaling(s.x, s.y, s.z)
+    }
+    // blends translation and scale linearly and the rotation with `slerp`
+    pub fn interpolate(&self, other: &Self, t: Float) -> Self {
+        Self {
+            translation: self.translation + (other.translation - self.translation) * t,
+            rotation: self.rotation.slerp(&other.rotation, t),
+            scale: self.scale + (other.scale - self.scale) * t,
+        }
+    }
+}
+
+impl Matrix {
+    // Splits a 4x4 affine transform into translation, rotation and scale.
+    // None for singular matrices and projections. A mirroring transform gets
+    // a negative x scale.
+    pub fn decompose(&self) -> Option<Decomposition> {
+        if (self.n_rows, self.n_cols) != (4, 4) || self[3] != [0.0, 0.0, 0.0, 1.0][..] {
+            return None;
+        }
+        let column = |c: usize| Vector3::new(self[0][c], self[1][c], self[2][c]);
+        let mut scale = Vector3::new(
+            column(0).magnitude(),
+            column(1).magnitude(),
+            column(2).magnitude(),
+        );
+        // the determinant is at most the product of the scales, relative to it
+        // flat transforms are rejected whatever their size
+        let determinant = self.determinant();
+        if determinant.abs() <= float_cmp::EPSILON * scale.x * scale.y * scale.z {
+            return None;
+        }
+        if determinant < 0.0 {
+            scale.x = -scale.x;
+        }
+        let mut rotation = Self::identity_matrix(4);
+        for (c, s) in [scale.x, scale.y, scale.z].iter().enumerate() {
+            for r in 0..3 {
+                rotation.cells[r][c] = self[r][c] / s;
+            }
+        }
+        Some(Decomposition {
+            translation: column(3),
+            rotation: Quaternion::from_matrix(&rotation),
+            scale,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::float_cmp::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+    use super::*;
+
+    #[test]
+    fn axis_angle_matches_rotation_matrices() {
+        for angle in &[FRAC_PI_4, FRAC_PI_2, 2.0, -1.0] {
+            let angle = *angle;
+            assert_eq!(
+                Quaternion::from_axis_angle(Vector3::new(1, 0, 0), angle).to_matrix(),
+                Matrix::rotation_x(angle)
+            );
+            assert_eq!(
+                Quaternion::from_axis_angle(Vector3::new(0, 1, 0), angle).to_matrix(),
+                Matrix::rotation_y(angle)
+            );
+            assert_eq!(
+                Quaternion::from_axis_angle(Vector3::new(0, 0, 2), angle).to_matrix(),
+                Matrix::rotation_z(angle)
+            );
+        }
+    }
+    #[test]
+    fn rotate_vector() {
+        let q = Quaternion::from_axis_angle(Vector3::new(0, 0, 1), FRAC_PI_2);
+        assert_eq!(q.rotate(Vector3::new(1, 0, 0)), Vector3::new(0, 1, 0));
+        assert_eq!(
+            q.conjugate().rotate(Vector3::new(0, 1, 0)),
+            Vector3::new(1, 0, 0)
+        );
+    }
+    #[test]
+    fn combine_rotations() {
+        let x = Quaternion::from_axis_angle(Vector3::new(1, 0, 0), FRAC_PI_2);
+        let y = Quaternion::from_axis_angle(Vector3::new(0, 1, 0), FRAC_PI_4);
+        assert_eq!(
+            (y * x).to_matrix(),
+            Matrix::rotation_y(FRAC_PI_4) * &Matrix::rotation_x(FRAC_PI_2)
+        );
+    }
+    #[test]
+    fn axis_angle_roundtrip() {
+        let q = Quaternion::from_axis_angle(Vector3::new(0, 3, 4), 1.2);
+        let (axis, angle) = q.to_axis_angle();
+        assert_eq!(axis, Vector3::new(0.0, 0.6, 0.8));
+        assert!(float_cmp::equal(angle, 1.2));
+        assert_eq!(Quaternion::identity().to_axis_angle().1, 0.0);
+    }
+    #[test]
+    fn matrix_roundtrip() {
+        let rotations = [
+            Matrix::identity_matrix(4),
+            Matrix::rotation_x(PI),
+            Matrix::rotation_y(PI),
+            Matrix::rotation_z(PI),
+            Matrix::rotation_x(0.3) * &Matrix::rotation_y(-2.5) * &Matrix::rotation_z(1.7),
+        ];
+        for m in &rotations {
+            assert_eq!(Quaternion::from_matrix(m).to_matrix(), *m);
+        }
+    }
+    #[test]
+    fn slerp_between_rotations() {
+        let a = Quaternion::identity();
+        let b = Quaternion::from_axis_angle(Vector3::new(0, 1, 0), FRAC_PI_2);
+        assert_eq!(a.slerp(&b, 0.0), a);
+        assert_eq!(a.slerp(&b, 1.0), b);
+        assert_eq!(
+            a.slerp(&b, 0.5),
+            Quaternion::from_axis_angle(Vector3::new(0, 1, 0), FRAC_PI_4)
+        );
+        // -b is the same rotation, the blend still takes the short way
+        assert_eq!(
+            a.slerp(&-b, 0.5),
+            Quaternion::from_axis_angle(Vector3::new(0, 1, 0), FRAC_PI_4)
+        );
+    }
+    #[test]
+    fn decompose_affine_transform() {
+        let rotation = Quaternion::from_axis_angle(Vector3::new(1, 1, 0), 0.7);
+        let m = Matrix::translation(1.0, -2.0, 3.0)
+            * &rotation.to_matrix()
+            * &Matrix::scaling(2.0, 0.5, 3.0);
+        let parts = m.decompose().unwrap();
+        assert_eq!(parts.translation, Vector3::new(1, -2, 3));
+        assert_eq!(parts.rotation, rotation);
+        assert_eq!(parts.scale, Vector3::new(2.0, 0.5, 3.0));
+        assert_eq!(parts.to_matrix(), m);
+    }
+    #[test]
+    fn decompose_mirroring_transform() {
+        let m = Matrix::rotation_z(1.0) * &Matrix::scaling(1.0, 1.0, -1.0);
+        let parts = m.decompose().unwrap();
+        assert!(parts.scale.x < 0.0);
+        assert_eq!(parts.to_matrix(), m);
+    }
+    #[test]
+    fn singular_matrices_do_not_decompose() {
+        assert!(Matrix::scaling(1.0, 0.0, 1.0).decompose().is_none());
+        assert!(Matrix::identity_matrix(3).decompose().is_none());
+        // long columns in a common plane
+        let flat =
+            Matrix::scaling(100.0, 100.0, 0.0) * &Matrix::shearing(0.0, 1.0, 0.0, 1.0, 0.0, 0.0);
+        assert!(flat.decompose().is_none());
+    }
+    #[test]
+    fn decompose_small_scales() {
+        // determinant of 6.4e-5, well within the absolute epsilon of zero
+        let m = Matrix::translation(1.0, 0.0, 0.0) * &Matrix::scaling(0.04, 0.04, 0.04);
+        let parts = m.decompose().unwrap();
+        assert_eq!(parts.scale, Vector3::new(0.04, 0.04, 0.04));
+        assert_eq!(parts.to_matrix(), m);
+    }
+    #[test]
+    fn interpolated_rotation_keeps_the_scale() {
+        let a = Matrix::scaling(2.0, 2.0, 2.0).decompose().unwrap();
+        let b = (Matrix::rotation_y(FRAC_PI_2) * &Matrix::scaling(2.0, 2.0, 2.0))
+            .decompose()
+            .unwrap();
+        assert_eq!(
+            a.interpolate(&b, 0.5).to_matrix(),
+            Matrix::rotation_y(FRAC_PI_4) * &Matrix::scaling(2.0, 2.0, 2.0)
+        );
+    }
+}
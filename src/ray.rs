@@ -1,27 +1,32 @@
+use super::float_cmp::Float;
 use super::intersection::*;
 use super::matrix::Matrix;
 use super::object::*;
-use super::vec3::Vec3;
+use super::simd::{Float4, LANES};
+use super::vec3::{Point3, Vector3};
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Ray {
-    pub origin: Vec3,
-    pub direction: Vec3,
+    pub origin: Point3,
+    pub direction: Vector3,
+    // moment within the camera shutter interval the ray travels at,
+    // moving objects are intersected where they are at that time
+    pub time: Float,
 }
 
 impl Ray {
-    pub fn new(origin: Vec3, direction: Vec3) -> Self {
-        assert!(
-            origin.is_point(),
-            "`origin` is not a point, failed to create Ray"
-        );
-        assert!(
-            direction.is_vector(),
-            "`direction` is not a vector, failed to create Ray"
-        );
-        Self { origin, direction }
+    pub fn new(origin: Point3, direction: Vector3) -> Self {
+        Self {
+            origin,
+            direction,
+            time: 0.0,
+        }
+    }
+    pub fn with_time(mut self, time: Float) -> Self {
+        self.time = time;
+        self
     }
-    pub fn position(&self, t: f32) -> Vec3 {
+    pub fn position(&self, t: Float) -> Point3 {
         self.origin + self.direction * t
     }
     pub fn intersect<T: Object>(&self, obj: &T) -> Intersections {
@@ -31,41 +36,118 @@ impl Ray {
         Self {
             origin: transform * self.origin,
             direction: transform * self.direction,
+            time: self.time,
+        }
+    }
+}
+
+// Rays traced together, lane i of every component belongs to the i-th ray.
+// The rays share their time, moving objects are only placed once per packet.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RayPacket {
+    pub origin: [Float4; 3],
+    pub direction: [Float4; 3],
+    pub time: Float,
+}
+
+impl RayPacket {
+    pub fn new(rays: &[Ray; LANES]) -> Self {
+        let time = rays[0].time;
+        assert!(
+            rays.iter().all(|ray| ray.time == time),
+            "rays of a packet have to share their time"
+        );
+        let lanes = |component: fn(&Ray) -> Float| Float4::new(rays.each_ref().map(component));
+        Self {
+            origin: [
+                lanes(|ray| ray.origin.x),
+                lanes(|ray| ray.origin.y),
+                lanes(|ray| ray.origin.z),
+            ],
+            direction: [
+                lanes(|ray| ray.direction.x),
+                lanes(|ray| ray.direction.y),
+                lanes(|ray| ray.direction.z),
+            ],
+            time,
+        }
+    }
+    pub fn ray(&self, lane: usize) -> Ray {
+        let [ox, oy, oz] = self.origin.map(|c| c.to_array()[lane]);
+        let [dx, dy, dz] = self.direction.map(|c| c.to_array()[lane]);
+        Ray::new(Point3::new(ox, oy, oz), Vector3::new(dx, dy, dz)).with_time(self.time)
+    }
+    // same as transforming every ray on its own
+    pub fn transform(&self, transform: &Matrix) -> Self {
+        let m = &transform.cells;
+        let apply = |v: &[Float4; 3], w: Float| {
+            let row = |i: usize| {
+                v[0] * m[i][0] + v[1] * m[i][1] + v[2] * m[i][2] + Float4::splat(m[i][3] * w)
+            };
+            [row(0), row(1), row(2)]
+        };
+        Self {
+            origin: apply(&self.origin, 1.0),
+            direction: apply(&self.direction, 0.0),
+            time: self.time,
         }
     }
 }
 
 #[cfg(test)]
-pub mod tests {
+mod tests {
     use super::*;
     #[test]
     fn create_ray() {
-        let origin = Vec3::point(0, 0, 0);
-        let direction = Vec3::vector(1, 2, 3);
+        let origin = Point3::new(0, 0, 0);
+        let direction = Vector3::new(1, 2, 3);
         let ray = Ray::new(origin, direction);
         assert_eq!(ray.origin, origin);
         assert_eq!(ray.direction, direction);
     }
     #[test]
     fn position_of_ray() {
-        let r = Ray::new(Vec3::point(2, 3, 4), Vec3::vector(1, 0, 0));
-        assert_eq!(r.position(0.0), Vec3::point(2, 3, 4));
-        assert_eq!(r.position(1.0), Vec3::point(3, 3, 4));
-        assert_eq!(r.position(-1.0), Vec3::point(1, 3, 4));
-        assert_eq!(r.position(2.5), Vec3::point(4.5, 3.0, 4.0));
+        let r = Ray::new(Point3::new(2, 3, 4), Vector3::new(1, 0, 0));
+        assert_eq!(r.position(0.0), Point3::new(2, 3, 4));
+        assert_eq!(r.position(1.0), Point3::new(3, 3, 4));
+        assert_eq!(r.position(-1.0), Point3::new(1, 3, 4));
+        assert_eq!(r.position(2.5), Point3::new(4.5, 3.0, 4.0));
     }
     #[test]
     fn translating_ray() {
-        let r1 = Ray::new(Vec3::point(1, 2, 3), Vec3::vector(0, 1, 0));
+        let r1 = Ray::new(Point3::new(1, 2, 3), Vector3::new(0, 1, 0));
         let r2 = r1.transform(&Matrix::translation(3.0, 4.0, 5.0));
-        assert_eq!(r2.origin, Vec3::point(4, 6, 8));
-        assert_eq!(r2.direction, Vec3::vector(0, 1, 0));
+        assert_eq!(r2.origin, Point3::new(4, 6, 8));
+        assert_eq!(r2.direction, Vector3::new(0, 1, 0));
     }
     #[test]
     fn scaling_ray() {
-        let r1 = Ray::new(Vec3::point(1, 2, 3), Vec3::vector(0, 1, 0));
+        let r1 = Ray::new(Point3::new(1, 2, 3), Vector3::new(0, 1, 0));
         let r2 = r1.transform(&Matrix::scaling(2.0, 3.0, 4.0));
-        assert_eq!(r2.origin, Vec3::point(2, 6, 12));
-        assert_eq!(r2.direction, Vec3::vector(0, 3, 0));
+        assert_eq!(r2.origin, Point3::new(2, 6, 12));
+        assert_eq!(r2.direction, Vector3::new(0, 3, 0));
+    }
+    #[test]
+    fn transformed_ray_keeps_its_time() {
+        let r1 = Ray::new(Point3::new(1, 2, 3), Vector3::new(0, 1, 0)).with_time(0.25);
+        let r2 = r1.transform(&Matrix::translation(3.0, 4.0, 5.0));
+        assert_eq!(r2.time, 0.25);
+    }
+    #[test]
+    fn packet_of_rays() {
+        let rays = [
+            Ray::new(Point3::new(0, 0, -5), Vector3::new(0, 0, 1)),
+            Ray::new(Point3::new(1, 2, 3), Vector3::new(0, 1, 0)),
+            Ray::new(Point3::new(-1, 0, 0), Vector3::new(1, 1, 0)),
+            Ray::new(Point3::new(0.5, -2.0, 4.0), Vector3::new(0.0, 0.6, 0.8)),
+        ]
+        .map(|ray| ray.with_time(0.5));
+        let packet = RayPacket::new(&rays);
+        let m = Matrix::translation(3.0, 4.0, 5.0).scale(2.0, 3.0, 4.0);
+        let transformed = packet.transform(&m);
+        for (lane, ray) in rays.iter().enumerate() {
+            assert_eq!(packet.ray(lane), *ray);
+            assert_eq!(transformed.ray(lane), ray.transform(&m));
+        }
     }
 }
@@ -0,0 +1,165 @@
+use super::float_cmp::consts::PI;
+use super::float_cmp::Float;
+use super::vec3::Vector3;
+
+// Small xorshift generator, seeded per pixel and sample so that renders are
+// reproducible regardless of thread scheduling, tile order or resumption.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+// finalizer of splitmix64, spreads neighbouring seeds over the whole state space
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        let state = mix(seed.wrapping_add(0x9e37_79b9_7f4a_7c15));
+        // xorshift gets stuck on a zero state
+        Self {
+            state: if state == 0 { 1 } else { state },
+        }
+    }
+    // Seeded by several values, each is mixed in on its own so that no two
+    // lists of values share a seed by their bits overlapping.
+    pub fn from_values(values: &[u64]) -> Self {
+        let seed = values.iter().fold(0, |seed: u64, value| {
+            mix(seed.wrapping_add(0x9e37_79b9_7f4a_7c15) ^ value)
+        });
+        Self::new(seed)
+    }
+    pub fn for_pixel(col: u32, row: u32, sample: u32) -> Self {
+        Self::new((u64::from(col) << 40) ^ (u64::from(row) << 20) ^ u64::from(sample))
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+    // uniform in [0, 1)
+    pub fn next_float(&mut self) -> Float {
+        (self.next_u64() >> 40) as Float / (1u64 << 24) as Float
+    }
+}
+
+// two unit vectors perpendicular to `normal` and to each other
+pub fn orthonormal_basis(normal: Vector3) -> (Vector3, Vector3) {
+    let helper = if normal.x.abs() > 0.9 {
+        Vector3::new(0, 1, 0)
+    } else {
+        Vector3::new(1, 0, 0)
+    };
+    let tangent = normal.cross(helper).normalize();
+    let bitangent = normal.cross(tangent);
+    (tangent, bitangent)
+}
+
+// direction in the hemisphere around `normal` with pdf cos(theta) / PI
+pub fn cosine_hemisphere(normal: Vector3, rng: &mut Rng) -> Vector3 {
+    let u1 = rng.next_float();
+    let u2 = rng.next_float();
+    let r = u1.sqrt();
+    let phi = 2.0 * PI * u2;
+    let (tangent, bitangent) = orthonormal_basis(normal);
+    let direction = tangent * (r * phi.cos())
+        + bitangent * (r * phi.sin())
+        + normal * (1.0 - u1).max(0.0).sqrt();
+    direction.normalize()
+}
+
+// direction uniformly distributed within `half_angle` radians of `axis`
+pub fn uniform_cone(axis: Vector3, half_angle: Float, rng: &mut Rng) -> Vector3 {
+    let cos_theta = 1.0 - rng.next_float() * (1.0 - half_angle.cos());
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * rng.next_float();
+    let (tangent, bitangent) = orthonormal_basis(axis);
+    (tangent * (sin_theta * phi.cos()) + bitangent * (sin_theta * phi.sin()) + axis * cos_theta)
+        .normalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::float_cmp;
+    use super::*;
+    #[test]
+    fn rng_is_deterministic() {
+        let mut a = Rng::for_pixel(3, 4, 5);
+        let mut b = Rng::for_pixel(3, 4, 5);
+        let mut c = Rng::for_pixel(4, 3, 5);
+        let xs: Vec<u64> = (0..8).map(|_| a.next_u64()).collect();
+        let ys: Vec<u64> = (0..8).map(|_| b.next_u64()).collect();
+        let zs: Vec<u64> = (0..8).map(|_| c.next_u64()).collect();
+        assert_eq!(xs, ys);
+        assert_ne!(xs, zs);
+    }
+    #[test]
+    fn every_value_changes_the_seed() {
+        let first = |values: &[u64]| Rng::from_values(values).next_u64();
+        assert_ne!(first(&[1, 0]), first(&[0, 1]));
+        // overlapping shifted values used to cancel out
+        assert_ne!(first(&[1 << 16, 1, 0]), first(&[0, 0, 1 << 16]));
+        assert_eq!(first(&[3, 4, 5]), first(&[3, 4, 5]));
+    }
+    #[test]
+    fn rng_is_uniform() {
+        let mut rng = Rng::new(42);
+        let n = 10000;
+        let mut sum = 0.0;
+        for _ in 0..n {
+            let x = rng.next_float();
+            assert!((0.0..1.0).contains(&x));
+            sum += x;
+        }
+        assert!((sum / n as Float - 0.5).abs() < 0.01);
+    }
+    #[test]
+    fn basis_is_orthonormal() {
+        for normal in &[
+            Vector3::new(1, 0, 0),
+            Vector3::new(0, 1, 0),
+            Vector3::new(1, 2, 3).normalize(),
+        ] {
+            let (t, b) = orthonormal_basis(*normal);
+            assert!(float_cmp::equal(t.magnitude(), 1.0));
+            assert!(float_cmp::equal(b.magnitude(), 1.0));
+            assert!(float_cmp::equal(t.dot(*normal), 0.0));
+            assert!(float_cmp::equal(b.dot(*normal), 0.0));
+            assert!(float_cmp::equal(t.dot(b), 0.0));
+        }
+    }
+    #[test]
+    fn cosine_weighted_samples() {
+        let normal = Vector3::new(0, 0, -1);
+        let mut rng = Rng::new(7);
+        let n = 10000;
+        let mut sum = 0.0;
+        for _ in 0..n {
+            let cos = cosine_hemisphere(normal, &mut rng).dot(normal);
+            assert!(cos >= 0.0);
+            sum += cos;
+        }
+        // E[cos] = 2/3 for a cosine weighted hemisphere
+        assert!((sum / n as Float - 2.0 / 3.0).abs() < 0.01);
+    }
+    #[test]
+    fn cone_samples_stay_inside_cone() {
+        let axis = Vector3::new(1, 1, 0).normalize();
+        let half_angle = 0.3;
+        let mut rng = Rng::new(2);
+        let n = 10000;
+        let mut sum = 0.0;
+        for _ in 0..n {
+            let cos = uniform_cone(axis, half_angle, &mut rng).dot(axis);
+            assert!(cos >= half_angle.cos() - 1.0e-5);
+            sum += cos;
+        }
+        // cos(theta) is uniform between cos(half_angle) and 1
+        assert!((sum / n as Float - (1.0 + half_angle.cos()) / 2.0).abs() < 0.001);
+        assert_eq!(uniform_cone(axis, 0.0, &mut rng), axis);
+    }
+}
@@ -0,0 +1,221 @@
+use super::float_cmp::Float;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+#[cfg(all(target_arch = "x86_64", not(feature = "f64")))]
+use std::arch::x86_64::*;
+
+// number of values processed together, rays in a `RayPacket`
+pub const LANES: usize = 4;
+
+// Four floats operated on at once. Uses SSE on x86_64, which every x86_64
+// cpu has so the intrinsics are always safe to call, and plain arrays
+// everywhere else and for the `f64` feature.
+#[cfg(all(target_arch = "x86_64", not(feature = "f64")))]
+#[derive(Debug, Copy, Clone)]
+pub struct Float4(__m128);
+
+#[cfg(not(all(target_arch = "x86_64", not(feature = "f64"))))]
+#[derive(Debug, Copy, Clone)]
+pub struct Float4([Float; LANES]);
+
+#[cfg(all(target_arch = "x86_64", not(feature = "f64")))]
+impl Float4 {
+    pub fn new(values: [Float; LANES]) -> Self {
+        unsafe { Self(_mm_loadu_ps(values.as_ptr())) }
+    }
+    pub fn splat(value: Float) -> Self {
+        unsafe { Self(_mm_set1_ps(value)) }
+    }
+    pub fn to_array(self) -> [Float; LANES] {
+        let mut values = [0.0; LANES];
+        unsafe { _mm_storeu_ps(values.as_mut_ptr(), self.0) };
+        values
+    }
+    pub fn sqrt(self) -> Self {
+        unsafe { Self(_mm_sqrt_ps(self.0)) }
+    }
+    pub fn max(self, other: Self) -> Self {
+        unsafe { Self(_mm_max_ps(self.0, other.0)) }
+    }
+    // lanes rotated to y, z, x, w, used for cross products
+    pub fn yzxw(self) -> Self {
+        unsafe { Self(_mm_shuffle_ps::<0b11_00_10_01>(self.0, self.0)) }
+    }
+    // sum of the lanes as (x + z) + (y + w), without leaving the register
+    pub fn sum(self) -> Float {
+        unsafe {
+            let pairs = _mm_add_ps(self.0, _mm_movehl_ps(self.0, self.0));
+            _mm_cvtss_f32(_mm_add_ss(pairs, _mm_shuffle_ps::<0b01>(pairs, pairs)))
+        }
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", not(feature = "f64")))]
+impl Add for Float4 {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        unsafe { Self(_mm_add_ps(self.0, other.0)) }
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", not(feature = "f64")))]
+impl Sub for Float4 {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        unsafe { Self(_mm_sub_ps(self.0, other.0)) }
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", not(feature = "f64")))]
+impl Mul for Float4 {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        unsafe { Self(_mm_mul_ps(self.0, other.0)) }
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", not(feature = "f64")))]
+impl Div for Float4 {
+    type Output = Self;
+
+    fn div(self, other: Self) -> Self {
+        unsafe { Self(_mm_div_ps(self.0, other.0)) }
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", not(feature = "f64")))]
+impl Neg for Float4 {
+    type Output = Self;
+
+    // flips the sign bits like scalar negation, also of zeros
+    fn neg(self) -> Self {
+        unsafe { Self(_mm_xor_ps(self.0, _mm_set1_ps(-0.0))) }
+    }
+}
+
+// scalar fallback
+
+#[cfg(not(all(target_arch = "x86_64", not(feature = "f64"))))]
+impl Float4 {
+    pub fn new(values: [Float; LANES]) -> Self {
+        Self(values)
+    }
+    pub fn splat(value: Float) -> Self {
+        Self([value; LANES])
+    }
+    pub fn to_array(self) -> [Float; LANES] {
+        self.0
+    }
+    pub fn sqrt(self) -> Self {
+        self.map(|a| a.sqrt())
+    }
+    pub fn max(self, other: Self) -> Self {
+        self.zip(other, Float::max)
+    }
+    // lanes rotated to y, z, x, w, used for cross products
+    pub fn yzxw(self) -> Self {
+        let [x, y, z, w] = self.0;
+        Self([y, z, x, w])
+    }
+    // sum of the lanes as (x + z) + (y + w), the same as the SIMD version
+    pub fn sum(self) -> Float {
+        let [x, y, z, w] = self.0;
+        (x + z) + (y + w)
+    }
+    fn map(self, f: impl Fn(Float) -> Float) -> Self {
+        Self(self.0.map(f))
+    }
+    fn zip(self, other: Self, f: impl Fn(Float, Float) -> Float) -> Self {
+        let mut values = self.0;
+        for (value, other) in values.iter_mut().zip(other.0) {
+            *value = f(*value, other);
+        }
+        Self(values)
+    }
+}
+
+#[cfg(not(all(target_arch = "x86_64", not(feature = "f64"))))]
+impl Add for Float4 {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        self.zip(other, |a, b| a + b)
+    }
+}
+
+#[cfg(not(all(target_arch = "x86_64", not(feature = "f64"))))]
+impl Sub for Float4 {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self.zip(other, |a, b| a - b)
+    }
+}
+
+#[cfg(not(all(target_arch = "x86_64", not(feature = "f64"))))]
+impl Mul for Float4 {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        self.zip(other, |a, b| a * b)
+    }
+}
+
+#[cfg(not(all(target_arch = "x86_64", not(feature = "f64"))))]
+impl Div for Float4 {
+    type Output = Self;
+
+    fn div(self, other: Self) -> Self {
+        self.zip(other, |a, b| a / b)
+    }
+}
+
+#[cfg(not(all(target_arch = "x86_64", not(feature = "f64"))))]
+impl Neg for Float4 {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        self.map(|a| -a)
+    }
+}
+
+impl Mul<Float> for Float4 {
+    type Output = Self;
+
+    fn mul(self, rhs: Float) -> Self {
+        self * Self::splat(rhs)
+    }
+}
+
+impl PartialEq for Float4 {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_array() == other.to_array()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lane_wise_arithmetic() {
+        let a = Float4::new([1.0, 2.0, 3.0, 4.0]);
+        let b = Float4::new([4.0, 3.0, 2.0, 1.0]);
+        assert_eq!((a + b).to_array(), [5.0; LANES]);
+        assert_eq!((a - b).to_array(), [-3.0, -1.0, 1.0, 3.0]);
+        assert_eq!((a * b).to_array(), [4.0, 6.0, 6.0, 4.0]);
+        assert_eq!((a / b).to_array(), [0.25, 2.0 / 3.0, 1.5, 4.0]);
+        assert_eq!((-a * 2.0).to_array(), [-2.0, -4.0, -6.0, -8.0]);
+        assert_eq!(a.max(b).to_array(), [4.0, 3.0, 3.0, 4.0]);
+        assert_eq!(Float4::splat(9.0).sqrt(), Float4::splat(3.0));
+    }
+    #[test]
+    fn horizontal_sum_and_shuffle() {
+        let a = Float4::new([1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(a.sum(), 10.0);
+        assert_eq!(a.yzxw().to_array(), [2.0, 3.0, 1.0, 4.0]);
+    }
+}
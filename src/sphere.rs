@@ -1,29 +1,40 @@
 use super::color::Color;
+use super::encode::*;
+use super::error::*;
+use super::float_cmp::consts::PI;
+use super::float_cmp::Float;
 use super::intersection::*;
 use super::light::Light;
 use super::material::Material;
 use super::matrix::Matrix;
+use super::motion::Motion;
 use super::object::*;
 use super::ray::*;
-use super::vec3::Vec3;
+use super::sampling::Rng;
+use super::simd::{Float4, LANES};
+use super::vec3::{Point3, Vector3};
+use std::io;
 
 #[derive(Debug)]
 pub struct Sphere {
     id: usize,
-    pub transform: Matrix,
+    transform: Matrix,
     pub material: Material,
+    pub flags: ObjectFlags,
+    // moves the object while the camera shutter is open, see `Motion`
+    pub motion: Option<Motion>,
+    // inverse of `transform`, see `transform_inverse`
+    inverse: Matrix,
 }
 
 impl Object for Sphere {
-    fn intersection(&self, actual_ray: &Ray) -> Intersections {
-        let ray = actual_ray.transform(&self.transform.inverse_matrix());
-        let sphere_to_ray = ray.origin - Vec3::point(0, 0, 0);
+    fn intersect_into(&self, actual_ray: &Ray, intersections: &mut Intersections) {
+        let ray = self.object_ray(actual_ray);
+        let sphere_to_ray = ray.origin - Point3::new(0, 0, 0);
         let a = ray.direction.magnitude_square();
         let b = 2.0 * ray.direction.dot(sphere_to_ray);
         let c = sphere_to_ray.magnitude_square() - 1.0;
         let discriminant = b * b - 4.0 * a * c;
-        let mut intersections = Intersections::new();
-
         if discriminant >= 0.0 {
             intersections.push(Intersection {
                 t: (-b - discriminant.sqrt()) / (2.0 * a),
@@ -34,21 +45,42 @@ impl Object for Sphere {
                 obj_id: self.id,
             });
         }
-        intersections
     }
-    fn normal_at(&self, world_point: Vec3) -> Vec3 {
-        let object_point = self.transform.inverse_matrix() * world_point;
-        let object_normal = object_point - Vec3::point(0, 0, 0);
-        let mut world_normal =
-            (self.transform.inverse_matrix().transpose() * object_normal.as_vec()).get_tuple();
-        world_normal[3] = 0.0;
-        Vec3::new(&world_normal).normalize()
+    fn intersect_packet_into(
+        &self,
+        actual_packet: &RayPacket,
+        intersections: &mut [Intersections; LANES],
+    ) {
+        let packet = self.object_packet(actual_packet);
+        let [ox, oy, oz] = packet.origin;
+        let [dx, dy, dz] = packet.direction;
+        // summed x, z then y, the order `Float4::sum` uses for the scalar dot
+        let a = dx * dx + dz * dz + dy * dy;
+        let b = (dx * ox + dz * oz + dy * oy) * 2.0;
+        let c = ox * ox + oz * oz + oy * oy - Float4::splat(1.0);
+        let discriminant = b * b - a * 4.0 * c;
+        let root = discriminant.max(Float4::splat(0.0)).sqrt();
+        let t1 = ((-b - root) / (a * 2.0)).to_array();
+        let t2 = ((-b + root) / (a * 2.0)).to_array();
+        let discriminant = discriminant.to_array();
+        for (lane, xs) in intersections.iter_mut().enumerate() {
+            if discriminant[lane] >= 0.0 {
+                xs.push(Intersection::new(t1[lane], self.id));
+                xs.push(Intersection::new(t2[lane], self.id));
+            }
+        }
+    }
+    fn normal_at_time(&self, world_point: Point3, time: Float) -> Vector3 {
+        let inverse = self.inverse_transform_at(time);
+        let object_point = &inverse * world_point;
+        let object_normal = object_point - Point3::new(0, 0, 0);
+        (inverse.transpose() * object_normal).normalize()
     }
     fn lighting_at(
         &self,
-        point: Vec3,
-        eye_v: Vec3,
-        normal_v: Vec3,
+        point: Point3,
+        eye_v: Vector3,
+        normal_v: Vector3,
         light: Light,
         in_shadow: bool,
     ) -> Color {
@@ -62,6 +94,52 @@ impl Object for Sphere {
     fn material(&self) -> &Material {
         &self.material
     }
+    fn transform(&self) -> &Matrix {
+        &self.transform
+    }
+    fn inverse_transform(&self) -> &Matrix {
+        &self.inverse
+    }
+    fn try_set_transform(&mut self, transform: Matrix) -> Result<()> {
+        self.inverse = transform_inverse(&transform)?;
+        self.transform = transform;
+        Ok(())
+    }
+    fn motion(&self) -> Option<&Motion> {
+        self.motion.as_ref()
+    }
+    fn mut_motion(&mut self) -> &mut Option<Motion> {
+        &mut self.motion
+    }
+    fn flags(&self) -> &ObjectFlags {
+        &self.flags
+    }
+    fn mut_flags(&mut self) -> &mut ObjectFlags {
+        &mut self.flags
+    }
+    fn has_surface_samples(&self) -> bool {
+        true
+    }
+    fn sample_surface(&self, time: Float, rng: &mut Rng) -> Option<SurfaceSample> {
+        let transform = self.transform_at(time);
+        let z = 1.0 - 2.0 * rng.next_float();
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * rng.next_float();
+        let object_normal = Vector3::new(r * phi.cos(), r * phi.sin(), z);
+        let point = &transform * (Point3::new(0, 0, 0) + object_normal);
+        // an area element of the unit sphere grows by |det(M)| * |M^-T n| in world space
+        let world_normal = transform.inverse_matrix().transpose() * object_normal;
+        let area_scale = transform.determinant().abs() * world_normal.magnitude();
+        Some(SurfaceSample {
+            point,
+            normal: world_normal.normalize(),
+            pdf: 1.0 / (4.0 * PI * area_scale),
+        })
+    }
+    fn encode_tagged(&self, writer: &mut dyn io::Write) -> io::Result<()> {
+        SPHERE_TAG.encode(writer)?;
+        self.encode(writer)
+    }
 }
 
 impl Sphere {
@@ -71,16 +149,14 @@ impl Sphere {
             id,
             transform: Matrix::identity_matrix(4),
             material: Material::default(),
+            flags: ObjectFlags::default(),
+            motion: None,
+            inverse: Matrix::identity_matrix(4),
         }
     }
     pub fn get_id(&self) -> usize {
         self.id
     }
-    pub fn set_transform(&mut self, transform: Matrix) {
-        assert_eq!(transform.n_rows, 4, "Not a transform, invalid dimensions");
-        assert_eq!(transform.n_cols, 4, "Not a transform, invalid dimensions");
-        self.transform = transform;
-    }
     pub fn set_material(&mut self, material: Material) {
         self.material = material;
     }
@@ -99,10 +175,29 @@ impl PartialEq for Sphere {
 }
 
 #[cfg(test)]
-pub mod tests {
+#[allow(
+    clippy::approx_constant,
+    clippy::field_reassign_with_default,
+    clippy::neg_multiply
+)]
+mod tests {
     use super::super::float_cmp;
+    use super::super::float_cmp::consts;
     use super::*;
     #[test]
+    fn invalid_sphere_transform() {
+        let mut s = Sphere::new();
+        assert!(s.try_set_transform(Matrix::identity_matrix(3)).is_err());
+        assert_eq!(
+            s.try_set_transform(Matrix::scaling(1.0, 0.0, 1.0)),
+            Err(Error::NotInvertible)
+        );
+        assert_eq!(*s.transform(), Matrix::identity_matrix(4));
+        assert!(s
+            .try_set_transform(Matrix::translation(1.0, 0.0, 0.0))
+            .is_ok());
+    }
+    #[test]
     fn create_spheres() {
         let s1 = Sphere::new();
         let s2 = Sphere::new();
@@ -111,65 +206,113 @@ pub mod tests {
     #[test]
     fn default_transform() {
         let s = Sphere::new();
-        assert_eq!(s.transform, Matrix::identity_matrix(4));
+        assert_eq!(*s.transform(), Matrix::identity_matrix(4));
     }
     #[test]
     fn change_transformation() {
         let mut s = Sphere::new();
         let new_transform = Matrix::translation(1.0, 2.0, 3.0);
         s.set_transform(new_transform);
-        assert_eq!(s.transform, Matrix::translation(1.0, 2.0, 3.0));
+        assert_eq!(*s.transform(), Matrix::translation(1.0, 2.0, 3.0));
     }
     #[test]
     fn normal_on_x() {
         let s = Sphere::new();
-        let n = s.normal_at(Vec3::point(1, 0, 0));
-        assert_eq!(n, Vec3::vector(1, 0, 0));
+        let n = s.normal_at(Point3::new(1, 0, 0));
+        assert_eq!(n, Vector3::new(1, 0, 0));
     }
     #[test]
     fn normal_on_y() {
         let s = Sphere::new();
-        let n = s.normal_at(Vec3::point(0, 1, 0));
-        assert_eq!(n, Vec3::vector(0, 1, 0));
+        let n = s.normal_at(Point3::new(0, 1, 0));
+        assert_eq!(n, Vector3::new(0, 1, 0));
     }
     #[test]
     fn normal_on_z() {
         let s = Sphere::new();
-        let n = s.normal_at(Vec3::point(0, 0, 1));
-        assert_eq!(n, Vec3::vector(0, 0, 1));
+        let n = s.normal_at(Point3::new(0, 0, 1));
+        assert_eq!(n, Vector3::new(0, 0, 1));
     }
     #[test]
     fn normal_on_non_axial() {
         let s = Sphere::new();
-        let c = f32::sqrt(3.0) / 3.0;
-        let n = s.normal_at(Vec3::point(c, c, c));
-        assert_eq!(n, Vec3::vector(c, c, c));
+        let c = Float::sqrt(3.0) / 3.0;
+        let n = s.normal_at(Point3::new(c, c, c));
+        assert_eq!(n, Vector3::new(c, c, c));
     }
     #[test]
     fn normal_is_normalized() {
         let s = Sphere::new();
-        let c = f32::sqrt(3.0) / 3.0;
-        let n = s.normal_at(Vec3::point(c, c, c));
+        let c = Float::sqrt(3.0) / 3.0;
+        let n = s.normal_at(Point3::new(c, c, c));
         assert_eq!(n, n.normalize());
     }
     #[test]
     fn normal_on_translated_sphere() {
         let mut s = Sphere::new();
         s.set_transform(Matrix::translation(0.0, 1.0, 0.0));
-        let n = s.normal_at(Vec3::point(0.0, 1.70711, -0.70711));
-        assert_eq!(n, Vec3::vector(0.0, 0.70711, -0.70711));
+        let n = s.normal_at(Point3::new(0.0, 1.70711, -0.70711));
+        assert_eq!(n, Vector3::new(0.0, 0.70711, -0.70711));
     }
     #[test]
     fn normal_on_transformed_sphere() {
         let mut s = Sphere::new();
-        let m = Matrix::scaling(1.0, 0.5, 1.0) * &Matrix::rotation_z(std::f32::consts::PI / 5.0);
+        let m = Matrix::scaling(1.0, 0.5, 1.0) * &Matrix::rotation_z(consts::PI / 5.0);
         s.set_transform(m);
-        let n = s.normal_at(Vec3::point(
+        let n = s.normal_at(Point3::new(
             0.0,
-            f32::sqrt(2.0) / 2.0,
-            -1.0 * f32::sqrt(2.0) / 2.0,
+            Float::sqrt(2.0) / 2.0,
+            -1.0 * Float::sqrt(2.0) / 2.0,
         ));
-        assert_eq!(n, Vec3::vector(0.0, 0.97014, -0.24254));
+        assert_eq!(n, Vector3::new(0.0, 0.97014, -0.24254));
+    }
+    #[test]
+    fn moving_sphere_is_hit_where_it_is_at_the_time_of_the_ray() {
+        let mut sphere = Sphere::new();
+        sphere.motion = Some(Motion::linear(
+            Matrix::translation(0.0, 0.0, 0.0),
+            Matrix::translation(4.0, 0.0, 0.0),
+        ));
+        let ray = Ray::new(Point3::new(2, 0, -5), Vector3::new(0, 0, 1));
+        assert!(sphere.intersection(&ray).is_empty());
+        let xs = sphere.intersection(&ray.with_time(0.5));
+        assert_eq!(xs.len(), 2);
+        assert!(float_cmp::equal(xs[0].t, 4.0));
+        assert_eq!(
+            sphere.normal_at_time(Point3::new(5, 0, 0), 1.0),
+            Vector3::new(1, 0, 0)
+        );
+    }
+    #[test]
+    fn sample_surface_of_scaled_sphere() {
+        let mut s = Sphere::new();
+        s.set_transform(Matrix::translation(1.0, 2.0, 3.0) * &Matrix::scaling(2.0, 2.0, 2.0));
+        let mut rng = Rng::new(5);
+        for _ in 0..100 {
+            let sample = s.sample_surface(0.0, &mut rng).unwrap();
+            let radius = (sample.point - Point3::new(1, 2, 3)).magnitude();
+            assert!(float_cmp::equal(radius, 2.0));
+            assert_eq!(sample.normal, s.normal_at(sample.point));
+            // uniform over the world space area of 4 * PI * 2^2
+            assert!(float_cmp::equal(sample.pdf, 1.0 / (16.0 * PI)));
+        }
+    }
+    #[test]
+    fn sample_surface_of_stretched_sphere() {
+        // Monte Carlo estimate of the area has to match the ellipsoid area
+        let mut s = Sphere::new();
+        s.set_transform(Matrix::scaling(3.0, 1.0, 1.0));
+        let mut rng = Rng::new(9);
+        let n = 20000;
+        let mut area = 0.0;
+        for _ in 0..n {
+            area += 1.0 / s.sample_surface(0.0, &mut rng).unwrap().pdf;
+        }
+        area /= n as Float;
+        // prolate spheroid with semi axes 3, 1, 1
+        let e = (1.0 - 1.0 / (9.0 as Float)).sqrt();
+        let expected = 2.0 * PI * (1.0 + 3.0 * e.asin() / e);
+        assert!((area - expected).abs() / expected < 0.01);
     }
     #[test]
     fn sphere_material() {
@@ -193,4 +336,25 @@ pub mod tests {
         assert!(float_cmp::equal(s.material.diffuse, mat_c.diffuse));
         assert!(float_cmp::equal(s.material.shininess, mat_c.shininess));
     }
+    #[test]
+    fn packet_intersections_match_single_rays() {
+        let mut sphere = Sphere::new();
+        sphere.set_transform(Matrix::scaling(2.0, 2.0, 2.0).translate(0.5, 0.0, 0.0));
+        // through the middle, tangent, missing and from the inside
+        let rays = [
+            Ray::new(Point3::new(0, 0, -5), Vector3::new(0, 0, 1)),
+            Ray::new(Point3::new(2.5, 0.0, -5.0), Vector3::new(0, 0, 1)),
+            Ray::new(Point3::new(0, 3, -5), Vector3::new(0, 0, 1)),
+            Ray::new(Point3::new(0, 0, 0), Vector3::new(0.0, 0.6, 0.8)),
+        ];
+        let packet = sphere.intersect_packet(&RayPacket::new(&rays));
+        for (ray, xs) in rays.iter().zip(packet.iter()) {
+            let expected = sphere.intersection(ray);
+            assert_eq!(xs.len(), expected.len());
+            for (x, e) in xs.crossings.iter().zip(&expected.crossings) {
+                assert_eq!((x.t, x.obj_id), (e.t, e.obj_id));
+            }
+        }
+        assert_eq!(packet[2].len(), 0);
+    }
 }
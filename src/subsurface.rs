@@ -0,0 +1,218 @@
+use super::color::*;
+use super::error::*;
+use super::float_cmp::consts::PI;
+use super::float_cmp::Float;
+use super::intersection::State;
+use super::material::Material;
+use super::object::Object;
+use super::ray::Ray;
+use super::sampling::*;
+use super::vec3::{Point3, Vector3};
+use super::world::*;
+
+// longest random walk below the surface before the light is considered absorbed
+const MAX_WALK_STEPS: u32 = 64;
+
+// Light scattered below the surface of skin, wax, marble and the like,
+// set through `Material::subsurface`. It is estimated with random walks
+// through the inside of the object, so the object has to be closed.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Subsurface {
+    // share of the light surviving every scattering event, per channel
+    pub albedo: Color,
+    // average distance light travels between scattering events
+    pub mean_free_path: Float,
+    // random walks averaged per shaded point
+    pub samples: u32,
+}
+
+impl Subsurface {
+    pub fn new(albedo: Color, mean_free_path: Float) -> Self {
+        Self::try_new(albedo, mean_free_path).unwrap_or_else(|error| panic!("{}", error))
+    }
+    pub fn try_new(albedo: Color, mean_free_path: Float) -> Result<Self> {
+        Ok(Self {
+            albedo,
+            mean_free_path: check_positive("mean free path", mean_free_path)?,
+            samples: 16,
+        })
+    }
+}
+
+impl Material {
+    // Translucent material lit from below its surface, with a faint highlight
+    // on top. All diffuse light comes from the subsurface walks.
+    pub fn translucent(albedo: Color, mean_free_path: Float) -> Self {
+        Self {
+            color: albedo,
+            ambient: 0.05,
+            diffuse: 0.0,
+            specular: 0.3,
+            shininess: 50.0,
+            subsurface: Some(Subsurface::new(albedo, mean_free_path)),
+            ..Self::default()
+        }
+    }
+}
+
+impl World {
+    // Diffuse light leaving the surface at `state` after scattering inside the
+    // object. Every walk enters below the point, scatters after exponentially
+    // distributed steps until it crosses the surface again and picks up the
+    // light arriving at that exit, so light shines through thin parts and
+    // bleeds into shadows. Black for materials without subsurface scattering.
+    pub fn subsurface_lighting(&self, state: &State) -> Color {
+        let object = &self.objects[&state.obj_id];
+        let settings = match object.material().subsurface {
+            Some(settings) => settings,
+            None => return BLACK,
+        };
+        let n = settings.samples.max(1);
+        let mut rng = point_rng(state.point, 5);
+        let mut color = BLACK;
+        for _ in 0..n {
+            color = color + self.random_walk(object.as_ref(), state, &settings, &mut rng);
+        }
+        color * (1.0 / n as Float)
+    }
+    fn random_walk(
+        &self,
+        object: &dyn Object,
+        state: &State,
+        settings: &Subsurface,
+        rng: &mut Rng,
+    ) -> Color {
+        let mut position = state.under_point;
+        let mut direction = cosine_hemisphere(-state.normalv, rng);
+        let mut throughput = WHITE;
+        for _ in 0..MAX_WALK_STEPS {
+            let step = -settings.mean_free_path * (1.0 - rng.next_float()).ln();
+            let ray = Ray::new(position, direction).with_time(state.time);
+            // the inside of the object ends at the nearest crossing ahead
+            let exit = with_crossings(object, &ray, |crossings| {
+                crossings.iter().map(|x| x.t).find(|t| *t > 0.0)
+            });
+            match exit {
+                Some(t) if t <= step => {
+                    let point = ray.position(t);
+                    let normal = object.normal_at_time(point, state.time);
+                    let outside = point + normal * OCCLUSION_MARGIN;
+                    return throughput * self.irradiance(outside, normal, state.time);
+                }
+                Some(_) => {
+                    position = ray.position(step);
+                    direction = uniform_cone(direction, PI, rng);
+                    throughput = throughput * settings.albedo;
+                }
+                // numerically outside already
+                None => break,
+            }
+        }
+        BLACK
+    }
+    // light from `World::lights` arriving at a surface facing `normal`
+    fn irradiance(&self, point: Point3, normal: Vector3, time: Float) -> Color {
+        let mut color = BLACK;
+        for (light_index, light) in self.lights.iter().enumerate() {
+            let cos = (light.position - point).normalize().dot(normal);
+            if cos > 0.0 {
+                color = color
+                    + light.intensity * self.light_transmission(point, light_index, time) * cos;
+            }
+        }
+        color
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::intersection::Intersection;
+    use super::super::light::Light;
+    use super::super::matrix::Matrix;
+    use super::super::sphere::Sphere;
+    use super::*;
+
+    // wax ball with the light at `light`, seen from straight ahead
+    fn wax_ball(light: Point3, albedo: Color, mean_free_path: Float) -> (World, State) {
+        let mut world = World::new();
+        world.lights.push(Light::new(light, WHITE));
+        let mut ball = Sphere::new();
+        ball.material = Material::translucent(albedo, mean_free_path);
+        ball.material.subsurface.as_mut().unwrap().samples = 256;
+        let id = ball.get_id();
+        world.objects.insert(id, Box::new(ball));
+        let ray = Ray::new(Point3::new(0, 0, -5), Vector3::new(0, 0, 1));
+        let state = Intersection::new(4.0, id).compute_state(&ray, &world);
+        (world, state)
+    }
+    #[test]
+    fn opaque_materials_have_no_subsurface_light() {
+        let world = World::default();
+        let ray = Ray::new(Point3::new(0, 0, -5), Vector3::new(0, 0, 1));
+        let state = world
+            .intersect_with(&ray)
+            .hit()
+            .unwrap()
+            .compute_state(&ray, &world);
+        assert_eq!(world.subsurface_lighting(&state), BLACK);
+    }
+    #[test]
+    fn front_lit_translucent_surface_looks_diffuse() {
+        let (world, state) = wax_ball(Point3::new(0, 0, -10), WHITE, 0.01);
+        // a dense, white medium returns the light close to where it entered
+        let color = world.subsurface_lighting(&state);
+        assert!(color.red > 0.6 && color.red <= 1.0);
+        assert_eq!(color, world.subsurface_lighting(&state));
+    }
+    #[test]
+    fn light_shines_through_thin_objects() {
+        let behind = Point3::new(0, 0, 10);
+        let (world, state) = wax_ball(behind, WHITE, 0.5);
+        let glow = world.subsurface_lighting(&state);
+        assert!(glow.red > 0.02);
+        // a denser medium lets less through from the back
+        let (world, state) = wax_ball(behind, WHITE, 0.05);
+        assert!(world.subsurface_lighting(&state).red < glow.red);
+    }
+    #[test]
+    fn albedo_tints_scattered_light() {
+        let (world, state) = wax_ball(Point3::new(0, 0, -10), Color::new(1.0, 0.5, 0.2), 0.1);
+        let color = world.subsurface_lighting(&state);
+        assert!(color.red > color.green && color.green > color.blue);
+    }
+    #[test]
+    fn subsurface_light_bleeds_into_shadows() {
+        // a slab lit from above with a blocker shadowing the top around the origin
+        let mut world = World::new();
+        world.lights.push(Light::new(Point3::new(0, 10, 0), WHITE));
+        // planes have no inside, the slab is a flattened sphere
+        let mut slab = Sphere::new();
+        slab.set_transform(Matrix::scaling(10.0, 1.0, 10.0));
+        slab.material = Material::translucent(WHITE, 0.2);
+        let id = slab.get_id();
+        let mut blocker = Sphere::new();
+        blocker.set_transform(Matrix::translation(0.0, 3.0, 0.0) * &Matrix::scaling(0.3, 0.1, 0.3));
+        world.objects.insert(id, Box::new(slab));
+        world.objects.insert(blocker.get_id(), Box::new(blocker));
+        let ray = Ray::new(Point3::new(0, 5, 0), Vector3::new(0, -1, 0));
+        let state = Intersection::new(4.0, id).compute_state(&ray, &world);
+        assert!(world.is_shadowed(state.over_point, 0));
+        assert!(world.subsurface_lighting(&state).red > 0.0);
+    }
+    #[test]
+    #[should_panic]
+    fn mean_free_path_must_be_positive() {
+        Subsurface::new(WHITE, 0.0);
+    }
+    #[test]
+    fn try_subsurface() {
+        assert_eq!(
+            Subsurface::try_new(WHITE, -1.0),
+            Err(Error::ValueOutOfRange {
+                name: "mean free path",
+                value: -1.0
+            })
+        );
+        assert!(Subsurface::try_new(WHITE, 0.1).is_ok());
+    }
+}
@@ -0,0 +1,229 @@
+use super::color::Color;
+use super::float_cmp::Float;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Tile {
+    // top left pixel of the tile
+    pub col: u32,
+    pub row: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TileOrder {
+    // left to right, top to bottom
+    RowMajor,
+    // outwards from the center of the image
+    Spiral,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct TileSettings {
+    pub tile_size: u32,
+    pub order: TileOrder,
+    // only pixels inside this rectangle are rendered, whole image if None
+    pub crop: Option<Tile>,
+}
+
+impl Default for TileSettings {
+    fn default() -> Self {
+        Self {
+            tile_size: 32,
+            order: TileOrder::Spiral,
+            crop: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderedTile {
+    pub tile: Tile,
+    // row major colors of the pixels inside the tile
+    pub pixels: Vec<Color>,
+    // number of samples averaged into every pixel
+    pub samples: u32,
+}
+
+// Shared flag used to abort a render from another thread or from the tile callback.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+impl Tile {
+    pub fn new(col: u32, row: u32, width: u32, height: u32) -> Self {
+        Self {
+            col,
+            row,
+            width,
+            height,
+        }
+    }
+    pub fn n_pixels(&self) -> usize {
+        (self.width as usize)
+            .checked_mul(self.height as usize)
+            .expect("tile has too many pixels")
+    }
+    // (col, row) of every pixel in the tile, row major
+    pub fn pixels(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        (self.row..self.row + self.height)
+            .flat_map(move |row| (self.col..self.col + self.width).map(move |col| (col, row)))
+    }
+    pub fn contains(&self, col: u32, row: u32) -> bool {
+        col >= self.col && col < self.col_end() && row >= self.row && row < self.row_end()
+    }
+    // overlapping part of two rectangles, None if they are disjoint
+    pub fn intersect(&self, other: &Self) -> Option<Self> {
+        let col = self.col.max(other.col);
+        let row = self.row.max(other.row);
+        let col_end = self.col_end().min(other.col_end());
+        let row_end = self.row_end().min(other.row_end());
+        if col < col_end && row < row_end {
+            Some(Self::new(col, row, col_end - col, row_end - row))
+        } else {
+            None
+        }
+    }
+    // first column and row past the tile, clamped so decoded tiles that reach
+    // past u32::MAX don't overflow
+    fn col_end(&self) -> u32 {
+        self.col.saturating_add(self.width)
+    }
+    fn row_end(&self) -> u32 {
+        self.row.saturating_add(self.height)
+    }
+    // splits a `width` x `height` image, or its crop window, into tiles
+    // of at most `tile_size` pixels a side
+    pub fn split(width: u32, height: u32, settings: &TileSettings) -> Vec<Self> {
+        assert!(settings.tile_size > 0, "tile size has to be non zero");
+        let image = Self::new(0, 0, width, height);
+        let region = match settings.crop {
+            Some(crop) => match crop.intersect(&image) {
+                Some(region) => region,
+                None => return Vec::new(),
+            },
+            None => image,
+        };
+        let size = settings.tile_size;
+        let mut tiles = Vec::new();
+        for row in (region.row..region.row + region.height).step_by(size as usize) {
+            for col in (region.col..region.col + region.width).step_by(size as usize) {
+                tiles.push(Self::new(
+                    col,
+                    row,
+                    size.min(region.col + region.width - col),
+                    size.min(region.row + region.height - row),
+                ));
+            }
+        }
+        if settings.order == TileOrder::Spiral {
+            let center_col = region.col as Float + region.width as Float / 2.0;
+            let center_row = region.row as Float + region.height as Float / 2.0;
+            // ring index around the center tile, then the angle within the ring
+            let key = |tile: &Self| {
+                let dx =
+                    (tile.col as Float + tile.width as Float / 2.0 - center_col) / size as Float;
+                let dy =
+                    (tile.row as Float + tile.height as Float / 2.0 - center_row) / size as Float;
+                let ring = dx.abs().max(dy.abs()).round();
+                (ring, dy.atan2(dx))
+            };
+            tiles.sort_by(|a, b| key(a).partial_cmp(&key(b)).unwrap());
+        }
+        tiles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn split_covers_image_once() {
+        for order in &[TileOrder::RowMajor, TileOrder::Spiral] {
+            let settings = TileSettings {
+                tile_size: 4,
+                order: *order,
+                crop: None,
+            };
+            let tiles = Tile::split(10, 7, &settings);
+            assert_eq!(tiles.len(), 6);
+            let mut covered = vec![0; 70];
+            for tile in &tiles {
+                for (col, row) in tile.pixels() {
+                    covered[(row * 10 + col) as usize] += 1;
+                }
+            }
+            assert!(covered.iter().all(|&n| n == 1));
+        }
+    }
+    #[test]
+    fn row_major_order() {
+        let settings = TileSettings {
+            tile_size: 4,
+            order: TileOrder::RowMajor,
+            crop: None,
+        };
+        let tiles = Tile::split(10, 7, &settings);
+        assert_eq!(tiles[0], Tile::new(0, 0, 4, 4));
+        assert_eq!(tiles[2], Tile::new(8, 0, 2, 4));
+        assert_eq!(tiles[5], Tile::new(8, 4, 2, 3));
+    }
+    #[test]
+    fn spiral_starts_at_center() {
+        let settings = TileSettings {
+            tile_size: 2,
+            order: TileOrder::Spiral,
+            crop: None,
+        };
+        let tiles = Tile::split(10, 10, &settings);
+        assert_eq!(tiles[0], Tile::new(4, 4, 2, 2));
+        // the first ring surrounds the center tile
+        for tile in &tiles[1..9] {
+            assert!(tile.col >= 2 && tile.col <= 6);
+            assert!(tile.row >= 2 && tile.row <= 6);
+        }
+    }
+    #[test]
+    fn split_crop_window() {
+        let settings = TileSettings {
+            tile_size: 4,
+            order: TileOrder::RowMajor,
+            crop: Some(Tile::new(3, 2, 6, 20)),
+        };
+        let tiles = Tile::split(10, 7, &settings);
+        assert_eq!(tiles[0], Tile::new(3, 2, 4, 4));
+        assert_eq!(tiles[1], Tile::new(7, 2, 2, 4));
+        assert_eq!(tiles[3], Tile::new(7, 6, 2, 1));
+        let n_pixels: usize = tiles.iter().map(Tile::n_pixels).sum();
+        assert_eq!(n_pixels, 6 * 5);
+
+        let outside = TileSettings {
+            crop: Some(Tile::new(20, 20, 5, 5)),
+            ..settings
+        };
+        assert!(Tile::split(10, 7, &outside).is_empty());
+    }
+    #[test]
+    fn cancel_token_is_shared() {
+        let token = CancelToken::new();
+        let clone = token.clone();
+        assert!(!token.is_cancelled());
+        clone.cancel();
+        assert!(token.is_cancelled());
+    }
+}
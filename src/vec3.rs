@@ -1,37 +1,42 @@
 use super::float_cmp;
+use super::float_cmp::Float;
+use super::simd::Float4;
 use std::ops::{Add, Div, Mul, Neg, Sub};
 
-#[derive(Debug, PartialEq, Copy, Clone)]
-pub enum Type {
-    Point,
-    Vector,
+// A position in space. Only the operations that make sense for positions are
+// defined, adding two points or taking the length of a point doesn't compile.
+#[derive(Debug, Copy, Clone)]
+pub struct Point3 {
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
 }
 
+// A direction or offset in space
 #[derive(Debug, Copy, Clone)]
-pub struct Vec3 {
-    pub x: f32,
-    pub y: f32,
-    pub z: f32,
-    pub t: Type,
+pub struct Vector3 {
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
 }
 
 pub trait Coordinate {
-    fn value(self) -> f32;
+    fn value(self) -> Float;
 }
 
-impl Coordinate for f32 {
-    fn value(self) -> f32 {
+impl Coordinate for Float {
+    fn value(self) -> Float {
         self
     }
 }
 impl Coordinate for i32 {
-    fn value(self) -> f32 {
-        self as f32
+    fn value(self) -> Float {
+        self as Float
     }
 }
 
-impl Vec3 {
-    pub fn point<T>(x: T, y: T, z: T) -> Self
+impl Point3 {
+    pub fn new<T>(x: T, y: T, z: T) -> Self
     where
         T: Coordinate,
     {
@@ -39,10 +44,15 @@ impl Vec3 {
             x: x.value(),
             y: y.value(),
             z: z.value(),
-            t: Type::Point,
         }
     }
-    pub fn vector<T>(x: T, y: T, z: T) -> Self
+    pub fn origin() -> Self {
+        Self::new(0, 0, 0)
+    }
+}
+
+impl Vector3 {
+    pub fn new<T>(x: T, y: T, z: T) -> Self
     where
         T: Coordinate,
     {
@@ -50,307 +60,246 @@ impl Vec3 {
             x: x.value(),
             y: y.value(),
             z: z.value(),
-            t: Type::Vector,
         }
     }
-    pub fn new(v: &[f32]) -> Self {
-        assert_eq!(
-            v.len(),
-            4,
-            "vector length doesn't equal 4, can't create Vec3"
-        );
-        let vec_type: Type = if float_cmp::equal(v[3], 0.0) {
-            Type::Vector
-        } else {
-            Type::Point
-        };
-        Self {
-            x: v[0],
-            y: v[1],
-            z: v[2],
-            t: vec_type,
-        }
+    // x, y, z and a zero w lane for SIMD math
+    pub fn lanes(&self) -> Float4 {
+        Float4::new([self.x, self.y, self.z, 0.0])
+    }
+    pub fn from_lanes(lanes: Float4) -> Self {
+        let [x, y, z, _] = lanes.to_array();
+        Self { x, y, z }
+    }
+    pub fn magnitude(&self) -> Float {
+        self.magnitude_square().sqrt()
     }
-    pub fn as_vec(&self) -> Vec<f32> {
-        let w: f32 = if self.t == Type::Vector { 0.0 } else { 1.0 };
-        vec![self.x, self.y, self.z, w]
-    }
-    pub fn magnitude(&self) -> f32 {
-        assert_eq!(
-            self.t,
-            Type::Vector,
-            "Cannot call magnitude method on Point type"
-        );
-        (self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
-    }
-    pub fn magnitude_square(&self) -> f32 {
-        assert_eq!(
-            self.t,
-            Type::Vector,
-            "Cannot call magnitude_square method on Point type"
-        );
-        self.x * self.x + self.y * self.y + self.z * self.z
+    pub fn magnitude_square(&self) -> Float {
+        self.dot(*self)
     }
     pub fn normalize(&self) -> Self {
-        assert_eq!(
-            self.t,
-            Type::Vector,
-            "Cannot call normalize method on Point type"
-        );
-        let m = self.magnitude();
-        Self {
-            x: self.x / m,
-            y: self.y / m,
-            z: self.z / m,
-            t: self.t,
-        }
+        Self::from_lanes(self.lanes() / Float4::splat(self.magnitude()))
     }
     pub fn reflect(&self, normal: Self) -> Self {
-        assert_eq!(
-            self.t,
-            Type::Vector,
-            "Reflect method is undefined for Point type"
-        );
-        assert_eq!(normal.t, Type::Vector, "normal has to be Vector type");
         *self - normal * 2 * self.dot(normal)
     }
-    pub fn dot(&self, other: Self) -> f32 {
-        assert_eq!(
-            self.t,
-            Type::Vector,
-            "Cannot call dot product on two point types"
-        );
-        self.x * other.x + self.y * other.y + self.z * other.z
+    // Direction of a normalized ray bent by Snell's law when crossing a surface
+    // with `normal` facing against it, `eta` is the ratio n1 / n2 of the
+    // refractive indices. None on total internal reflection.
+    pub fn refract(&self, normal: Self, eta: Float) -> Option<Self> {
+        let cos_i = -self.dot(normal);
+        let sin2_t = eta * eta * (1.0 - cos_i * cos_i);
+        if sin2_t > 1.0 {
+            return None;
+        }
+        let cos_t = (1.0 - sin2_t).sqrt();
+        Some(*self * eta + normal * (eta * cos_i - cos_t))
+    }
+    pub fn dot(&self, other: Self) -> Float {
+        (self.lanes() * other.lanes()).sum()
     }
     pub fn cross(&self, other: Self) -> Self {
-        assert_eq!(
-            self.t,
-            Type::Vector,
-            "Cannot call cross product on two point types"
-        );
-        Self::vector(
-            self.y * other.z - self.z * other.y,
-            self.z * other.x - self.x * other.z,
-            self.x * other.y - self.y * other.x,
-        )
-    }
-    pub fn is_point(&self) -> bool {
-        self.t == Type::Point
-    }
-    pub fn is_vector(&self) -> bool {
-        self.t == Type::Vector
+        let (a, b) = (self.lanes(), other.lanes());
+        // z, x, y of the cross product, rotated into place
+        Self::from_lanes((a * b.yzxw() - a.yzxw() * b).yzxw())
     }
 }
 
 // operator overloading
 
-impl Add for Vec3 {
+// point + vector -> point
+impl Add<Vector3> for Point3 {
+    type Output = Self;
+
+    fn add(self, other: Vector3) -> Self {
+        Self::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+// vector + point -> point
+impl Add<Point3> for Vector3 {
+    type Output = Point3;
+
+    fn add(self, other: Point3) -> Point3 {
+        other + self
+    }
+}
+
+impl Add for Vector3 {
     type Output = Self;
 
     fn add(self, other: Self) -> Self {
-        let output_t;
-        if self.t == Type::Point && other.t == Type::Point {
-            panic!("Cannot add a point to a point.");
-        } else if self.t == Type::Point {
-            // point + vector -> point
-            output_t = Type::Point;
-        } else {
-            // vector + vector -> vector
-            output_t = other.t;
-        }
+        Self::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
 
-        Self {
-            x: self.x + other.x,
-            y: self.y + other.y,
-            z: self.z + other.z,
-            t: output_t,
-        }
+// p2 - p1 -> vector(p2-p1)
+impl Sub for Point3 {
+    type Output = Vector3;
+
+    fn sub(self, other: Self) -> Vector3 {
+        Vector3::new(self.x - other.x, self.y - other.y, self.z - other.z)
     }
 }
 
-impl Sub for Vec3 {
+// point - vector -> point
+impl Sub<Vector3> for Point3 {
     type Output = Self;
 
-    fn sub(self, other: Self) -> Self {
-        let output_t;
-        if self.t == Type::Point && other.t == Type::Point {
-            // p2 - p1 -> vector(p2-p1)
-            output_t = Type::Vector;
-        } else if self.t == Type::Point {
-            // point - vector -> point
-            output_t = Type::Point;
-        } else if self.t == Type::Vector && other.t == Type::Vector {
-            // vector - vector -> vector
-            output_t = Type::Vector;
-        } else {
-            panic!("Cannot subtract a point from a vector.");
-        }
+    fn sub(self, other: Vector3) -> Self {
+        Self::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
 
-        Self {
-            x: self.x - other.x,
-            y: self.y - other.y,
-            z: self.z - other.z,
-            t: output_t,
-        }
+impl Sub for Vector3 {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.x - other.x, self.y - other.y, self.z - other.z)
     }
 }
 
-impl Neg for Vec3 {
+impl Neg for Vector3 {
     type Output = Self;
 
     fn neg(self) -> Self {
-        Self {
-            x: -self.x,
-            y: -self.y,
-            z: -self.z,
-            t: self.t,
-        }
+        Self::new(-self.x, -self.y, -self.z)
     }
 }
 
-impl Mul<f32> for Vec3 {
+impl Mul<Float> for Vector3 {
     type Output = Self;
 
-    fn mul(self, rhs: f32) -> Self {
-        Self {
-            x: self.x * rhs.value(),
-            y: self.y * rhs.value(),
-            z: self.z * rhs.value(),
-            t: self.t,
-        }
+    fn mul(self, rhs: Float) -> Self {
+        Self::new(self.x * rhs, self.y * rhs, self.z * rhs)
     }
 }
 
-impl Mul<i32> for Vec3 {
+impl Mul<i32> for Vector3 {
     type Output = Self;
 
     fn mul(self, rhs: i32) -> Self {
-        Self {
-            x: self.x * rhs.value(),
-            y: self.y * rhs.value(),
-            z: self.z * rhs.value(),
-            t: self.t,
-        }
+        self * rhs.value()
     }
 }
 
-impl Div<f32> for Vec3 {
+impl Div<Float> for Vector3 {
     type Output = Self;
 
-    fn div(self, rhs: f32) -> Self {
-        if rhs.value() == 0.0 {
+    fn div(self, rhs: Float) -> Self {
+        if rhs == 0.0 {
             panic!("Cannot divide by zero-valued `scalar`!");
         }
-        Self {
-            x: self.x / rhs.value(),
-            y: self.y / rhs.value(),
-            z: self.z / rhs.value(),
-            t: self.t,
-        }
+        Self::new(self.x / rhs, self.y / rhs, self.z / rhs)
     }
 }
 
-impl Div<i32> for Vec3 {
+impl Div<i32> for Vector3 {
     type Output = Self;
 
     fn div(self, rhs: i32) -> Self {
-        if rhs.value() == 0.0 {
-            panic!("Cannot divide by zero-valued `scalar`!");
-        }
-        Self {
-            x: self.x / rhs.value(),
-            y: self.y / rhs.value(),
-            z: self.z / rhs.value(),
-            t: self.t,
-        }
+        self / rhs.value()
+    }
+}
+
+impl PartialEq for Point3 {
+    fn eq(&self, other: &Self) -> bool {
+        float_cmp::equal(self.x, other.x)
+            && float_cmp::equal(self.y, other.y)
+            && float_cmp::equal(self.z, other.z)
     }
 }
 
-impl PartialEq for Vec3 {
+impl PartialEq for Vector3 {
     fn eq(&self, other: &Self) -> bool {
         float_cmp::equal(self.x, other.x)
             && float_cmp::equal(self.y, other.y)
             && float_cmp::equal(self.z, other.z)
-            && self.t == other.t
     }
 }
 
 // unit tests
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn points_and_vectors_are_three_floats() {
+        let size = 3 * std::mem::size_of::<Float>();
+        assert_eq!(std::mem::size_of::<Point3>(), size);
+        assert_eq!(std::mem::size_of::<Vector3>(), size);
+    }
     #[test]
     fn adding_vec3() {
-        let p = Vec3::point(3, -2, 5);
-        let v = Vec3::vector(-2, 3, 1);
+        let p = Point3::new(3, -2, 5);
+        let v = Vector3::new(-2, 3, 1);
         let l = p + v;
-        assert_eq!(l, Vec3::point(1, 1, 6));
+        assert_eq!(l, Point3::new(1, 1, 6));
     }
     #[test]
     fn subtracting_vec3() {
-        let p1 = Vec3::point(3, 2, 1);
-        let p2 = Vec3::point(5, 6, 7);
-        let v = Vec3::vector(-2, -4, -6);
+        let p1 = Point3::new(3, 2, 1);
+        let p2 = Point3::new(5, 6, 7);
+        let v = Vector3::new(-2, -4, -6);
         assert_eq!(v, p1 - p2);
 
-        let v1 = Vec3::vector(5, 6, 7);
-        let p3 = Vec3::point(-2, -4, -6);
+        let v1 = Vector3::new(5, 6, 7);
+        let p3 = Point3::new(-2, -4, -6);
         assert_eq!(p3, p1 - v1);
 
-        let v2 = Vec3::vector(3, 2, 1);
+        let v2 = Vector3::new(3, 2, 1);
         assert_eq!(v, v2 - v1);
     }
     #[test]
     fn negating_vec3() {
-        let v1 = Vec3::vector(1, 2, 3);
+        let v1 = Vector3::new(1, 2, 3);
         let v2 = -v1;
-        assert_eq!(v2, Vec3::vector(-1, -2, -3));
+        assert_eq!(v2, Vector3::new(-1, -2, -3));
     }
     #[test]
     fn multiplying_with_scalar() {
-        let v1 = Vec3::vector(1, 2, 3);
+        let v1 = Vector3::new(1, 2, 3);
         let v2 = v1 * 3.0;
-        assert_eq!(v2, Vec3::vector(3, 6, 9));
+        assert_eq!(v2, Vector3::new(3, 6, 9));
     }
 
     #[test]
     fn dividing_by_scalar() {
-        let v1 = Vec3::vector(2, 4, 8);
+        let v1 = Vector3::new(2, 4, 8);
         let v2 = v1 / 2.0;
-        assert_eq!(v2, Vec3::vector(1, 2, 4));
+        assert_eq!(v2, Vector3::new(1, 2, 4));
     }
     #[test]
     fn magnitude_of_vector() {
-        let v1 = Vec3::vector(1, 0, 0);
+        let v1 = Vector3::new(1, 0, 0);
         assert_eq!(1.0, v1.magnitude());
-        let v2 = Vec3::vector(1, 2, 3);
-        let v3 = Vec3::vector(-1, -2, -3);
+        let v2 = Vector3::new(1, 2, 3);
+        let v3 = Vector3::new(-1, -2, -3);
         assert_eq!(v2.magnitude(), v3.magnitude());
     }
     #[test]
     fn normalize_vector() {
-        let v1 = Vec3::vector(4, 0, 0);
+        let v1 = Vector3::new(4, 0, 0);
         let v2 = v1.normalize();
-        assert_eq!(v2, Vec3::vector(1, 0, 0));
+        assert_eq!(v2, Vector3::new(1, 0, 0));
 
-        let v = Vec3::vector(1, 2, 3);
+        let v = Vector3::new(1, 2, 3);
         let uv = v.normalize();
 
         assert!(float_cmp::equal(1.0, uv.magnitude()));
     }
     #[test]
     fn dot_product() {
-        let v1 = Vec3::vector(1, 2, 3);
-        let v2 = Vec3::vector(2, 3, 4);
+        let v1 = Vector3::new(1, 2, 3);
+        let v2 = Vector3::new(2, 3, 4);
         assert!(float_cmp::equal(20.0, v1.dot(v2)));
     }
     #[test]
     fn cross_product() {
-        let v1 = Vec3::vector(1, 2, 3);
-        let v2 = Vec3::vector(2, 3, 4);
-        let v3 = Vec3::vector(-1, 2, -1);
-        let v4 = Vec3::vector(1, -2, 1);
+        let v1 = Vector3::new(1, 2, 3);
+        let v2 = Vector3::new(2, 3, 4);
+        let v3 = Vector3::new(-1, 2, -1);
+        let v4 = Vector3::new(1, -2, 1);
         assert_eq!(v3, v1.cross(v2));
         assert_eq!(v4, v2.cross(v1));
     }
@@ -362,14 +311,28 @@ mod tests {
     }
     #[test]
     fn reflecting_vector() {
-        let v = Vec3::vector(1, -1, 0);
-        let n = Vec3::vector(0, 1, 0);
+        let v = Vector3::new(1, -1, 0);
+        let n = Vector3::new(0, 1, 0);
         let r = v.reflect(n);
-        assert_eq!(r, Vec3::vector(1, 1, 0));
+        assert_eq!(r, Vector3::new(1, 1, 0));
 
-        let v = Vec3::vector(0, -1, 0);
-        let n = Vec3::vector(f32::sqrt(2.0) / 2.0, f32::sqrt(2.0) / 2.0, 0.0);
+        let v = Vector3::new(0, -1, 0);
+        let n = Vector3::new(Float::sqrt(2.0) / 2.0, Float::sqrt(2.0) / 2.0, 0.0);
         let r = v.reflect(n);
-        assert_eq!(r, Vec3::vector(1, 0, 0));
+        assert_eq!(r, Vector3::new(1, 0, 0));
+    }
+    #[test]
+    fn refracting_vector() {
+        let n = Vector3::new(0, 1, 0);
+        let v = Vector3::new(0, -1, 0);
+        assert_eq!(v.refract(n, 1.5), Some(v));
+        // 45 degrees into glass and back out again
+        let v = Vector3::new(1, -1, 0).normalize();
+        let inside = v.refract(n, 1.0 / 1.5).unwrap();
+        assert!((inside.x - Float::sqrt(0.5) / 1.5).abs() < 1.0e-6);
+        assert!(float_cmp::equal(inside.magnitude(), 1.0));
+        assert_eq!(inside.refract(n, 1.5), Some(v));
+        // too flat to leave the glass
+        assert_eq!(v.refract(n, 1.5), None);
     }
 }
@@ -0,0 +1,292 @@
+use super::color::*;
+use super::error::*;
+use super::float_cmp::Float;
+use super::intersection::*;
+use super::ray::Ray;
+use super::world::*;
+
+// Exponential fog filling the whole scene, applied along camera and
+// reflected rays. Surfaces fade into `color` with distance.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Fog {
+    pub color: Color,
+    // fraction of the light replaced by fog per unit of distance
+    pub density: Float,
+}
+
+// Homogeneous participating medium like smoke or murky water filling a closed
+// object, set through `Material::medium`. The surface itself is invisible,
+// light passing through the inside is absorbed and scattered, light from
+// `World::lights` scattered towards the eye makes shafts of light visible.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Medium {
+    // tint of the scattered light
+    pub color: Color,
+    // fractions of the light absorbed and scattered per unit of distance
+    pub absorption: Float,
+    pub scattering: Float,
+    // points along a ray the scattered light is gathered at
+    pub steps: u32,
+}
+
+impl Fog {
+    pub fn new(color: Color, density: Float) -> Self {
+        Self::try_new(color, density).unwrap_or_else(|error| panic!("{}", error))
+    }
+    pub fn try_new(color: Color, density: Float) -> Result<Self> {
+        Ok(Self {
+            color,
+            density: check_range("fog density", density, 0.0..=Float::INFINITY)?,
+        })
+    }
+    // share of the light making it through `distance` of fog
+    pub fn transmittance(&self, distance: Float) -> Float {
+        if self.density <= 0.0 {
+            1.0
+        } else {
+            (-self.density * distance).exp()
+        }
+    }
+}
+
+impl Medium {
+    pub fn new(color: Color, absorption: Float, scattering: Float) -> Self {
+        Self::try_new(color, absorption, scattering).unwrap_or_else(|error| panic!("{}", error))
+    }
+    pub fn try_new(color: Color, absorption: Float, scattering: Float) -> Result<Self> {
+        Ok(Self {
+            color,
+            absorption: check_range("absorption", absorption, 0.0..=Float::INFINITY)?,
+            scattering: check_range("scattering", scattering, 0.0..=Float::INFINITY)?,
+            steps: 16,
+        })
+    }
+    // share of the light making it through `distance` of the medium
+    pub fn transmittance(&self, distance: Float) -> Float {
+        (-(self.absorption + self.scattering) * distance).exp()
+    }
+}
+
+impl World {
+    // true for objects that only bound a medium
+    pub fn is_medium(&self, obj_id: usize) -> bool {
+        self.objects[&obj_id].material().medium.is_some()
+    }
+    // nearest crossing with a surface that isn't the boundary of a medium
+    pub fn surface_hit(&self, xs: &Intersections) -> Option<Intersection> {
+        if !xs.crossings.iter().any(|x| self.is_medium(x.obj_id)) {
+            return xs.hit();
+        }
+        let mut surfaces = Intersections::new();
+        surfaces.crossings = xs
+            .crossings
+            .iter()
+            .filter(|x| !self.is_medium(x.obj_id))
+            .copied()
+            .collect();
+        surfaces.hit()
+    }
+    // Stretches of the ray from 0 up to `max_distance` inside media, nearest
+    // first. Crossings of every medium object pair up into entry and exit.
+    pub fn medium_segments(
+        &self,
+        xs: &Intersections,
+        max_distance: Float,
+    ) -> Vec<(Float, Float, Medium)> {
+        let mut crossings: Vec<(usize, Vec<Float>)> = Vec::new();
+        for x in &xs.crossings {
+            if !self.is_medium(x.obj_id) {
+                continue;
+            }
+            match crossings.iter_mut().find(|(id, _)| *id == x.obj_id) {
+                Some((_, ts)) => ts.push(x.t),
+                None => crossings.push((x.obj_id, vec![x.t])),
+            }
+        }
+        let mut segments = Vec::new();
+        for (obj_id, ts) in crossings {
+            let medium = self.objects[&obj_id].material().medium.unwrap();
+            for pair in ts.chunks_exact(2) {
+                let start = pair[0].max(0.0);
+                let end = pair[1].min(max_distance);
+                if start < end {
+                    segments.push((start, end, medium));
+                }
+            }
+        }
+        segments.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        segments
+    }
+    // Media and fog between the origin of `ray` and the surface at `distance`,
+    // as the share of the surface's light getting through and the light
+    // scattered towards the origin on the way.
+    pub fn volumes_along(&self, ray: &Ray, xs: &Intersections, distance: Float) -> (Color, Color) {
+        let scale = ray.direction.magnitude();
+        let mut transmittance = WHITE;
+        let mut scattered = BLACK;
+        // from the far end towards the origin
+        for (start, end, medium) in self.medium_segments(xs, distance).iter().rev() {
+            let through = medium.transmittance((end - start) * scale);
+            transmittance = transmittance * through;
+            scattered = scattered * through + self.in_scattering(ray, *start, *end, medium);
+        }
+        if let Some(fog) = &self.fog {
+            let through = fog.transmittance(distance * scale);
+            transmittance = transmittance * through;
+            scattered = scattered * through + fog.color * (1.0 - through);
+        }
+        (transmittance, scattered)
+    }
+    // Light from `World::lights` scattered towards the origin of `ray` between
+    // `start` and `end`, gathered at evenly spaced points with a random offset.
+    // Scattering is isotropic and light intensities are scaled by PI like in
+    // `Material::lighting`.
+    fn in_scattering(&self, ray: &Ray, start: Float, end: Float, medium: &Medium) -> Color {
+        if medium.scattering <= 0.0 || self.lights.is_empty() {
+            return BLACK;
+        }
+        let scale = ray.direction.magnitude();
+        let steps = medium.steps.max(1);
+        let dt = (end - start) / steps as Float;
+        let offset = point_rng(ray.position(start), 4).next_float();
+        let mut color = BLACK;
+        for i in 0..steps {
+            let t = start + (i as Float + offset) * dt;
+            let point = ray.position(t);
+            let to_origin = medium.transmittance((t - start) * scale);
+            for (light_index, light) in self.lights.iter().enumerate() {
+                color = color
+                    + light.intensity
+                        * self.light_transmission(point, light_index, ray.time)
+                        * to_origin;
+            }
+        }
+        color * medium.color * (medium.scattering * dt * scale / 4.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::background::Background;
+    use super::super::cube::Cube;
+    use super::super::float_cmp::consts::FRAC_PI_2;
+    use super::super::light::Light;
+    use super::super::matrix::Matrix;
+    use super::super::object::Object;
+    use super::super::plane::Plane;
+    use super::super::sphere::Sphere;
+    use super::super::vec3::{Point3, Vector3};
+    use super::*;
+
+    #[test]
+    fn fog_fades_surfaces() {
+        let mut world = World::new();
+        let mut wall = Plane::new();
+        wall.set_transform(Matrix::translation(0.0, 0.0, 10.0) * &Matrix::rotation_x(FRAC_PI_2));
+        wall.material.ambient = 1.0;
+        wall.material.diffuse = 0.0;
+        wall.material.specular = 0.0;
+        world.objects.insert(wall.get_id(), Box::new(wall));
+        world.background = Background::Solid(RED);
+        let ray = Ray::new(Point3::new(0, 0, 0), Vector3::new(0, 0, 1));
+        world.lights.push(Light::new(Point3::new(0, 0, 0), WHITE));
+        assert!(world.color_at(&ray).equals(WHITE));
+        world.fog = Some(Fog::new(BLUE, 0.1));
+        let e = (-(1.0 as Float)).exp();
+        assert!(world.color_at(&ray).equals(Color::new(e, e, 1.0)));
+        // missed rays disappear in the fog completely
+        let up = Ray::new(Point3::new(0, 0, 0), Vector3::new(0, 1, 0));
+        assert!(world.color_at(&up).equals(BLUE));
+    }
+    #[test]
+    fn no_fog_without_density() {
+        let fog = Fog::new(WHITE, 0.0);
+        assert_eq!(fog.transmittance(Float::INFINITY), 1.0);
+    }
+    #[test]
+    #[should_panic]
+    fn negative_fog_density() {
+        Fog::new(WHITE, -1.0);
+    }
+    #[test]
+    fn negative_coefficients() {
+        assert!(Fog::try_new(WHITE, Float::NAN).is_err());
+        assert_eq!(
+            Medium::try_new(WHITE, 0.5, -0.5),
+            Err(Error::ValueOutOfRange {
+                name: "scattering",
+                value: -0.5
+            })
+        );
+        assert!(Medium::try_new(WHITE, 0.0, 0.0).is_ok());
+    }
+    // light straight above a cube of smoke
+    fn smoke_box(medium: Medium) -> (World, usize) {
+        let mut world = World::new();
+        world.lights.push(Light::new(Point3::new(0, 10, 0), WHITE));
+        let mut smoke = Cube::new();
+        smoke.material.medium = Some(medium);
+        let id = smoke.get_id();
+        world.objects.insert(id, Box::new(smoke));
+        (world, id)
+    }
+    #[test]
+    fn media_are_not_surfaces() {
+        let (world, id) = smoke_box(Medium::new(WHITE, 0.5, 0.0));
+        let ray = Ray::new(Point3::new(0, 0, -5), Vector3::new(0, 0, 1));
+        let xs = world.intersect_with(&ray);
+        assert!(world.is_medium(id));
+        assert!(world.surface_hit(&xs).is_none());
+        let segments = world.medium_segments(&xs, Float::INFINITY);
+        assert_eq!(segments.len(), 1);
+        assert_eq!((segments[0].0, segments[0].1), (4.0, 6.0));
+        // starting inside the medium
+        let ray = Ray::new(Point3::new(0, 0, 0), Vector3::new(0, 0, 1));
+        let xs = world.intersect_with(&ray);
+        let segments = world.medium_segments(&xs, 0.5);
+        assert_eq!((segments[0].0, segments[0].1), (0.0, 0.5));
+    }
+    #[test]
+    fn absorbing_medium_darkens_the_background() {
+        let (mut world, _) = smoke_box(Medium::new(WHITE, 0.5, 0.0));
+        world.background = Background::Solid(WHITE);
+        let ray = Ray::new(Point3::new(0, 0, -5), Vector3::new(0, 0, 1));
+        let e = (-(1.0 as Float)).exp();
+        assert!(world.color_at(&ray).equals(Color::new(e, e, e)));
+        // and the shadows it casts
+        let below = Point3::new(0, -5, 0);
+        assert!(world
+            .light_transmission(below, 0, 0.0)
+            .equals(Color::new(e, e, e)));
+    }
+    #[test]
+    fn scattering_medium_shows_light_shafts() {
+        let (mut world, _) = smoke_box(Medium::new(WHITE, 0.0, 0.2));
+        let ray = Ray::new(Point3::new(0, 0, -5), Vector3::new(0, 0, 1));
+        let lit = world.color_at(&ray);
+        assert!(lit.red > 0.01 && lit.red < 1.0);
+        assert_eq!(lit.red, lit.green);
+        // an opaque slab between the light and the smoke casts a shaft of shadow
+        let mut slab = Cube::new();
+        slab.set_transform(Matrix::translation(0.0, 3.0, 0.0) * &Matrix::scaling(0.5, 0.1, 5.0));
+        world.objects.insert(slab.get_id(), Box::new(slab));
+        let shadowed = world.color_at(&ray);
+        assert!(shadowed.red < lit.red * 0.8);
+        let beside = Ray::new(Point3::new(0.8, 0.0, -5.0), Vector3::new(0, 0, 1));
+        assert!(world.color_at(&beside).red > shadowed.red);
+    }
+    #[test]
+    fn objects_inside_media_are_seen_through_them() {
+        let (mut world, _) = smoke_box(Medium::new(WHITE, 0.5, 0.0));
+        let mut ball = Sphere::new();
+        ball.set_transform(Matrix::scaling(0.5, 0.5, 0.5));
+        ball.material.ambient = 1.0;
+        ball.material.diffuse = 0.0;
+        ball.material.specular = 0.0;
+        world.objects.insert(ball.get_id(), Box::new(ball));
+        let ray = Ray::new(Point3::new(0, 0, -5), Vector3::new(0, 0, 1));
+        // half a unit of medium in front of the ball
+        let e = (-(0.25 as Float)).exp();
+        assert!(world.color_at(&ray).equals(Color::new(e, e, e)));
+    }
+}
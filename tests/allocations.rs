@@ -0,0 +1,135 @@
+#![allow(non_snake_case)]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use Handmade3DRenderer::*;
+
+// counts the allocations of the current thread, tests run on several threads
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations_of<F: FnMut()>(mut f: F) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn hit_queries_do_not_allocate() {
+    let mut world = World::default();
+    let mut floor = Plane::new();
+    floor.set_transform(Matrix::translation(0.0, -1.0, 0.0));
+    world.objects.insert(floor.get_id(), Box::new(floor));
+    let rays = [
+        Ray::new(Point3::new(0, 0, -5), Vector3::new(0, 0, 1)),
+        Ray::new(Point3::new(0, 0, -5), Vector3::new(0, 1, 0)),
+        Ray::new(Point3::new(0, 5, 0), Vector3::new(0, -1, 0)),
+    ];
+    // the first rays fill the buffers and the cached inverse transforms
+    for ray in &rays {
+        world.closest_hit(ray, RayKind::Camera, 0.0, Float::INFINITY);
+    }
+    let allocations = allocations_of(|| {
+        for _ in 0..100 {
+            for ray in &rays {
+                world.closest_hit(ray, RayKind::Camera, 0.0, Float::INFINITY);
+                world.any_hit(ray, RayKind::Shadow, 0.0, 10.0);
+                world.is_blocked(ray, 10.0);
+            }
+            world.is_shadowed(Point3::new(0, 10, 0), 0);
+            world.is_shadowed(Point3::new(-2, 2, -2), 0);
+            world.is_shadowed(Point3::new(10, -10, 10), 0);
+        }
+    });
+    assert_eq!(allocations, 0);
+    // while collecting every crossing allocates the returned list
+    assert!(allocations_of(|| drop(world.intersect_with(&rays[0]))) > 0);
+}
+
+#[test]
+fn shading_does_not_allocate() {
+    let world = World::default();
+    let hit = Ray::new(Point3::new(0, 0, -5), Vector3::new(0, 0, 1));
+    let miss = Ray::new(Point3::new(0, 0, -5), Vector3::new(0, 1, 0));
+    world.color_at(&hit);
+    let allocations = allocations_of(|| {
+        for _ in 0..100 {
+            world.color_at(&hit);
+            world.color_at(&miss);
+            // behind both spheres, seen from the light
+            assert!(world.is_shadowed(Point3::new(2, -2, 2), 0));
+        }
+    });
+    assert_eq!(allocations, 0);
+}
+
+#[test]
+fn intersecting_into_a_buffer_does_not_allocate() {
+    let sphere = Sphere::new();
+    let ray = Ray::new(Point3::new(0, 0, -5), Vector3::new(0, 0, 1));
+    let mut buffer = Intersections::new();
+    sphere.intersect_into(&ray, &mut buffer);
+    let allocations = allocations_of(|| {
+        for _ in 0..100 {
+            buffer.crossings.clear();
+            sphere.intersect_into(&ray, &mut buffer);
+        }
+    });
+    assert_eq!(allocations, 0);
+    assert_eq!(buffer.len(), 2);
+}
+
+#[test]
+fn packets_do_not_allocate() {
+    let world = World::default();
+    let rays = [
+        Ray::new(Point3::new(0, 0, -5), Vector3::new(0, 0, 1)),
+        Ray::new(Point3::new(0, 0, -5), Vector3::new(0, 1, 0)),
+        Ray::new(Point3::new(0.5, 0.0, -5.0), Vector3::new(0, 0, 1)),
+        Ray::new(Point3::new(0, 5, 0), Vector3::new(0, -1, 0)),
+    ];
+    let packet = RayPacket::new(&rays);
+    world.color_at_packet(&packet);
+    let allocations = allocations_of(|| {
+        for _ in 0..100 {
+            world.closest_hit_packet(&packet, RayKind::Camera, 0.0, Float::INFINITY);
+            world.color_at_packet(&packet);
+        }
+    });
+    assert_eq!(allocations, 0);
+}
+
+#[test]
+fn subsurface_walks_do_not_allocate() {
+    let mut world = World::new();
+    world.lights.push(Light::new(Point3::new(0, 0, -10), WHITE));
+    let mut ball = Sphere::new();
+    ball.material = Material::translucent(WHITE, 0.1);
+    let id = ball.get_id();
+    world.objects.insert(id, Box::new(ball));
+    let ray = Ray::new(Point3::new(0, 0, -5), Vector3::new(0, 0, 1));
+    let state = Intersection::new(4.0, id).compute_state(&ray, &world);
+    world.subsurface_lighting(&state);
+    let allocations = allocations_of(|| {
+        for _ in 0..10 {
+            world.subsurface_lighting(&state);
+        }
+    });
+    assert_eq!(allocations, 0);
+}
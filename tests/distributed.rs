@@ -0,0 +1,93 @@
+#![allow(non_snake_case)]
+
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::thread;
+use Handmade3DRenderer::*;
+
+fn spawn_worker() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    thread::spawn(move || run_worker(&listener));
+    address
+}
+
+// accepts connections and drops them right away
+fn spawn_broken_worker() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            drop(stream);
+        }
+    });
+    address
+}
+
+// announces a float size neither build uses, then waits for the coordinator to hang up
+fn spawn_foreign_worker() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            stream.write_all(&16u32.to_le_bytes()).unwrap();
+            let _ = stream.read_to_end(&mut Vec::new());
+        }
+    });
+    address
+}
+
+fn scene() -> (Camera, World) {
+    let mut world = World::default();
+    let mut plane = Plane::new();
+    plane.set_transform(Matrix::translation(0.0, -1.0, 0.0));
+    plane.material.reflective = 0.3;
+    plane.material.pattern = Some(Pattern::checkers(vec![WHITE, BLACK]));
+    world.objects.insert(plane.get_id(), Box::new(plane));
+
+    let mut camera = Camera::new(24, 16, consts::PI / 2.0);
+    camera.transform = Matrix::view_transformation(
+        Point3::new(0, 1, -5),
+        Point3::new(0, 0, 0),
+        Vector3::new(0, 1, 0),
+    );
+    (camera, world)
+}
+
+#[test]
+fn render_on_several_workers() {
+    let (camera, world) = scene();
+    let workers = vec![spawn_worker(), spawn_worker(), spawn_worker()];
+    let settings = TileSettings {
+        tile_size: 5,
+        ..TileSettings::default()
+    };
+    let image = render_distributed(&camera, &world, &settings, &workers).unwrap();
+    assert_eq!(image.grid, camera.render(&world).grid);
+}
+
+#[test]
+fn survive_failing_worker() {
+    let (camera, world) = scene();
+    let workers = vec![spawn_broken_worker(), spawn_worker()];
+    let image = render_distributed(&camera, &world, &TileSettings::default(), &workers).unwrap();
+    assert_eq!(image.grid, camera.render(&world).grid);
+}
+
+#[test]
+fn fail_without_working_workers() {
+    let (camera, world) = scene();
+    let workers = vec![spawn_broken_worker()];
+    assert!(render_distributed(&camera, &world, &TileSettings::default(), &workers).is_err());
+    assert!(render_distributed(&camera, &world, &TileSettings::default(), &[]).is_err());
+}
+
+#[test]
+fn refuse_workers_of_other_precision() {
+    let (camera, world) = scene();
+    let workers = vec![spawn_foreign_worker()];
+    let error =
+        render_distributed(&camera, &world, &TileSettings::default(), &workers).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+}
@@ -1,4 +1,5 @@
 #![allow(non_snake_case)]
+#![allow(clippy::clone_on_copy)]
 
 use Handmade3DRenderer::*;
 
@@ -22,7 +23,7 @@ fn aggregating_intersections() {
 }
 #[test]
 fn ray_intersections() {
-    let ray = Ray::new(Vec3::point(0, 0, -5), Vec3::vector(0, 0, 1));
+    let ray = Ray::new(Point3::new(0, 0, -5), Vector3::new(0, 0, 1));
     let sphere = Sphere::new();
     let xs = ray.intersect(&sphere);
     assert_eq!(xs.len(), 2);
@@ -5,7 +5,7 @@ use Handmade3DRenderer::*;
 #[test]
 fn ray_parallel_to_plane() {
     let p = Plane::new();
-    let r = Ray::new(Vec3::point(0, 10, 0), Vec3::vector(0, 0, 1));
+    let r = Ray::new(Point3::new(0, 10, 0), Vector3::new(0, 0, 1));
     let xs = r.intersect(&p);
     assert_eq!(xs.len(), 0);
 }
@@ -13,7 +13,7 @@ fn ray_parallel_to_plane() {
 #[test]
 fn intersect_with_coplanar_ray() {
     let p = Plane::new();
-    let r = Ray::new(Vec3::point(0, 0, 0), Vec3::vector(0, 0, 1));
+    let r = Ray::new(Point3::new(0, 0, 0), Vector3::new(0, 0, 1));
     let xs = r.intersect(&p);
     assert_eq!(xs.len(), 0);
 }
@@ -22,7 +22,7 @@ fn intersect_with_coplanar_ray() {
 fn intersect_plane_from_above() {
     let p = Plane::new();
     let id = p.get_id();
-    let r = Ray::new(Vec3::point(0, 1, 0), Vec3::vector(0, -1, 0));
+    let r = Ray::new(Point3::new(0, 1, 0), Vector3::new(0, -1, 0));
     let xs = r.intersect(&p);
     assert_eq!(xs.len(), 1);
     assert!(float_cmp::equal(xs[0].t, 1.0));
@@ -33,7 +33,7 @@ fn intersect_plane_from_above() {
 fn intersect_plane_from_below() {
     let p = Plane::new();
     let id = p.get_id();
-    let r = Ray::new(Vec3::point(0, -1, 0), Vec3::vector(0, 1, 0));
+    let r = Ray::new(Point3::new(0, -1, 0), Vector3::new(0, 1, 0));
     let xs = r.intersect(&p);
     assert_eq!(xs.len(), 1);
     assert!(float_cmp::equal(xs[0].t, 1.0));
@@ -4,7 +4,7 @@ use Handmade3DRenderer::*;
 
 #[test]
 fn ray_sphere_intersection_two() {
-    let ray = Ray::new(Vec3::point(0, 0, -5), Vec3::vector(0, 0, 1));
+    let ray = Ray::new(Point3::new(0, 0, -5), Vector3::new(0, 0, 1));
     let sphere = Sphere::new();
     let xs: Intersections = ray.intersect(&sphere);
     assert_eq!(xs.len(), 2);
@@ -14,7 +14,7 @@ fn ray_sphere_intersection_two() {
 
 #[test]
 fn ray_sphere_tangent_intersection() {
-    let ray = Ray::new(Vec3::point(0, 1, -5), Vec3::vector(0, 0, 1));
+    let ray = Ray::new(Point3::new(0, 1, -5), Vector3::new(0, 0, 1));
     let sphere = Sphere::new();
     let xs: Intersections = ray.intersect(&sphere);
     assert_eq!(xs.len(), 2);
@@ -24,14 +24,14 @@ fn ray_sphere_tangent_intersection() {
 
 #[test]
 fn ray_sphere_no_intersection() {
-    let ray = Ray::new(Vec3::point(0, 2, -5), Vec3::vector(0, 0, 1));
+    let ray = Ray::new(Point3::new(0, 2, -5), Vector3::new(0, 0, 1));
     let sphere = Sphere::new();
     let xs: Intersections = ray.intersect(&sphere);
     assert_eq!(xs.len(), 0);
 }
 #[test]
 fn ray_originates_inside_sphere() {
-    let ray = Ray::new(Vec3::point(0, 0, 0), Vec3::vector(0, 0, 1));
+    let ray = Ray::new(Point3::new(0, 0, 0), Vector3::new(0, 0, 1));
     let sphere = Sphere::new();
     let xs: Intersections = ray.intersect(&sphere);
     assert_eq!(xs.len(), 2);
@@ -40,7 +40,7 @@ fn ray_originates_inside_sphere() {
 }
 #[test]
 fn sphere_behind_ray() {
-    let ray = Ray::new(Vec3::point(0, 0, 5), Vec3::vector(0, 0, 1));
+    let ray = Ray::new(Point3::new(0, 0, 5), Vector3::new(0, 0, 1));
     let sphere = Sphere::new();
     let xs: Intersections = ray.intersect(&sphere);
     assert_eq!(xs.len(), 2);
@@ -49,7 +49,7 @@ fn sphere_behind_ray() {
 }
 #[test]
 fn intersect_scaled_sphere() {
-    let ray = Ray::new(Vec3::point(0, 0, -5), Vec3::vector(0, 0, 1));
+    let ray = Ray::new(Point3::new(0, 0, -5), Vector3::new(0, 0, 1));
     let mut sphere = Sphere::new();
     sphere.set_transform(Matrix::scaling(2.0, 2.0, 2.0));
     let xs: Intersections = ray.intersect(&sphere);
@@ -59,7 +59,7 @@ fn intersect_scaled_sphere() {
 }
 #[test]
 fn intersect_translated_sphere() {
-    let ray = Ray::new(Vec3::point(0, 0, -5), Vec3::vector(0, 0, 1));
+    let ray = Ray::new(Point3::new(0, 0, -5), Vector3::new(0, 0, 1));
     let mut sphere = Sphere::new();
     sphere.set_transform(Matrix::translation(5.0, 0.0, 0.0));
     let xs: Intersections = ray.intersect(&sphere);